@@ -0,0 +1,322 @@
+//! The language features, computed from the text of a template and its syntax tree
+
+use crate::builtins::{self, Doc};
+use crate::position::to_range;
+use lsp_types::{
+    CompletionItem, CompletionItemKind, Diagnostic, DiagnosticSeverity, DocumentSymbol,
+    Documentation, Location, MarkupContent, MarkupKind, NumberOrString, Range, SymbolKind, Url,
+};
+use templar::ast::*;
+use templar::Templar;
+
+/// Syntax errors, or lint warnings if the template parses
+pub fn diagnostics(templar: &Templar, text: &str, errors: &[SyntaxError]) -> Vec<Diagnostic> {
+    if !errors.is_empty() {
+        return errors
+            .iter()
+            .map(|error| Diagnostic {
+                range: to_range(text, error.span),
+                severity: Some(DiagnosticSeverity::ERROR),
+                source: Some("templar".into()),
+                message: error.message.clone(),
+                ..Default::default()
+            })
+            .collect();
+    }
+    templar
+        .lint(text)
+        .unwrap_or_default()
+        .into_iter()
+        .map(|lint| Diagnostic {
+            range: to_range(text, lint.span),
+            severity: Some(DiagnosticSeverity::WARNING),
+            code: Some(NumberOrString::String(lint.kind.code().into())),
+            source: Some("templar".into()),
+            message: lint.message,
+            ..Default::default()
+        })
+        .collect()
+}
+
+/// The names that can be written at `offset`, depending on what comes before it in the tag
+pub fn completions(
+    templar: &Templar,
+    text: &str,
+    offset: usize,
+    macros: &[MacroInfo],
+) -> Vec<CompletionItem> {
+    let before = &text[..offset];
+    let open = before.rfind("{{").max(before.rfind("{%"));
+    let close = before.rfind("}}").max(before.rfind("%}"));
+    if open.is_none() || close > open {
+        return vec![];
+    }
+    let word_start = before
+        .trim_end_matches(|c: char| c.is_ascii_alphanumeric() || c == '_')
+        .len();
+    let rest = before[..word_start].trim_end();
+    let last_words: Vec<&str> = rest.split_whitespace().rev().take(2).collect();
+    match last_words.as_slice() {
+        _ if rest.ends_with('|') => names(templar.filter_names(), "filter", builtins::filter),
+        ["filter", tag, ..] if tag.starts_with("{%") => {
+            names(templar.filter_names(), "filter", builtins::filter)
+        }
+        ["is", ..] | ["not", "is", ..] => names(templar.test_names(), "test", builtins::test),
+        _ => {
+            let mut items = names(templar.function_names(), "function", builtins::function);
+            items.extend(macros.iter().map(|m| CompletionItem {
+                label: m.name.clone(),
+                kind: Some(CompletionItemKind::FUNCTION),
+                detail: Some(m.signature.clone()),
+                ..Default::default()
+            }));
+            items
+        }
+    }
+}
+
+fn names(names: Vec<&str>, kind: &str, doc: fn(&str) -> Option<Doc>) -> Vec<CompletionItem> {
+    names
+        .into_iter()
+        .map(|name| {
+            let doc = doc(name);
+            CompletionItem {
+                label: name.into(),
+                kind: Some(CompletionItemKind::FUNCTION),
+                detail: Some(doc.as_ref().map_or(name, |d| d.signature).to_string()),
+                documentation: doc.map(|d| {
+                    Documentation::MarkupContent(MarkupContent {
+                        kind: MarkupKind::Markdown,
+                        value: format!("Built in {}. {}", kind, d.description),
+                    })
+                }),
+                ..Default::default()
+            }
+        })
+        .collect()
+}
+
+/// A macro defined by a template
+#[derive(Debug, Clone)]
+pub struct MacroInfo {
+    pub name: String,
+    pub signature: String,
+    pub span: Span,
+}
+
+/// The macros defined anywhere in a template
+pub fn macros(ast: &Ast) -> Vec<MacroInfo> {
+    struct Macros<'a>(&'a str, Vec<MacroInfo>);
+
+    impl<'a> Visitor for Macros<'a> {
+        fn visit_macro(&mut self, block: &MacroBlock) {
+            let params: Vec<String> = block
+                .params
+                .iter()
+                .map(|p| match &p.default {
+                    Some(default) => format!("{}={}", p.name.name, default.span().slice(self.0)),
+                    None => p.name.name.clone(),
+                })
+                .collect();
+            self.1.push(MacroInfo {
+                name: block.name.name.clone(),
+                signature: format!("macro {}({})", block.name.name, params.join(", ")),
+                span: block.name.span,
+            });
+            walk_macro(self, block)
+        }
+    }
+
+    let mut macros = Macros(&ast.source, vec![]);
+    macros.visit_ast(ast);
+    macros.1
+}
+
+/// The name under the cursor that the server knows something about
+enum Target {
+    Filter(String, Span),
+    Function(String, Span),
+    Test(String, Span),
+    Template(String),
+}
+
+fn target_at(ast: &Ast, offset: usize) -> Option<Target> {
+    struct Finder(usize, Option<Target>);
+
+    impl Finder {
+        fn check(&mut self, span: Span, target: impl FnOnce() -> Target) {
+            if span.start <= self.0 && self.0 <= span.end {
+                self.1 = Some(target());
+            }
+        }
+    }
+
+    impl Visitor for Finder {
+        fn visit_filter(&mut self, filter: &FilterCall) {
+            let name = &filter.name;
+            self.check(name.span, || Target::Filter(name.name.clone(), name.span));
+            if let Some(args) = &filter.args {
+                walk_args(self, args)
+            }
+        }
+
+        fn visit_function(&mut self, call: &FunctionCall) {
+            let name = &call.name;
+            self.check(name.span, || Target::Function(name.name.clone(), name.span));
+            walk_args(self, &call.args)
+        }
+
+        fn visit_test(&mut self, test: &TestExpr) {
+            let name = &test.name;
+            self.check(name.span, || Target::Test(name.name.clone(), name.span));
+            walk_test(self, test)
+        }
+
+        fn visit_include(&mut self, block: &IncludeBlock) {
+            let name = &block.name;
+            self.check(name.span, || Target::Template(name.value.to_string()));
+            if let Some(vars) = &block.vars {
+                self.visit_expression(vars)
+            }
+        }
+
+        fn visit_extends(&mut self, block: &ExtendsBlock) {
+            let name = &block.name;
+            self.check(name.span, || Target::Template(name.value.to_string()));
+        }
+    }
+
+    let mut finder = Finder(offset, None);
+    finder.visit_ast(ast);
+    finder.1
+}
+
+/// Markdown describing the filter, function, macro or test at `offset`, and the span it covers
+pub fn hover(templar: &Templar, ast: &Ast, offset: usize) -> Option<(String, Span)> {
+    let (signature, description, span) = match target_at(ast, offset)? {
+        Target::Filter(name, span) if templar.filter_names().contains(&name.as_str()) => {
+            describe(builtins::filter(&name), &name, "filter", span)
+        }
+        Target::Test(name, span) if templar.test_names().contains(&name.as_str()) => {
+            describe(builtins::test(&name), &name, "test", span)
+        }
+        Target::Function(name, span) => match macros(ast).into_iter().find(|m| m.name == name) {
+            Some(m) => (
+                m.signature,
+                "Macro defined in this template".to_string(),
+                span,
+            ),
+            None if templar.function_names().contains(&name.as_str()) => {
+                describe(builtins::function(&name), &name, "function", span)
+            }
+            None => return None,
+        },
+        _ => return None,
+    };
+    Some((
+        format!("```templar\n{}\n```\n\n{}", signature, description),
+        span,
+    ))
+}
+
+fn describe(doc: Option<Doc>, name: &str, kind: &str, span: Span) -> (String, String, Span) {
+    match doc {
+        Some(doc) => (
+            doc.signature.into(),
+            format!("Built in {}. {}", kind, doc.description),
+            span,
+        ),
+        None => (name.into(), format!("Custom {}", kind), span),
+    }
+}
+
+/// The macro definition or included template referred to at `offset`
+pub fn definition(ast: &Ast, uri: &Url, offset: usize) -> Option<Location> {
+    match target_at(ast, offset)? {
+        Target::Function(name, _) => {
+            let found = macros(ast).into_iter().find(|m| m.name == name)?;
+            Some(Location::new(
+                uri.clone(),
+                to_range(&ast.source, found.span),
+            ))
+        }
+        Target::Template(name) => {
+            let path = uri.to_file_path().ok()?.parent()?.join(name);
+            if !path.is_file() {
+                return None;
+            }
+            Some(Location::new(
+                Url::from_file_path(path).ok()?,
+                Range::default(),
+            ))
+        }
+        _ => None,
+    }
+}
+
+/// The named blocks and macros of a template, nested as they are in the template
+pub fn symbols(ast: &Ast) -> Vec<DocumentSymbol> {
+    nested_symbols(&ast.source, &ast.nodes)
+}
+
+fn nested_symbols(text: &str, nodes: &[TemplateNode]) -> Vec<DocumentSymbol> {
+    let mut symbols = vec![];
+    for node in nodes {
+        match node {
+            TemplateNode::Block(n) => symbols.push(symbol(
+                text,
+                &n.name,
+                SymbolKind::NAMESPACE,
+                None,
+                n.span,
+                nested_symbols(text, &n.body),
+            )),
+            TemplateNode::Macro(n) => {
+                let params: Vec<&str> = n.params.iter().map(|p| p.name.name.as_str()).collect();
+                symbols.push(symbol(
+                    text,
+                    &n.name,
+                    SymbolKind::FUNCTION,
+                    Some(format!("({})", params.join(", "))),
+                    n.span,
+                    nested_symbols(text, &n.body),
+                ))
+            }
+            TemplateNode::If(n) => {
+                for branch in n.branches.iter() {
+                    symbols.extend(nested_symbols(text, &branch.body));
+                }
+                if let Some(otherwise) = &n.otherwise {
+                    symbols.extend(nested_symbols(text, &otherwise.body));
+                }
+            }
+            TemplateNode::For(n) => symbols.extend(nested_symbols(text, &n.body)),
+            TemplateNode::Scope(n) => symbols.extend(nested_symbols(text, &n.body)),
+            TemplateNode::Filter(n) => symbols.extend(nested_symbols(text, &n.body)),
+            TemplateNode::Call(n) => symbols.extend(nested_symbols(text, &n.body)),
+            _ => {}
+        }
+    }
+    symbols
+}
+
+#[allow(deprecated)]
+fn symbol(
+    text: &str,
+    name: &Ident,
+    kind: SymbolKind,
+    detail: Option<String>,
+    span: Span,
+    children: Vec<DocumentSymbol>,
+) -> DocumentSymbol {
+    DocumentSymbol {
+        name: name.name.clone(),
+        detail,
+        kind,
+        tags: None,
+        deprecated: None,
+        range: to_range(text, span),
+        selection_range: to_range(text, name.span),
+        children: Some(children),
+    }
+}
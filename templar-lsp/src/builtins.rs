@@ -0,0 +1,78 @@
+//! Hover documentation for the filters, functions and tests built in to Templar
+
+/// A built in with its signature and a short description
+pub struct Doc {
+    pub signature: &'static str,
+    pub description: &'static str,
+}
+
+macro_rules! docs {
+    ($fn_name:ident { $( $name:literal => $signature:literal : $description:literal ; )* }) => {
+        pub fn $fn_name(name: &str) -> Option<Doc> {
+            match name {
+                $( $name => Some(Doc { signature: $signature, description: $description }), )*
+                _ => None,
+            }
+        }
+    };
+}
+
+docs!(filter {
+    "require" => "require" : "Throws an error if the result is empty or null";
+    "default" => "default(value)" : "Replaces empty, null, or error types with the default value from the args";
+    "length" => "length" : "Returns the length of a string or array";
+    "exists" => "exists" : "True if the result is not empty";
+    "lower" => "lower" : "Lowercase the rendered result";
+    "upper" => "upper" : "Uppercase the rendered result";
+    "trim" => "trim" : "Trim whitespace off the rendered result";
+    "split" => "split(sep?)" : "Split a string into an array. Delimited by newline unless a delimiter is given";
+    "index" => "index(index)" : "Retrieve the int index from the array";
+    "join" => "join(sep?)" : "Join an array with the provided string. Defaults to newline";
+    "string" => "string" : "Forces the result into a string type, usually by rendering it";
+    "key" => "key(name)" : "Retrieve the value of the specified key from the dictionary";
+    "escape_html" => "escape_html" : "Render the result and escape HTML characters";
+    "e" => "e" : "Render the result and escape HTML characters, alias of escape_html";
+    "replace" => "replace(from, to)" : "Replace occurrences of a character in a string";
+    "truncate" => "truncate(size)" : "Shorten a string to at most the given number of bytes";
+    "indent" => "indent(int)" : "Indent every line after the first by the number of spaces specified. Blank lines are left alone";
+    "map" => "map(fn)" : "Call the lambda with each item of an array, returning an array of the results";
+    "select" => "select(fn?)" : "Keep the items of an array where the lambda returns true, or the items that are true if no lambda is given";
+    "reject" => "reject(fn?)" : "Remove the items of an array where the lambda returns true, or the items that are true if no lambda is given";
+    "sort_by" => "sort_by(fn)" : "Sort an array by the result of the lambda for each item";
+    "group_by" => "group_by(fn)" : "Group the items of an array into a map keyed by the result of the lambda for each item";
+    "any" => "any(fn?)" : "True if the lambda returns true for any item";
+    "all" => "all(fn?)" : "True if the lambda returns true for every item";
+    "reduce" => "reduce(fn, initial?)" : "Combine the items of an array with a two parameter lambda, e.g. `reduce((acc, x) => acc + x)`";
+    "yaml" => "yaml" : "Serialize the data into a YAML string";
+    "yml" => "yml" : "Serialize the data into a YAML string, alias of yaml";
+    "json" => "json(pretty?)" : "Serialize the data into a JSON string. Set pretty to true to print with indentation";
+    "base64" => "base64(str?)" : "Encode the result as Base64, or decode it if the argument is \"decode\"";
+});
+
+docs!(function {
+    "file" => "file(str)" : "Open file and read contents to a string";
+    "env" => "env(str)" : "Read the named environment variable";
+    "script" => "script(str, cwd=str?)" : "Execute the string as a shell script. Returns a map with keys \"stdout\", \"stderr\", \"status\"";
+    "command" => "command(str, str[]?, cwd=str?)" : "Execute the supplied command with the supplied arguments. Returns a map with keys \"stdout\", \"stderr\", \"status\"";
+    "json" => "json(str)" : "Parse the supplied JSON string into a map";
+    "yaml" => "yaml(str)" : "Parse the supplied YAML string into a map";
+    "yml" => "yml(str)" : "Parse the supplied YAML string into a map, alias of yaml";
+});
+
+docs!(test {
+    "defined" => "defined" : "True if the value has been set";
+    "undefined" => "undefined" : "True if the value has not been set";
+    "none" => "none" : "True if the value is null";
+    "number" => "number" : "True if the value is any numeric type";
+    "integer" => "integer" : "True if the value is a whole number";
+    "float" => "float" : "True if the value is a floating point number";
+    "string" => "string" : "True if the value is a string";
+    "boolean" => "boolean" : "True if the value is a boolean";
+    "mapping" => "mapping" : "True if the value is a map";
+    "sequence" => "sequence" : "True if the value is an array";
+    "iterable" => "iterable" : "True if the value is an array, map or string";
+    "empty" => "empty" : "True if the value is unset, null, an empty string, an empty array or an empty map";
+    "even" => "even" : "True if the value is an even integer";
+    "odd" => "odd" : "True if the value is an odd integer";
+    "divisibleby" => "divisibleby(int)" : "True if the value is an integer evenly divisible by the argument";
+});
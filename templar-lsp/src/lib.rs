@@ -0,0 +1,227 @@
+/*!
+A language server for Templar templates.
+
+The server speaks the Language Server Protocol and provides:
+
+- Diagnostics for every syntax error, and the warnings from `Templar::lint` once a template parses
+- Completion of filter, function, test and macro names
+- Hover documentation for filters, functions, tests and macros
+- Go to definition for macros and for included or extended templates
+- Document symbols for named blocks and macros
+
+The `templar-lsp` binary runs the server over stdio. The server can also be run on any
+`lsp_server::Connection`, e.g. an in-memory connection for testing.
+
+# Usage
+
+```no_run
+let (connection, io_threads) = lsp_server::Connection::stdio();
+templar_lsp::run(&connection, templar::Templar::default())?;
+io_threads.join()?;
+# Ok::<(), templar_lsp::Error>(())
+```
+*/
+
+mod analysis;
+mod builtins;
+mod position;
+
+use analysis::MacroInfo;
+use lsp_server::{Connection, ErrorCode, Message, Notification, Request, Response};
+use lsp_types::notification::{
+    DidChangeTextDocument, DidCloseTextDocument, DidOpenTextDocument,
+    Notification as NotificationType, PublishDiagnostics,
+};
+use lsp_types::request::{
+    Completion, DocumentSymbolRequest, GotoDefinition, HoverRequest, Request as RequestType,
+};
+use lsp_types::*;
+use std::collections::HashMap;
+use templar::ast::Ast;
+use templar::Templar;
+
+/// The error type returned by the server
+pub type Error = Box<dyn std::error::Error + Send + Sync>;
+
+/// The capabilities the server advertises to clients
+pub fn capabilities() -> ServerCapabilities {
+    ServerCapabilities {
+        text_document_sync: Some(TextDocumentSyncCapability::Kind(TextDocumentSyncKind::FULL)),
+        completion_provider: Some(CompletionOptions {
+            trigger_characters: Some(vec!["|".into(), " ".into()]),
+            ..Default::default()
+        }),
+        hover_provider: Some(HoverProviderCapability::Simple(true)),
+        definition_provider: Some(OneOf::Left(true)),
+        document_symbol_provider: Some(OneOf::Left(true)),
+        ..Default::default()
+    }
+}
+
+/// Run the server on a connection until the client shuts it down. The filters, functions and tests
+/// of `templar` are the ones offered for completion and checked for in diagnostics.
+pub fn run(connection: &Connection, templar: Templar) -> Result<(), Error> {
+    connection.initialize(serde_json::to_value(capabilities())?)?;
+    let mut server = Server {
+        templar,
+        documents: HashMap::new(),
+    };
+    for message in &connection.receiver {
+        match message {
+            Message::Request(request) => {
+                if connection.handle_shutdown(&request)? {
+                    return Ok(());
+                }
+                connection
+                    .sender
+                    .send(Message::Response(server.request(request)))?;
+            }
+            Message::Notification(notification) => {
+                if let Some(diagnostics) = server.notification(notification)? {
+                    connection.sender.send(Message::Notification(diagnostics))?;
+                }
+            }
+            Message::Response(_) => {}
+        }
+    }
+    Ok(())
+}
+
+/// An open template
+struct Document {
+    text: String,
+    /// The syntax tree of the current text, with error nodes for the tags that fail to parse
+    ast: Ast,
+    /// The macros of the current text, along with those of the previous version that are missing
+    /// from it because of syntax errors, for completion while editing
+    macros: Vec<MacroInfo>,
+}
+
+struct Server {
+    templar: Templar,
+    documents: HashMap<Url, Document>,
+}
+
+impl Server {
+    fn request(&self, request: Request) -> Response {
+        let id = request.id.clone();
+        let result = match request.method.as_str() {
+            Completion::METHOD => self.dispatch::<Completion>(request, Server::completion),
+            HoverRequest::METHOD => self.dispatch::<HoverRequest>(request, Server::hover),
+            GotoDefinition::METHOD => self.dispatch::<GotoDefinition>(request, Server::definition),
+            DocumentSymbolRequest::METHOD => {
+                self.dispatch::<DocumentSymbolRequest>(request, Server::symbols)
+            }
+            method => {
+                return Response::new_err(
+                    id,
+                    ErrorCode::MethodNotFound as i32,
+                    format!("Unsupported request {}", method),
+                )
+            }
+        };
+        match result {
+            Ok(value) => Response::new_ok(id, value),
+            Err(e) => Response::new_err(id, ErrorCode::InvalidParams as i32, e.to_string()),
+        }
+    }
+
+    fn dispatch<R: RequestType>(
+        &self,
+        request: Request,
+        handler: fn(&Self, R::Params) -> R::Result,
+    ) -> Result<serde_json::Value, Error> {
+        let params = serde_json::from_value(request.params)?;
+        Ok(serde_json::to_value(handler(self, params))?)
+    }
+
+    /// Update the open documents, returning the diagnostics to publish if a document changed
+    fn notification(&mut self, notification: Notification) -> Result<Option<Notification>, Error> {
+        let params = notification.params;
+        let (uri, version, text) = match notification.method.as_str() {
+            DidOpenTextDocument::METHOD => {
+                let params: DidOpenTextDocumentParams = serde_json::from_value(params)?;
+                let doc = params.text_document;
+                (doc.uri, Some(doc.version), doc.text)
+            }
+            DidChangeTextDocument::METHOD => {
+                let params: DidChangeTextDocumentParams = serde_json::from_value(params)?;
+                let doc = params.text_document;
+                match params.content_changes.into_iter().last() {
+                    Some(change) => (doc.uri, Some(doc.version), change.text),
+                    None => return Ok(None),
+                }
+            }
+            DidCloseTextDocument::METHOD => {
+                let params: DidCloseTextDocumentParams = serde_json::from_value(params)?;
+                self.documents.remove(&params.text_document.uri);
+                return Ok(Some(publish(params.text_document.uri, None, vec![])));
+            }
+            _ => return Ok(None),
+        };
+        let (ast, errors) = self.templar.parse_ast_recovering(&text);
+        let diagnostics = analysis::diagnostics(&self.templar, &text, &errors);
+        let mut macros = analysis::macros(&ast);
+        if let (false, Some(previous)) = (errors.is_empty(), self.documents.remove(&uri)) {
+            let missing: Vec<MacroInfo> = previous
+                .macros
+                .into_iter()
+                .filter(|old| macros.iter().all(|m| m.name != old.name))
+                .collect();
+            macros.extend(missing);
+        }
+        self.documents
+            .insert(uri.clone(), Document { text, ast, macros });
+        Ok(Some(publish(uri, version, diagnostics)))
+    }
+
+    /// The document and byte offset of a position
+    fn locate(&self, position: &TextDocumentPositionParams) -> Option<(&Document, usize)> {
+        let document = self.documents.get(&position.text_document.uri)?;
+        let offset = position::to_offset(&document.text, position.position);
+        Some((document, offset))
+    }
+
+    fn completion(&self, params: CompletionParams) -> Option<CompletionResponse> {
+        let (document, offset) = self.locate(&params.text_document_position)?;
+        let items = analysis::completions(&self.templar, &document.text, offset, &document.macros);
+        Some(CompletionResponse::Array(items))
+    }
+
+    fn hover(&self, params: HoverParams) -> Option<Hover> {
+        let (document, offset) = self.locate(&params.text_document_position_params)?;
+        let (value, span) = analysis::hover(&self.templar, &document.ast, offset)?;
+        Some(Hover {
+            contents: HoverContents::Markup(MarkupContent {
+                kind: MarkupKind::Markdown,
+                value,
+            }),
+            range: Some(position::to_range(&document.text, span)),
+        })
+    }
+
+    fn definition(&self, params: GotoDefinitionParams) -> Option<GotoDefinitionResponse> {
+        let position = &params.text_document_position_params;
+        let (document, offset) = self.locate(position)?;
+        let location = analysis::definition(&document.ast, &position.text_document.uri, offset)?;
+        Some(GotoDefinitionResponse::Scalar(location))
+    }
+
+    fn symbols(&self, params: DocumentSymbolParams) -> Option<DocumentSymbolResponse> {
+        let document = self.documents.get(&params.text_document.uri)?;
+        Some(DocumentSymbolResponse::Nested(analysis::symbols(
+            &document.ast,
+        )))
+    }
+}
+
+fn publish(uri: Url, version: Option<i32>, diagnostics: Vec<Diagnostic>) -> Notification {
+    Notification::new(
+        PublishDiagnostics::METHOD.into(),
+        PublishDiagnosticsParams {
+            uri,
+            diagnostics,
+            version,
+        },
+    )
+}
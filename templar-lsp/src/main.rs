@@ -0,0 +1,23 @@
+/*!
+Templar language server
+
+Speaks the Language Server Protocol over stdio, configure your editor to run `templar-lsp` for
+template files.
+*/
+
+use lsp_server::Connection;
+use templar::Templar;
+
+fn main() {
+    let (connection, io_threads) = Connection::stdio();
+    let result = templar_lsp::run(&connection, Templar::default());
+    drop(connection);
+    ::std::process::exit(match result.and_then(|_| Ok(io_threads.join()?)) {
+        Ok(_) => 0,
+        Err(e) => {
+            eprintln!("Failure!");
+            eprintln!("➜ {}", e);
+            2
+        }
+    })
+}
@@ -0,0 +1,35 @@
+//! Conversions between byte offsets in a template and LSP positions, which count UTF-16 code units
+
+use lsp_types::{Position, Range};
+use templar::ast::Span;
+
+pub fn to_position(text: &str, offset: usize) -> Position {
+    let before = &text[..offset.min(text.len())];
+    let line = before.matches('\n').count();
+    let line_start = before.rfind('\n').map_or(0, |i| i + 1);
+    let character = before[line_start..].encode_utf16().count();
+    Position::new(line as u32, character as u32)
+}
+
+pub fn to_offset(text: &str, position: Position) -> usize {
+    let mut line_start = 0;
+    for _ in 0..position.line {
+        match text[line_start..].find('\n') {
+            Some(i) => line_start += i + 1,
+            None => return text.len(),
+        }
+    }
+    let line = text[line_start..].split('\n').next().unwrap_or_default();
+    let mut units = 0;
+    for (i, c) in line.char_indices() {
+        if units >= position.character as usize {
+            return line_start + i;
+        }
+        units += c.len_utf16();
+    }
+    line_start + line.len()
+}
+
+pub fn to_range(text: &str, span: Span) -> Range {
+    Range::new(to_position(text, span.start), to_position(text, span.end))
+}
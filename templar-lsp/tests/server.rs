@@ -0,0 +1,315 @@
+use lsp_server::{Connection, Message, Notification, Request, RequestId};
+use lsp_types::notification::{
+    DidChangeTextDocument, DidOpenTextDocument, Exit, Initialized,
+    Notification as NotificationType, PublishDiagnostics,
+};
+use lsp_types::request::{
+    Completion, DocumentSymbolRequest, GotoDefinition, HoverRequest, Initialize,
+    Request as RequestType, Shutdown,
+};
+use lsp_types::*;
+use std::thread::JoinHandle;
+use templar::Templar;
+
+/// A client talking to a server running on another thread over an in-memory connection
+struct Client {
+    connection: Connection,
+    server: Option<JoinHandle<()>>,
+    next_id: i32,
+}
+
+impl Client {
+    fn start() -> Self {
+        let (server, connection) = Connection::memory();
+        let server = std::thread::spawn(move || {
+            templar_lsp::run(&server, Templar::default()).unwrap();
+        });
+        let mut client = Client {
+            connection,
+            server: Some(server),
+            next_id: 0,
+        };
+        let result = client.request::<Initialize>(InitializeParams::default());
+        assert!(result.capabilities.hover_provider.is_some());
+        client.notify::<Initialized>(InitializedParams {});
+        client
+    }
+
+    fn request<R: RequestType>(&mut self, params: R::Params) -> R::Result {
+        self.next_id += 1;
+        let id = RequestId::from(self.next_id);
+        let request = Request::new(id.clone(), R::METHOD.into(), params);
+        self.connection.sender.send(request.into()).unwrap();
+        loop {
+            match self.connection.receiver.recv().unwrap() {
+                Message::Response(response) if response.id == id => {
+                    assert!(response.error.is_none(), "{:?}", response.error);
+                    return serde_json::from_value(response.result.unwrap()).unwrap();
+                }
+                _ => continue,
+            }
+        }
+    }
+
+    fn notify<N: NotificationType>(&self, params: N::Params) {
+        let notification = Notification::new(N::METHOD.into(), params);
+        self.connection.sender.send(notification.into()).unwrap();
+    }
+
+    /// Wait for the next diagnostics the server publishes
+    fn diagnostics(&self) -> Vec<Diagnostic> {
+        loop {
+            if let Message::Notification(n) = self.connection.receiver.recv().unwrap() {
+                if n.method == PublishDiagnostics::METHOD {
+                    let params: PublishDiagnosticsParams =
+                        serde_json::from_value(n.params).unwrap();
+                    return params.diagnostics;
+                }
+            }
+        }
+    }
+
+    fn open(&self, uri: &Url, text: &str) -> Vec<Diagnostic> {
+        self.notify::<DidOpenTextDocument>(DidOpenTextDocumentParams {
+            text_document: TextDocumentItem::new(uri.clone(), "templar".into(), 1, text.into()),
+        });
+        self.diagnostics()
+    }
+
+    fn position(uri: &Url, line: u32, character: u32) -> TextDocumentPositionParams {
+        TextDocumentPositionParams::new(
+            TextDocumentIdentifier::new(uri.clone()),
+            Position::new(line, character),
+        )
+    }
+
+    fn completion(&mut self, uri: &Url, line: u32, character: u32) -> Vec<String> {
+        let result = self.request::<Completion>(CompletionParams {
+            text_document_position: Client::position(uri, line, character),
+            work_done_progress_params: Default::default(),
+            partial_result_params: Default::default(),
+            context: None,
+        });
+        match result {
+            Some(CompletionResponse::Array(items)) => {
+                items.into_iter().map(|item| item.label).collect()
+            }
+            other => panic!("Unexpected completion response {:?}", other),
+        }
+    }
+
+    fn hover(&mut self, uri: &Url, line: u32, character: u32) -> Option<String> {
+        let result = self.request::<HoverRequest>(HoverParams {
+            text_document_position_params: Client::position(uri, line, character),
+            work_done_progress_params: Default::default(),
+        });
+        result.map(|hover| match hover.contents {
+            HoverContents::Markup(markup) => markup.value,
+            other => panic!("Unexpected hover contents {:?}", other),
+        })
+    }
+
+    fn definition(&mut self, uri: &Url, line: u32, character: u32) -> Option<Location> {
+        let result = self.request::<GotoDefinition>(GotoDefinitionParams {
+            text_document_position_params: Client::position(uri, line, character),
+            work_done_progress_params: Default::default(),
+            partial_result_params: Default::default(),
+        });
+        result.map(|response| match response {
+            GotoDefinitionResponse::Scalar(location) => location,
+            other => panic!("Unexpected definition response {:?}", other),
+        })
+    }
+}
+
+impl Drop for Client {
+    fn drop(&mut self) {
+        if std::thread::panicking() {
+            return;
+        }
+        self.request::<Shutdown>(());
+        self.notify::<Exit>(());
+        self.server.take().unwrap().join().unwrap();
+    }
+}
+
+fn uri(name: &str) -> Url {
+    Url::from_file_path(std::env::temp_dir().join(name)).unwrap()
+}
+
+#[test]
+fn diagnostics() {
+    let client = Client::start();
+    let doc = uri("diagnostics.tmpl");
+
+    let diagnostics = client.open(&doc, "Hello\n{{ name | shout }}");
+    assert_eq!(diagnostics.len(), 1);
+    assert_eq!(diagnostics[0].severity, Some(DiagnosticSeverity::WARNING));
+    assert_eq!(
+        diagnostics[0].code,
+        Some(NumberOrString::String("unknown-filter".into()))
+    );
+    assert_eq!(
+        diagnostics[0].range,
+        Range::new(Position::new(1, 10), Position::new(1, 15))
+    );
+
+    client.notify::<DidChangeTextDocument>(DidChangeTextDocumentParams {
+        text_document: VersionedTextDocumentIdentifier::new(doc.clone(), 2),
+        content_changes: vec![TextDocumentContentChangeEvent {
+            range: None,
+            range_length: None,
+            text: "Hello\n{% if name %}".into(),
+        }],
+    });
+    let diagnostics = client.diagnostics();
+    assert_eq!(diagnostics.len(), 1);
+    assert_eq!(diagnostics[0].severity, Some(DiagnosticSeverity::ERROR));
+    assert_eq!(diagnostics[0].message, "`if` block is never closed");
+    assert_eq!(
+        diagnostics[0].range,
+        Range::new(Position::new(1, 0), Position::new(1, 13))
+    );
+
+    client.notify::<DidChangeTextDocument>(DidChangeTextDocumentParams {
+        text_document: VersionedTextDocumentIdentifier::new(doc.clone(), 3),
+        content_changes: vec![TextDocumentContentChangeEvent {
+            range: None,
+            range_length: None,
+            text: "{{ a + }}{{ b | }}".into(),
+        }],
+    });
+    let diagnostics: Vec<String> = client
+        .diagnostics()
+        .into_iter()
+        .map(|d| d.message)
+        .collect();
+    assert_eq!(
+        diagnostics,
+        vec![
+            "Invalid output tag, expected an expression",
+            "Invalid output tag, expected a name"
+        ]
+    );
+
+    client.notify::<DidChangeTextDocument>(DidChangeTextDocumentParams {
+        text_document: VersionedTextDocumentIdentifier::new(doc, 4),
+        content_changes: vec![TextDocumentContentChangeEvent {
+            range: None,
+            range_length: None,
+            text: "{{ name | upper }}".into(),
+        }],
+    });
+    assert_eq!(client.diagnostics(), vec![]);
+}
+
+#[test]
+fn completion() {
+    let mut client = Client::start();
+    let doc = uri("completion.tmpl");
+    client.open(
+        &doc,
+        "{% macro greet(name) %}Hi {{ name }}{% end macro %}\n{{ user | up }}\n{{ gr }}\n{{ 5 is e }}\ntext",
+    );
+
+    let filters = client.completion(&doc, 1, 12);
+    assert!(filters.contains(&"upper".to_string()));
+    assert!(!filters.contains(&"env".to_string()));
+
+    let functions = client.completion(&doc, 2, 5);
+    assert!(functions.contains(&"env".to_string()));
+    assert!(functions.contains(&"greet".to_string()));
+    assert!(!functions.contains(&"upper".to_string()));
+
+    let tests = client.completion(&doc, 3, 9);
+    assert!(tests.contains(&"even".to_string()));
+
+    assert_eq!(client.completion(&doc, 4, 2), Vec::<String>::new());
+}
+
+#[test]
+fn hover() {
+    let mut client = Client::start();
+    let doc = uri("hover.tmpl");
+    client.open(
+        &doc,
+        "{% macro greet(name, punct='!') %}{{ name }}{{ punct }}{% end macro %}\n{{ greet('Bob') | upper }}{{ env('HOME') }}",
+    );
+
+    let filter = client.hover(&doc, 1, 19).unwrap();
+    assert!(
+        filter.contains("Uppercase the rendered result"),
+        "{}",
+        filter
+    );
+
+    let function = client.hover(&doc, 1, 30).unwrap();
+    assert!(function.contains("env(str)"), "{}", function);
+
+    let macro_call = client.hover(&doc, 1, 4).unwrap();
+    assert!(
+        macro_call.contains("macro greet(name, punct='!')"),
+        "{}",
+        macro_call
+    );
+
+    assert_eq!(client.hover(&doc, 0, 2), None);
+}
+
+#[test]
+fn definition() {
+    let mut client = Client::start();
+    let dir = std::env::temp_dir().join("templar-lsp-definition");
+    std::fs::create_dir_all(&dir).unwrap();
+    std::fs::write(dir.join("header.tmpl"), "Header").unwrap();
+    let doc = Url::from_file_path(dir.join("page.tmpl")).unwrap();
+    client.open(
+        &doc,
+        "{% include 'header.tmpl' %}\n{% macro m() %}{% end macro %}\n{{ m() }}{% include 'missing.tmpl' ignore missing %}",
+    );
+
+    let include = client.definition(&doc, 0, 14).unwrap();
+    assert_eq!(
+        include.uri,
+        Url::from_file_path(dir.join("header.tmpl")).unwrap()
+    );
+
+    let macro_def = client.definition(&doc, 2, 3).unwrap();
+    assert_eq!(macro_def.uri, doc);
+    assert_eq!(
+        macro_def.range,
+        Range::new(Position::new(1, 9), Position::new(1, 10))
+    );
+
+    assert_eq!(client.definition(&doc, 2, 25), None);
+}
+
+#[test]
+fn document_symbols() {
+    let mut client = Client::start();
+    let doc = uri("symbols.tmpl");
+    client.open(
+        &doc,
+        "{% block body %}{% if a %}{% block inner %}{% end block %}{% end if %}{% end block %}\n{% macro row(a, b) %}{% end macro %}",
+    );
+    let result = client.request::<DocumentSymbolRequest>(DocumentSymbolParams {
+        text_document: TextDocumentIdentifier::new(doc),
+        work_done_progress_params: Default::default(),
+        partial_result_params: Default::default(),
+    });
+    let symbols = match result {
+        Some(DocumentSymbolResponse::Nested(symbols)) => symbols,
+        other => panic!("Unexpected symbols response {:?}", other),
+    };
+    let names: Vec<(&str, SymbolKind)> = symbols.iter().map(|s| (&*s.name, s.kind)).collect();
+    assert_eq!(
+        names,
+        vec![
+            ("body", SymbolKind::NAMESPACE),
+            ("row", SymbolKind::FUNCTION)
+        ]
+    );
+    let children = symbols[0].children.as_ref().unwrap();
+    assert_eq!(children[0].name, "inner");
+    assert_eq!(symbols[1].detail.as_deref(), Some("(a, b)"));
+}
@@ -14,6 +14,58 @@ This is a template.
 {{ "THAT CAN CALL FILTERS" | lower }}
 "#;
 
+static LOOP: &str = r#"
+{% for user in users %}
+{%- if user.active && user.age > 18 -%}
+  {{ user.name | upper }} ({{ user.age + 1 }}): {% for tag in user.tags %}[{{ tag }}]{% end for %}
+{% end if -%}
+{% end for %}
+"#;
+
+fn loop_context() -> StandardContext {
+    let context = StandardContext::new();
+    let users: Vec<InnerData> = (0..20i64)
+        .map(|i| {
+            let mut user = InnerData::default();
+            user["name"] = format!("user {}", i).into();
+            user["age"] = (i * 3).into();
+            user["active"] = (i % 4 != 0).into();
+            user["tags"] = InnerData::Seq(vec!["a".into(), "b".into(), "c".into()]);
+            user
+        })
+        .collect();
+    context.set_path(&[&"users".into()], users).unwrap();
+    context
+}
+
+/// A context with thousands of keys at its root and as much again beneath `settings`
+fn large_context() -> StandardContext {
+    let context = StandardContext::new();
+    let mut doc = InnerData::default();
+    for i in 0..5000i64 {
+        let key = format!("key{}", i);
+        doc[key.as_str()]["id"] = i.into();
+        doc[key.as_str()]["name"] = format!("name {}", i).into();
+        doc["settings"][key.as_str()] = i.into();
+    }
+    context.set(doc).unwrap();
+    context
+}
+
+static DEEP: &str = r#"{% for i in items %}{{ config.server.http.port }}{{ config.server.http.host }}{% end for %}"#;
+
+/// A loop reading the same deep paths of a large context on every iteration
+fn deep_context() -> StandardContext {
+    let context = large_context();
+    let mut config = InnerData::default();
+    config["server"]["http"]["port"] = 8080i64.into();
+    config["server"]["http"]["host"] = "localhost".into();
+    context.set_path(&[&"config".into()], config).unwrap();
+    let items: Vec<InnerData> = (0..100i64).map(InnerData::from).collect();
+    context.set_path(&[&"items".into()], items).unwrap();
+    context
+}
+
 fn exec_expression(template: &Template, context: &impl Context) -> Result<()> {
     template.exec(context);
     Ok(())
@@ -25,6 +77,40 @@ fn criterion_benchmark(c: &mut Criterion) {
     c.bench_function("Execute a simple expression", |b| {
         b.iter(|| exec_expression(black_box(&template), black_box(&context)))
     });
+    let compiled = template.compile();
+    c.bench_function("Execute a simple expression (compiled)", |b| {
+        b.iter(|| black_box(&compiled).exec(black_box(&context)))
+    });
+
+    let template = Templar::global().parse_template(LOOP).unwrap();
+    let context = loop_context();
+    c.bench_function("Render a loop", |b| {
+        b.iter(|| black_box(&template).render(black_box(&context)))
+    });
+    let compiled = template.compile();
+    c.bench_function("Render a loop (compiled)", |b| {
+        b.iter(|| black_box(&compiled).render(black_box(&context)))
+    });
+
+    let context = large_context();
+    let template = Templar::global().parse_expression(".").unwrap();
+    c.bench_function("Read the root of a large context", |b| {
+        b.iter(|| black_box(&template).exec(black_box(&context)))
+    });
+    let template = Templar::global().parse_expression("settings").unwrap();
+    c.bench_function("Read a large map from the context", |b| {
+        b.iter(|| black_box(&template).exec(black_box(&context)))
+    });
+
+    let template = Templar::global().parse_template(DEEP).unwrap();
+    let context = deep_context();
+    c.bench_function("Read deep paths in a loop", |b| {
+        b.iter(|| black_box(&template).render(black_box(&context)))
+    });
+    let compiled = template.compile();
+    c.bench_function("Read deep paths in a loop (compiled)", |b| {
+        b.iter(|| black_box(&compiled).render(black_box(&context)))
+    });
 }
 
 criterion_group!(benches, criterion_benchmark);
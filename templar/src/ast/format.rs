@@ -0,0 +1,300 @@
+use super::*;
+
+/// Options for `Templar::format_template`
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct FormatOptions {
+    /// Indent tags in nested control blocks by this many spaces. Only tags on their own line whose
+    /// leading whitespace is already trimmed are indented, so the rendered output never changes.
+    pub indent: Option<usize>,
+}
+
+impl FormatOptions {
+    /// Indent nested control blocks by `width` spaces
+    pub fn indent(mut self, width: usize) -> Self {
+        self.indent = Some(width);
+        self
+    }
+}
+
+/// Print a syntax tree back out as a template. Text and comments are copied from the source
+/// unchanged while tags are rewritten with normalised spacing.
+pub(crate) fn print(ast: &Ast, options: &FormatOptions) -> String {
+    let mut printer = Printer {
+        source: &ast.source,
+        indent: options.indent.map(|w| " ".repeat(w)),
+        trivia: true,
+        depth: 0,
+        pending: String::new(),
+        out: String::with_capacity(ast.source.len()),
+    };
+    printer.body(&ast.nodes);
+    printer.flush();
+    printer.out
+}
+
+/// Print a syntax tree without any of the whitespace trimmed by tags. Two templates with the same
+/// canonical form render the same output.
+pub(crate) fn canonical(ast: &Ast) -> String {
+    let mut printer = Printer {
+        source: &ast.source,
+        indent: None,
+        trivia: false,
+        depth: 0,
+        pending: String::new(),
+        out: String::new(),
+    };
+    printer.body(&ast.nodes);
+    printer.out
+}
+
+struct Printer<'a> {
+    source: &'a str,
+    indent: Option<String>,
+    trivia: bool,
+    depth: usize,
+    /// Whitespace trimmed by the end of the last tag, held back in case the next tag can take it
+    pending: String,
+    out: String,
+}
+
+impl<'a> Printer<'a> {
+    fn body(&mut self, nodes: &[TemplateNode]) {
+        for node in nodes {
+            self.node(node);
+        }
+    }
+
+    fn nested(&mut self, nodes: &[TemplateNode]) {
+        self.depth += 1;
+        self.body(nodes);
+        self.depth -= 1;
+    }
+
+    fn node(&mut self, node: &TemplateNode) {
+        match node {
+            TemplateNode::Text(n) => {
+                self.flush();
+                self.out.push_str(&n.text);
+            }
+            TemplateNode::Comment(n) => {
+                let text = n.text.as_str();
+                let start = text.len() - text.trim_start().len();
+                let end = text.trim_end().len();
+                self.leading(&text[..start]);
+                self.out.push_str(&text[start..end]);
+                self.trailing(&text[end..]);
+            }
+            TemplateNode::Output(n) => {
+                let expr = self.expression(&n.expr);
+                self.tag(&n.tag, "{{", &expr, "}}");
+            }
+            TemplateNode::If(n) => {
+                for (i, branch) in n.branches.iter().enumerate() {
+                    let keyword = if i == 0 { "if" } else { "else if" };
+                    let inner = format!("{} {}", keyword, self.expression(&branch.condition));
+                    self.control(&branch.tag, &inner);
+                    self.nested(&branch.body);
+                }
+                if let Some(otherwise) = &n.otherwise {
+                    self.control(&otherwise.tag, "else");
+                    self.nested(&otherwise.body);
+                }
+                self.control(&n.end_tag, "end if");
+            }
+            TemplateNode::For(n) => {
+                let inner = format!(
+                    "for {} in {}",
+                    n.binding.span.slice(self.source),
+                    self.expression(&n.iterable)
+                );
+                self.control(&n.open, &inner);
+                self.nested(&n.body);
+                self.control(&n.close, "end for");
+            }
+            TemplateNode::Scope(n) => {
+                self.control(&n.open, "scope");
+                self.nested(&n.body);
+                self.control(&n.close, "end scope");
+            }
+            TemplateNode::Filter(n) => {
+                let filters: Vec<String> = n.filters.iter().map(|f| self.filter(f)).collect();
+                self.control(&n.open, &format!("filter {}", filters.join(" | ")));
+                self.nested(&n.body);
+                self.control(&n.close, "end filter");
+            }
+            TemplateNode::Macro(n) => {
+                let params: Vec<String> = n
+                    .params
+                    .iter()
+                    .map(|p| match &p.default {
+                        Some(default) => format!("{}={}", p.name.name, self.expression(default)),
+                        None => p.name.name.clone(),
+                    })
+                    .collect();
+                let inner = format!("macro {}({})", n.name.name, params.join(", "));
+                self.control(&n.open, &inner);
+                self.nested(&n.body);
+                self.control(&n.close, "end macro");
+            }
+            TemplateNode::Call(n) => {
+                let inner = format!("call {}", self.function(&n.call));
+                self.control(&n.open, &inner);
+                self.nested(&n.body);
+                self.control(&n.close, "end call");
+            }
+            TemplateNode::Include(n) => {
+                let mut inner = format!("include {}", n.name.span.slice(self.source));
+                if n.ignore_missing {
+                    inner.push_str(" ignore missing");
+                }
+                if let Some(vars) = &n.vars {
+                    inner.push_str(" with ");
+                    inner.push_str(&self.expression(vars));
+                }
+                if n.only {
+                    inner.push_str(" only");
+                }
+                self.control(&n.tag, &inner);
+            }
+            TemplateNode::Block(n) => {
+                self.control(&n.open, &format!("block {}", n.name.name));
+                self.nested(&n.body);
+                self.control(&n.close, "end block");
+            }
+            TemplateNode::Extends(n) => {
+                let inner = format!("extends {}", n.name.span.slice(self.source));
+                self.control(&n.tag, &inner);
+            }
+            TemplateNode::Error(n) => {
+                self.flush();
+                self.out.push_str(n.span.slice(self.source));
+            }
+        }
+    }
+
+    fn control(&mut self, tag: &Tag, inner: &str) {
+        self.tag(tag, "{%", inner, "%}");
+    }
+
+    /// Print a tag, keeping any whitespace it trims
+    fn tag(&mut self, tag: &Tag, open: &str, inner: &str, close: &str) {
+        let text = tag.span.slice(self.source);
+        let before = &text[..text.len() - text.trim_start().len()];
+        let after = &text[text.trim_end().len()..];
+        self.leading(before);
+        self.out.push_str(open);
+        if tag.trim_left {
+            self.out.push('-');
+        }
+        self.out.push(' ');
+        self.out.push_str(inner);
+        self.out.push(' ');
+        if tag.trim_right {
+            self.out.push('-');
+        }
+        self.out.push_str(close);
+        self.trailing(after);
+    }
+
+    /// Whitespace trimmed before a tag. Along with any whitespace trimmed by the tag before it,
+    /// this is never rendered so it is safe to re-indent.
+    fn leading(&mut self, whitespace: &str) {
+        if !self.trivia {
+            return;
+        }
+        let mut gap = std::mem::take(&mut self.pending);
+        gap.push_str(whitespace);
+        match (&self.indent, gap.rfind('\n')) {
+            (Some(indent), Some(line)) => {
+                self.out.push_str(&gap[..=line]);
+                for _ in 0..self.depth {
+                    self.out.push_str(indent);
+                }
+            }
+            _ => self.out.push_str(&gap),
+        }
+    }
+
+    /// Whitespace trimmed after a tag
+    fn trailing(&mut self, whitespace: &str) {
+        if self.trivia {
+            self.pending.push_str(whitespace);
+        }
+    }
+
+    fn flush(&mut self) {
+        self.out.push_str(&self.pending);
+        self.pending.clear();
+    }
+
+    fn expression(&self, expr: &Expression) -> String {
+        match expr {
+            Expression::Literal(e) => e.span.slice(self.source).into(),
+            Expression::Path(e) => e.span.slice(self.source).into(),
+            Expression::Array(e) => format!("[{}]", self.list(&e.items)),
+            Expression::Map(e) => {
+                let entries: Vec<String> = e
+                    .entries
+                    .iter()
+                    .map(|(k, v)| format!("{}: {}", k.span.slice(self.source), self.expression(v)))
+                    .collect();
+                format!("{{{}}}", entries.join(", "))
+            }
+            Expression::Function(e) => self.function(e),
+            Expression::Filter(e) => {
+                format!("{} | {}", self.expression(&e.input), self.filter(&e.filter))
+            }
+            Expression::Test(e) => format!(
+                "{} is {}{}{}",
+                self.expression(&e.input),
+                if e.negated { "not " } else { "" },
+                e.name.name,
+                e.args.as_ref().map(|a| self.args(a)).unwrap_or_default()
+            ),
+            Expression::Binary(e) => format!(
+                "{} {} {}",
+                self.expression(&e.left),
+                e.op,
+                self.expression(&e.right)
+            ),
+            Expression::Not(e) => format!("!{}", self.expression(&e.expr)),
+            Expression::Lambda(e) => {
+                let body = self.expression(&e.body);
+                match e.params.as_slice() {
+                    [param] => format!("{} => {}", param.name, body),
+                    params => {
+                        let names: Vec<&str> = params.iter().map(|p| p.name.as_str()).collect();
+                        format!("({}) => {}", names.join(", "), body)
+                    }
+                }
+            }
+            Expression::Group(e) => format!("({})", self.expression(&e.expr)),
+        }
+    }
+
+    fn list(&self, items: &[Expression]) -> String {
+        let items: Vec<String> = items.iter().map(|i| self.expression(i)).collect();
+        items.join(", ")
+    }
+
+    fn args(&self, args: &CallArgs) -> String {
+        let mut all: Vec<String> = args.positional.iter().map(|a| self.expression(a)).collect();
+        all.extend(
+            args.named
+                .iter()
+                .map(|a| format!("{}={}", a.name.name, self.expression(&a.value))),
+        );
+        format!("({})", all.join(", "))
+    }
+
+    fn function(&self, call: &FunctionCall) -> String {
+        format!("{}{}", call.name.name, self.args(&call.args))
+    }
+
+    fn filter(&self, filter: &FilterCall) -> String {
+        match &filter.args {
+            Some(args) => format!("{}{}", filter.name.name, self.args(args)),
+            None => filter.name.name.clone(),
+        }
+    }
+}
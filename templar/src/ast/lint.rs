@@ -0,0 +1,325 @@
+use super::*;
+use std::collections::HashSet;
+
+/// The kinds of problems found by `Templar::lint`
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[non_exhaustive]
+pub enum LintKind {
+    /// A filter that is not registered with Templar
+    UnknownFilter,
+    /// A function that is not registered with Templar or defined as a macro
+    UnknownFunction,
+    /// A test that is not registered with Templar
+    UnknownTest,
+    /// A value is assigned but never read
+    UnusedAssignment,
+    /// A loop variable hides the variable of an enclosing loop
+    ShadowedLoopVariable,
+    /// A comparison between literals of different types, which is always false for `==`, always
+    /// true for `!=` and meaningless for orderings such as `<`
+    ConstantComparison,
+    /// An `if` condition that is a literal other than a boolean
+    NonBoolCondition,
+    /// A macro parameter that the macro body never uses
+    UnusedMacroParam,
+}
+
+impl LintKind {
+    /// A short, stable name for the kind of problem, e.g. `unknown-filter`
+    pub fn code(self) -> &'static str {
+        match self {
+            LintKind::UnknownFilter => "unknown-filter",
+            LintKind::UnknownFunction => "unknown-function",
+            LintKind::UnknownTest => "unknown-test",
+            LintKind::UnusedAssignment => "unused-assignment",
+            LintKind::ShadowedLoopVariable => "shadowed-loop-variable",
+            LintKind::ConstantComparison => "constant-comparison",
+            LintKind::NonBoolCondition => "non-bool-condition",
+            LintKind::UnusedMacroParam => "unused-macro-param",
+        }
+    }
+}
+
+impl fmt::Display for LintKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.code())
+    }
+}
+
+/// A problem found in a template by `Templar::lint`
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[non_exhaustive]
+pub struct Diagnostic {
+    pub kind: LintKind,
+    pub message: String,
+    /// The part of the template the problem was found in
+    pub span: Span,
+}
+
+impl Diagnostic {
+    fn new(kind: LintKind, span: Span, message: String) -> Self {
+        Diagnostic {
+            kind,
+            message,
+            span,
+        }
+    }
+}
+
+/// Check a syntax tree for likely mistakes, sorted by where they appear in the template
+pub(crate) fn lint(ast: &Ast, templar: &Templar) -> Vec<Diagnostic> {
+    let mut macros = MacroNames::default();
+    macros.visit_ast(ast);
+    let mut linter = Linter {
+        templar,
+        macros: macros.0,
+        loops: vec![],
+        in_macro: 0,
+        in_block: 0,
+        assignments: vec![],
+        reads: vec![],
+        diagnostics: vec![],
+    };
+    linter.visit_ast(ast);
+
+    let Linter {
+        assignments,
+        reads,
+        mut diagnostics,
+        ..
+    } = linter;
+    for target in assignments.iter() {
+        if !reads.iter().any(|read| overlaps(&target.segments, read)) {
+            diagnostics.push(Diagnostic::new(
+                LintKind::UnusedAssignment,
+                target.span,
+                format!("'{}' is assigned but never read", target),
+            ));
+        }
+    }
+    diagnostics.sort_by_key(|d| (d.span.start, d.span.end));
+    diagnostics
+}
+
+/// Either path is a prefix of the other, so reading one may read the other. The root path is a
+/// prefix of everything.
+fn overlaps(a: &[InnerData], b: &[InnerData]) -> bool {
+    a.iter().zip(b.iter()).all(|(a, b)| a == b)
+}
+
+#[derive(Default)]
+struct MacroNames(HashSet<String>);
+
+impl Visitor for MacroNames {
+    fn visit_macro(&mut self, block: &MacroBlock) {
+        self.0.insert(block.name.name.clone());
+        walk_macro(self, block)
+    }
+}
+
+/// The first segment of every path read within part of a template
+#[derive(Default)]
+struct PathRoots {
+    roots: HashSet<String>,
+    root_read: bool,
+}
+
+impl Visitor for PathRoots {
+    fn visit_path(&mut self, path: &Path) {
+        match path.segments.first() {
+            Some(first) => {
+                self.roots.insert(first.to_string());
+            }
+            None => self.root_read = true,
+        }
+    }
+}
+
+struct Linter<'a> {
+    templar: &'a Templar,
+    macros: HashSet<String>,
+    loops: Vec<String>,
+    in_macro: usize,
+    in_block: usize,
+    assignments: Vec<Path>,
+    reads: Vec<Vec<InnerData>>,
+    diagnostics: Vec<Diagnostic>,
+}
+
+impl<'a> Linter<'a> {
+    fn report(&mut self, kind: LintKind, span: Span, message: String) {
+        self.diagnostics.push(Diagnostic::new(kind, span, message));
+    }
+}
+
+/// The type of a literal expression, if it is one
+fn literal_type(expr: &Expression) -> Option<&'static str> {
+    Some(match expr {
+        Expression::Literal(lit) => match lit.value {
+            InnerData::Bool(_) => "bool",
+            InnerData::Null => "null",
+            InnerData::Number(_) => "number",
+            InnerData::String(_) => "string",
+            _ => return None,
+        },
+        Expression::Array(_) => "array",
+        Expression::Map(_) => "map",
+        Expression::Group(group) => return literal_type(&group.expr),
+        _ => return None,
+    })
+}
+
+impl<'a> Visitor for Linter<'a> {
+    fn visit_if(&mut self, block: &IfBlock) {
+        for branch in block.branches.iter() {
+            match literal_type(&branch.condition) {
+                Some("bool") | None => {}
+                Some(kind) => self.report(
+                    LintKind::NonBoolCondition,
+                    branch.condition.span(),
+                    format!("Condition is a {} literal instead of a boolean", kind),
+                ),
+            }
+        }
+        walk_if(self, block)
+    }
+
+    fn visit_for(&mut self, block: &ForBlock) {
+        let name = block.binding.to_string();
+        if self.loops.contains(&name) {
+            self.report(
+                LintKind::ShadowedLoopVariable,
+                block.binding.span,
+                format!("Loop variable '{}' shadows an enclosing loop", name),
+            );
+        }
+        self.visit_expression(&block.iterable);
+        self.loops.push(name);
+        walk_body(self, &block.body);
+        self.loops.pop();
+    }
+
+    fn visit_macro(&mut self, block: &MacroBlock) {
+        let mut used = PathRoots::default();
+        walk_body(&mut used, &block.body);
+        for param in block.params.iter() {
+            if !used.root_read && !used.roots.contains(&param.name.name) {
+                self.report(
+                    LintKind::UnusedMacroParam,
+                    param.name.span,
+                    format!(
+                        "Parameter '{}' of macro '{}' is never used",
+                        param.name.name, block.name.name
+                    ),
+                );
+            }
+        }
+        self.in_macro += 1;
+        walk_macro(self, block);
+        self.in_macro -= 1;
+    }
+
+    fn visit_block(&mut self, block: &NamedBlock) {
+        self.in_block += 1;
+        walk_body(self, &block.body);
+        self.in_block -= 1;
+    }
+
+    fn visit_include(&mut self, block: &IncludeBlock) {
+        if !block.only {
+            // The included template can read anything in the context
+            self.reads.push(vec![]);
+        }
+        if let Some(vars) = &block.vars {
+            self.visit_expression(vars)
+        }
+    }
+
+    fn visit_extends(&mut self, _block: &ExtendsBlock) {
+        self.reads.push(vec![]);
+    }
+
+    fn visit_expression(&mut self, expr: &Expression) {
+        match expr {
+            Expression::Binary(e) if e.op == BinaryOp::Set => {
+                match e.left.as_ref() {
+                    Expression::Path(target) => self.assignments.push(target.clone()),
+                    other => self.visit_expression(other),
+                }
+                self.visit_expression(&e.right);
+            }
+            Expression::Binary(e) => {
+                if let (Some(left), Some(right)) = (literal_type(&e.left), literal_type(&e.right)) {
+                    let outcome = match e.op {
+                        BinaryOp::Equals => Some("is always false"),
+                        BinaryOp::NotEquals => Some("is always true"),
+                        BinaryOp::GreaterThan
+                        | BinaryOp::GreaterThanEquals
+                        | BinaryOp::LessThan
+                        | BinaryOp::LessThanEquals => Some("does not compare their values"),
+                        _ => None,
+                    };
+                    if let Some(outcome) = outcome.filter(|_| left != right) {
+                        self.report(
+                            LintKind::ConstantComparison,
+                            e.span,
+                            format!(
+                                "Comparing {} and {} literals with {} {}",
+                                left,
+                                right,
+                                e.op.as_str(),
+                                outcome
+                            ),
+                        );
+                    }
+                }
+                walk_expression(self, expr)
+            }
+            _ => walk_expression(self, expr),
+        }
+    }
+
+    fn visit_path(&mut self, path: &Path) {
+        self.reads.push(path.segments.clone());
+    }
+
+    fn visit_function(&mut self, call: &FunctionCall) {
+        let name = call.name.name.as_str();
+        let known = self.macros.contains(name)
+            || (name == "caller" && self.in_macro > 0)
+            || (name == "super" && self.in_block > 0)
+            || self.templar.functions.contains_key(name);
+        if !known {
+            self.report(
+                LintKind::UnknownFunction,
+                call.name.span,
+                format!("Function '{}' was not found", name),
+            );
+        }
+        walk_args(self, &call.args)
+    }
+
+    fn visit_filter(&mut self, filter: &FilterCall) {
+        if !self.templar.filters.contains_key(&filter.name.name) {
+            self.report(
+                LintKind::UnknownFilter,
+                filter.name.span,
+                format!("Filter '{}' was not found", filter.name.name),
+            );
+        }
+        if let Some(args) = &filter.args {
+            walk_args(self, args)
+        }
+    }
+
+    fn visit_test(&mut self, test: &TestExpr) {
+        if !self.templar.tests.contains_key(&test.name.name) {
+            self.report(
+                LintKind::UnknownTest,
+                test.name.span,
+                format!("Test '{}' was not found", test.name.name),
+            );
+        }
+        walk_test(self, test)
+    }
+}
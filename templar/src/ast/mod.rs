@@ -0,0 +1,563 @@
+/*!
+A read-only syntax tree for templates, for building tools such as linters and documentation
+generators on top of Templar.
+
+The tree is produced by `Templar::parse_ast` and mirrors the source closely: every node records the
+`Span` of source it was parsed from, and nothing is resolved, so includes, macros and parent
+templates are left as they were written. Operators are applied from left to right in the same way
+they are executed, e.g. `1 + 2 * 3` is `(1 + 2) * 3` and a filter applies to everything before it.
+
+# Usage
+
+```
+# use templar::*;
+# use templar::ast::*;
+struct Paths(Vec<String>);
+
+impl Visitor for Paths {
+    fn visit_path(&mut self, path: &Path) {
+        self.0.push(path.to_string());
+    }
+}
+
+let ast = Templar::global().parse_ast("{% for u in users %}{{ u.name | upper }}{% end for %}")?;
+let mut paths = Paths(vec![]);
+paths.visit_ast(&ast);
+assert_eq!(paths.0, vec!["u", "users", "u.name"]);
+# Ok::<(), templar::TemplarError>(())
+```
+*/
+
+use crate::*;
+use std::fmt;
+
+mod format;
+mod lint;
+mod visitor;
+
+pub use format::FormatOptions;
+pub(crate) use format::{canonical, print};
+pub(crate) use lint::lint;
+pub use lint::{Diagnostic, LintKind};
+pub use visitor::*;
+
+/// A range of the template source as byte offsets
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+pub struct Span {
+    /// The offset of the first byte
+    pub start: usize,
+    /// The offset after the last byte
+    pub end: usize,
+}
+
+impl Span {
+    /// Create a new span
+    pub fn new(start: usize, end: usize) -> Self {
+        Span { start, end }
+    }
+
+    /// The smallest span containing both this span and `other`
+    pub fn join(self, other: Span) -> Span {
+        Span::new(self.start.min(other.start), self.end.max(other.end))
+    }
+
+    /// The text of the source this span covers
+    pub fn slice<'a>(&self, source: &'a str) -> &'a str {
+        &source[self.start..self.end]
+    }
+
+    /// The zero based line and column (in characters) of the start of this span
+    pub fn line_col(&self, source: &str) -> (usize, usize) {
+        let before = &source[..self.start];
+        let line = before.matches('\n').count();
+        let line_start = before.rfind('\n').map(|i| i + 1).unwrap_or(0);
+        (line, before[line_start..].chars().count())
+    }
+}
+
+/// A single tag such as `{{ name }}` or `{% end if %}`. A tag that trims the whitespace next to it,
+/// e.g. `{{- name -}}`, includes the whitespace it removes.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+#[non_exhaustive]
+pub struct Tag {
+    pub span: Span,
+    /// The tag removes the whitespace before it
+    pub trim_left: bool,
+    /// The tag removes the whitespace after it
+    pub trim_right: bool,
+}
+
+/// A parsed template
+#[derive(Debug, Clone)]
+#[non_exhaustive]
+pub struct Ast {
+    /// The source the template was parsed from
+    pub source: String,
+    /// The top level nodes of the template
+    pub nodes: Vec<TemplateNode>,
+}
+
+/// A node of a template
+#[derive(Debug, Clone)]
+#[non_exhaustive]
+pub enum TemplateNode {
+    /// Plain text that is copied to the output
+    Text(Text),
+    /// A comment, e.g. `{# note #}`
+    Comment(Comment),
+    /// An output block, e.g. `{{ user.name }}`
+    Output(Output),
+    /// An if block, including any else branches
+    If(IfBlock),
+    /// A for loop
+    For(ForBlock),
+    /// A scope block
+    Scope(ScopeBlock),
+    /// A filter block, e.g. `{% filter upper %}...{% end filter %}`
+    Filter(FilterBlock),
+    /// A macro definition
+    Macro(MacroBlock),
+    /// A macro call with a body, e.g. `{% call wrap('b') %}...{% end call %}`
+    Call(CallBlock),
+    /// An include
+    Include(IncludeBlock),
+    /// A named block that child templates can override
+    Block(NamedBlock),
+    /// An extends tag
+    Extends(ExtendsBlock),
+    /// A part of the template that could not be parsed, see `Templar::parse_ast_recovering`
+    Error(ErrorNode),
+}
+
+impl TemplateNode {
+    /// The source this node was parsed from
+    pub fn span(&self) -> Span {
+        match self {
+            TemplateNode::Text(n) => n.span,
+            TemplateNode::Comment(n) => n.span,
+            TemplateNode::Output(n) => n.span,
+            TemplateNode::If(n) => n.span,
+            TemplateNode::For(n) => n.span,
+            TemplateNode::Scope(n) => n.span,
+            TemplateNode::Filter(n) => n.span,
+            TemplateNode::Macro(n) => n.span,
+            TemplateNode::Call(n) => n.span,
+            TemplateNode::Include(n) => n.span,
+            TemplateNode::Block(n) => n.span,
+            TemplateNode::Extends(n) => n.span,
+            TemplateNode::Error(n) => n.span,
+        }
+    }
+}
+
+/// Plain text that is copied to the output
+#[derive(Debug, Clone)]
+#[non_exhaustive]
+pub struct Text {
+    pub span: Span,
+    pub text: String,
+}
+
+/// A comment, e.g. `{# note #}`. The text includes the delimiters.
+#[derive(Debug, Clone)]
+#[non_exhaustive]
+pub struct Comment {
+    pub span: Span,
+    pub text: String,
+}
+
+/// An output block, e.g. `{{ user.name }}`
+#[derive(Debug, Clone)]
+#[non_exhaustive]
+pub struct Output {
+    pub span: Span,
+    pub tag: Tag,
+    pub expr: Expression,
+}
+
+/// An if block. The first branch is the `if` and any further branches are `else if`s.
+#[derive(Debug, Clone)]
+#[non_exhaustive]
+pub struct IfBlock {
+    pub span: Span,
+    pub branches: Vec<IfBranch>,
+    pub otherwise: Option<ElseBranch>,
+    pub end_tag: Tag,
+}
+
+/// A condition of an if block along with the body rendered if it is true
+#[derive(Debug, Clone)]
+#[non_exhaustive]
+pub struct IfBranch {
+    pub span: Span,
+    pub tag: Tag,
+    pub condition: Expression,
+    pub body: Vec<TemplateNode>,
+}
+
+/// The final `else` of an if block
+#[derive(Debug, Clone)]
+#[non_exhaustive]
+pub struct ElseBranch {
+    pub span: Span,
+    pub tag: Tag,
+    pub body: Vec<TemplateNode>,
+}
+
+/// A for loop, e.g. `{% for item in items %}...{% end for %}`
+#[derive(Debug, Clone)]
+#[non_exhaustive]
+pub struct ForBlock {
+    pub span: Span,
+    pub open: Tag,
+    pub close: Tag,
+    pub binding: Path,
+    pub iterable: Expression,
+    pub body: Vec<TemplateNode>,
+}
+
+/// A scope block
+#[derive(Debug, Clone)]
+#[non_exhaustive]
+pub struct ScopeBlock {
+    pub span: Span,
+    pub open: Tag,
+    pub close: Tag,
+    pub body: Vec<TemplateNode>,
+}
+
+/// A filter block, the filters are applied in order
+#[derive(Debug, Clone)]
+#[non_exhaustive]
+pub struct FilterBlock {
+    pub span: Span,
+    pub open: Tag,
+    pub close: Tag,
+    pub filters: Vec<FilterCall>,
+    pub body: Vec<TemplateNode>,
+}
+
+/// A macro definition
+#[derive(Debug, Clone)]
+#[non_exhaustive]
+pub struct MacroBlock {
+    pub span: Span,
+    pub open: Tag,
+    pub close: Tag,
+    pub name: Ident,
+    pub params: Vec<MacroParam>,
+    pub body: Vec<TemplateNode>,
+}
+
+/// A parameter of a macro definition
+#[derive(Debug, Clone)]
+#[non_exhaustive]
+pub struct MacroParam {
+    pub span: Span,
+    pub name: Ident,
+    pub default: Option<Expression>,
+}
+
+/// A macro call with a body
+#[derive(Debug, Clone)]
+#[non_exhaustive]
+pub struct CallBlock {
+    pub span: Span,
+    pub open: Tag,
+    pub close: Tag,
+    pub call: FunctionCall,
+    pub body: Vec<TemplateNode>,
+}
+
+/// An include, e.g. `{% include 'header.tmpl' with { 'title': 'Home' } %}`
+#[derive(Debug, Clone)]
+#[non_exhaustive]
+pub struct IncludeBlock {
+    pub span: Span,
+    pub tag: Tag,
+    pub name: Literal,
+    pub ignore_missing: bool,
+    pub vars: Option<Expression>,
+    pub only: bool,
+}
+
+/// A named block, e.g. `{% block content %}...{% end block %}`
+#[derive(Debug, Clone)]
+#[non_exhaustive]
+pub struct NamedBlock {
+    pub span: Span,
+    pub open: Tag,
+    pub close: Tag,
+    pub name: Ident,
+    pub body: Vec<TemplateNode>,
+}
+
+/// An extends tag, e.g. `{% extends 'base.tmpl' %}`
+#[derive(Debug, Clone)]
+#[non_exhaustive]
+pub struct ExtendsBlock {
+    pub span: Span,
+    pub tag: Tag,
+    pub name: Literal,
+}
+
+/// A part of the template that could not be parsed, such as a malformed tag or the tags of a block
+/// that could not be built because one of its tags was malformed
+#[derive(Debug, Clone)]
+#[non_exhaustive]
+pub struct ErrorNode {
+    pub span: Span,
+}
+
+/// A syntax error found by `Templar::parse_ast_recovering`
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[non_exhaustive]
+pub struct SyntaxError {
+    pub span: Span,
+    pub message: String,
+}
+
+impl fmt::Display for SyntaxError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.message)
+    }
+}
+
+/// An expression
+#[derive(Debug, Clone)]
+#[non_exhaustive]
+pub enum Expression {
+    /// A literal value such as `'text'`, `5`, `true` or `null`
+    Literal(Literal),
+    /// A path into the context, e.g. `user.name`
+    Path(Path),
+    /// An array, e.g. `[1, 2]`
+    Array(ArrayExpr),
+    /// A map, e.g. `{'a': 1}`
+    Map(MapExpr),
+    /// A call to a function or macro, e.g. `env('HOME')`
+    Function(FunctionCall),
+    /// A filter applied to an expression, e.g. `name | upper`
+    Filter(FilterExpr),
+    /// A test applied to an expression, e.g. `value is defined`
+    Test(TestExpr),
+    /// A binary operation, e.g. `a + b`
+    Binary(BinaryExpr),
+    /// A negation, e.g. `!value`
+    Not(NotExpr),
+    /// A lambda, e.g. `x => x.name`
+    Lambda(LambdaExpr),
+    /// An expression in parentheses
+    Group(GroupExpr),
+}
+
+impl Expression {
+    /// The source this expression was parsed from
+    pub fn span(&self) -> Span {
+        match self {
+            Expression::Literal(e) => e.span,
+            Expression::Path(e) => e.span,
+            Expression::Array(e) => e.span,
+            Expression::Map(e) => e.span,
+            Expression::Function(e) => e.span,
+            Expression::Filter(e) => e.span,
+            Expression::Test(e) => e.span,
+            Expression::Binary(e) => e.span,
+            Expression::Not(e) => e.span,
+            Expression::Lambda(e) => e.span,
+            Expression::Group(e) => e.span,
+        }
+    }
+}
+
+/// An identifier such as a filter or macro name
+#[derive(Debug, Clone)]
+#[non_exhaustive]
+pub struct Ident {
+    pub span: Span,
+    pub name: String,
+}
+
+/// A literal value
+#[derive(Debug, Clone)]
+#[non_exhaustive]
+pub struct Literal {
+    pub span: Span,
+    pub value: InnerData,
+}
+
+/// A path into the context. The root path `.` has no segments.
+#[derive(Debug, Clone)]
+#[non_exhaustive]
+pub struct Path {
+    pub span: Span,
+    pub segments: Vec<InnerData>,
+}
+
+impl fmt::Display for Path {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if self.segments.is_empty() {
+            return write!(f, ".");
+        }
+        let names: Vec<String> = self.segments.iter().map(|s| s.to_string()).collect();
+        write!(f, "{}", names.join("."))
+    }
+}
+
+/// An array expression
+#[derive(Debug, Clone)]
+#[non_exhaustive]
+pub struct ArrayExpr {
+    pub span: Span,
+    pub items: Vec<Expression>,
+}
+
+/// A map expression
+#[derive(Debug, Clone)]
+#[non_exhaustive]
+pub struct MapExpr {
+    pub span: Span,
+    pub entries: Vec<(Literal, Expression)>,
+}
+
+/// A call to a function or macro
+#[derive(Debug, Clone)]
+#[non_exhaustive]
+pub struct FunctionCall {
+    pub span: Span,
+    pub name: Ident,
+    pub args: CallArgs,
+}
+
+/// The arguments of a call
+#[derive(Debug, Clone, Default)]
+#[non_exhaustive]
+pub struct CallArgs {
+    pub span: Span,
+    pub positional: Vec<Expression>,
+    pub named: Vec<NamedArg>,
+}
+
+/// An argument passed by name, e.g. `sep=', '`
+#[derive(Debug, Clone)]
+#[non_exhaustive]
+pub struct NamedArg {
+    pub span: Span,
+    pub name: Ident,
+    pub value: Expression,
+}
+
+/// A filter with its arguments, e.g. `join(', ')`
+#[derive(Debug, Clone)]
+#[non_exhaustive]
+pub struct FilterCall {
+    pub span: Span,
+    pub name: Ident,
+    pub args: Option<CallArgs>,
+}
+
+/// A filter applied to an expression
+#[derive(Debug, Clone)]
+#[non_exhaustive]
+pub struct FilterExpr {
+    pub span: Span,
+    pub input: Box<Expression>,
+    pub filter: FilterCall,
+}
+
+/// A test applied to an expression, e.g. `value is not even`
+#[derive(Debug, Clone)]
+#[non_exhaustive]
+pub struct TestExpr {
+    pub span: Span,
+    pub input: Box<Expression>,
+    pub negated: bool,
+    pub name: Ident,
+    pub args: Option<CallArgs>,
+}
+
+/// The operator of a binary expression
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[non_exhaustive]
+pub enum BinaryOp {
+    Add,
+    Subtract,
+    Multiply,
+    Divide,
+    Modulus,
+    And,
+    Or,
+    Equals,
+    NotEquals,
+    GreaterThan,
+    GreaterThanEquals,
+    LessThan,
+    LessThanEquals,
+    Concat,
+    Set,
+}
+
+impl BinaryOp {
+    /// The operator as it is written in a template
+    pub fn as_str(self) -> &'static str {
+        match self {
+            BinaryOp::Add => "+",
+            BinaryOp::Subtract => "-",
+            BinaryOp::Multiply => "*",
+            BinaryOp::Divide => "/",
+            BinaryOp::Modulus => "%",
+            BinaryOp::And => "&&",
+            BinaryOp::Or => "||",
+            BinaryOp::Equals => "==",
+            BinaryOp::NotEquals => "!=",
+            BinaryOp::GreaterThan => ">",
+            BinaryOp::GreaterThanEquals => ">=",
+            BinaryOp::LessThan => "<",
+            BinaryOp::LessThanEquals => "<=",
+            BinaryOp::Concat => "~",
+            BinaryOp::Set => "=",
+        }
+    }
+}
+
+impl fmt::Display for BinaryOp {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.as_str())
+    }
+}
+
+/// A binary operation
+#[derive(Debug, Clone)]
+#[non_exhaustive]
+pub struct BinaryExpr {
+    pub span: Span,
+    pub op: BinaryOp,
+    pub op_span: Span,
+    pub left: Box<Expression>,
+    pub right: Box<Expression>,
+}
+
+/// A negation
+#[derive(Debug, Clone)]
+#[non_exhaustive]
+pub struct NotExpr {
+    pub span: Span,
+    pub expr: Box<Expression>,
+}
+
+/// A lambda
+#[derive(Debug, Clone)]
+#[non_exhaustive]
+pub struct LambdaExpr {
+    pub span: Span,
+    pub params: Vec<Ident>,
+    pub body: Box<Expression>,
+}
+
+/// An expression in parentheses
+#[derive(Debug, Clone)]
+#[non_exhaustive]
+pub struct GroupExpr {
+    pub span: Span,
+    pub expr: Box<Expression>,
+}
@@ -0,0 +1,192 @@
+use super::*;
+
+/// Visits the nodes of a template's syntax tree.
+///
+/// Every method defaults to visiting the children of the node with the matching `walk_` function,
+/// so an implementation only needs to override the nodes it is interested in. When overriding a
+/// method, call the `walk_` function to keep visiting the children.
+pub trait Visitor {
+    fn visit_ast(&mut self, ast: &Ast) {
+        walk_ast(self, ast)
+    }
+
+    fn visit_node(&mut self, node: &TemplateNode) {
+        walk_node(self, node)
+    }
+
+    fn visit_text(&mut self, _text: &Text) {}
+
+    fn visit_comment(&mut self, _comment: &Comment) {}
+
+    fn visit_output(&mut self, output: &Output) {
+        self.visit_expression(&output.expr)
+    }
+
+    fn visit_if(&mut self, block: &IfBlock) {
+        walk_if(self, block)
+    }
+
+    fn visit_for(&mut self, block: &ForBlock) {
+        walk_for(self, block)
+    }
+
+    fn visit_scope(&mut self, block: &ScopeBlock) {
+        walk_body(self, &block.body)
+    }
+
+    fn visit_filter_block(&mut self, block: &FilterBlock) {
+        walk_filter_block(self, block)
+    }
+
+    fn visit_macro(&mut self, block: &MacroBlock) {
+        walk_macro(self, block)
+    }
+
+    fn visit_call(&mut self, block: &CallBlock) {
+        self.visit_function(&block.call);
+        walk_body(self, &block.body)
+    }
+
+    fn visit_include(&mut self, block: &IncludeBlock) {
+        if let Some(vars) = &block.vars {
+            self.visit_expression(vars)
+        }
+    }
+
+    fn visit_block(&mut self, block: &NamedBlock) {
+        walk_body(self, &block.body)
+    }
+
+    fn visit_extends(&mut self, _block: &ExtendsBlock) {}
+
+    fn visit_error(&mut self, _node: &ErrorNode) {}
+
+    fn visit_expression(&mut self, expr: &Expression) {
+        walk_expression(self, expr)
+    }
+
+    fn visit_literal(&mut self, _literal: &Literal) {}
+
+    fn visit_path(&mut self, _path: &Path) {}
+
+    fn visit_function(&mut self, call: &FunctionCall) {
+        walk_args(self, &call.args)
+    }
+
+    fn visit_filter(&mut self, filter: &FilterCall) {
+        if let Some(args) = &filter.args {
+            walk_args(self, args)
+        }
+    }
+
+    fn visit_test(&mut self, test: &TestExpr) {
+        walk_test(self, test)
+    }
+
+    fn visit_lambda(&mut self, lambda: &LambdaExpr) {
+        self.visit_expression(&lambda.body)
+    }
+}
+
+pub fn walk_ast<V: Visitor + ?Sized>(visitor: &mut V, ast: &Ast) {
+    walk_body(visitor, &ast.nodes)
+}
+
+pub fn walk_body<V: Visitor + ?Sized>(visitor: &mut V, body: &[TemplateNode]) {
+    for node in body.iter() {
+        visitor.visit_node(node)
+    }
+}
+
+pub fn walk_node<V: Visitor + ?Sized>(visitor: &mut V, node: &TemplateNode) {
+    match node {
+        TemplateNode::Text(n) => visitor.visit_text(n),
+        TemplateNode::Comment(n) => visitor.visit_comment(n),
+        TemplateNode::Output(n) => visitor.visit_output(n),
+        TemplateNode::If(n) => visitor.visit_if(n),
+        TemplateNode::For(n) => visitor.visit_for(n),
+        TemplateNode::Scope(n) => visitor.visit_scope(n),
+        TemplateNode::Filter(n) => visitor.visit_filter_block(n),
+        TemplateNode::Macro(n) => visitor.visit_macro(n),
+        TemplateNode::Call(n) => visitor.visit_call(n),
+        TemplateNode::Include(n) => visitor.visit_include(n),
+        TemplateNode::Block(n) => visitor.visit_block(n),
+        TemplateNode::Extends(n) => visitor.visit_extends(n),
+        TemplateNode::Error(n) => visitor.visit_error(n),
+    }
+}
+
+pub fn walk_if<V: Visitor + ?Sized>(visitor: &mut V, block: &IfBlock) {
+    for branch in block.branches.iter() {
+        visitor.visit_expression(&branch.condition);
+        walk_body(visitor, &branch.body);
+    }
+    if let Some(otherwise) = &block.otherwise {
+        walk_body(visitor, &otherwise.body);
+    }
+}
+
+pub fn walk_for<V: Visitor + ?Sized>(visitor: &mut V, block: &ForBlock) {
+    visitor.visit_path(&block.binding);
+    visitor.visit_expression(&block.iterable);
+    walk_body(visitor, &block.body)
+}
+
+pub fn walk_filter_block<V: Visitor + ?Sized>(visitor: &mut V, block: &FilterBlock) {
+    for filter in block.filters.iter() {
+        visitor.visit_filter(filter);
+    }
+    walk_body(visitor, &block.body)
+}
+
+pub fn walk_macro<V: Visitor + ?Sized>(visitor: &mut V, block: &MacroBlock) {
+    for param in block.params.iter() {
+        if let Some(default) = &param.default {
+            visitor.visit_expression(default);
+        }
+    }
+    walk_body(visitor, &block.body)
+}
+
+pub fn walk_args<V: Visitor + ?Sized>(visitor: &mut V, args: &CallArgs) {
+    for arg in args.positional.iter() {
+        visitor.visit_expression(arg);
+    }
+    for arg in args.named.iter() {
+        visitor.visit_expression(&arg.value);
+    }
+}
+
+pub fn walk_test<V: Visitor + ?Sized>(visitor: &mut V, test: &TestExpr) {
+    visitor.visit_expression(&test.input);
+    if let Some(args) = &test.args {
+        walk_args(visitor, args)
+    }
+}
+
+pub fn walk_expression<V: Visitor + ?Sized>(visitor: &mut V, expr: &Expression) {
+    match expr {
+        Expression::Literal(e) => visitor.visit_literal(e),
+        Expression::Path(e) => visitor.visit_path(e),
+        Expression::Array(e) => e.items.iter().for_each(|i| visitor.visit_expression(i)),
+        Expression::Map(e) => {
+            for (key, value) in e.entries.iter() {
+                visitor.visit_literal(key);
+                visitor.visit_expression(value);
+            }
+        }
+        Expression::Function(e) => visitor.visit_function(e),
+        Expression::Filter(e) => {
+            visitor.visit_expression(&e.input);
+            visitor.visit_filter(&e.filter);
+        }
+        Expression::Test(e) => visitor.visit_test(e),
+        Expression::Binary(e) => {
+            visitor.visit_expression(&e.left);
+            visitor.visit_expression(&e.right);
+        }
+        Expression::Not(e) => visitor.visit_expression(&e.expr),
+        Expression::Lambda(e) => visitor.visit_lambda(e),
+        Expression::Group(e) => visitor.visit_expression(&e.expr),
+    }
+}
@@ -61,6 +61,66 @@ pub struct Command {
     /// Overwrite target if it already exists
     #[structopt(short, long)]
     pub force: bool,
+
+    /// Fail if a template renders a value that is not defined
+    #[structopt(long)]
+    pub strict: bool,
+
+    /// Which commands, files and environment variables templates may use: trusted allows all,
+    /// restricted only those allowed with the --allow options and pure none. The --allow options
+    /// are rejected with any other sandbox
+    #[structopt(
+        long,
+        default_value = "trusted",
+        possible_values = &["trusted", "restricted", "pure"]
+    )]
+    pub sandbox: String,
+
+    /// Command that templates may run in a restricted sandbox, `sh` allows scripts
+    #[structopt(long, number_of_values = 1, multiple = true)]
+    pub allow_command: Vec<String>,
+
+    /// Directory that templates may read files from in a restricted sandbox
+    #[structopt(long, parse(from_os_str), number_of_values = 1, multiple = true)]
+    pub allow_dir: Vec<PathBuf>,
+
+    /// Environment variable that templates may read in a restricted sandbox
+    #[structopt(long, number_of_values = 1, multiple = true)]
+    pub allow_env: Vec<String>,
+
+    #[structopt(subcommand)]
+    pub subcommand: Option<SubCommand>,
+}
+
+#[derive(StructOpt, Debug)]
+#[structopt(rename_all = "kebab_case")]
+pub enum SubCommand {
+    /// List the context values, filters and functions a template uses as JSON
+    Vars {
+        /// Template file to inspect, defaults to stdin
+        #[structopt(short, long, parse(from_os_str))]
+        template: Option<PathBuf>,
+    },
+    /// Check a template for likely mistakes and print them as JSON
+    Lint {
+        /// Template file to check, defaults to stdin
+        #[structopt(short, long, parse(from_os_str))]
+        template: Option<PathBuf>,
+    },
+    /// Reformat a template with consistent spacing inside tags
+    Fmt {
+        /// Template file to format, defaults to stdin
+        #[structopt(short, long, parse(from_os_str))]
+        template: Option<PathBuf>,
+
+        /// Indent nested control blocks by this many spaces where whitespace is already trimmed
+        #[structopt(long)]
+        indent: Option<usize>,
+
+        /// Overwrite the template file with the formatted result
+        #[structopt(short, long, requires = "template")]
+        write: bool,
+    },
 }
 
 /// Parse a single key-value pair
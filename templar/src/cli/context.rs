@@ -3,11 +3,13 @@ use std::path::PathBuf;
 use templar::{InnerData, Templar};
 // use unstructured::Document;
 
-pub fn build_context(options: &Command) -> Result<StandardContext> {
+/// Build the context from the inputs, parsing dynamic inputs with `templar` so that they are
+/// rendered with the same configuration as the templates
+pub fn build_context(options: &Command, templar: &Templar) -> Result<StandardContext> {
     let ctx = StandardContext::new();
     for file in options.dynamic_input.iter() {
         let doc = parse_data(file)?;
-        let tree: TemplateTree = Templar::global().parse(&doc)?;
+        let tree: TemplateTree = templar.parse(&doc)?;
         ctx.set(tree)?;
     }
     for file in options.input.iter() {
@@ -1,10 +1,12 @@
 use super::*;
 use command::*;
 use context::build_context;
-use std::fs::{create_dir_all, remove_file};
+use std::fs::create_dir_all;
 use std::io::prelude::*;
-use std::path::PathBuf;
-use templar::Templar;
+use std::io::BufWriter;
+use std::path::{Path, PathBuf};
+use templar::ast::FormatOptions;
+use templar::{SandboxPolicy, Templar, TemplarBuilder, UndefinedPolicy};
 use util::*;
 
 mod command;
@@ -15,19 +17,61 @@ pub fn run() -> Result<()> {
     CommandContext::new(Command::parse()?)?.run()
 }
 
-#[derive(Debug)]
+fn sandbox_policy(cmd: &Command) -> Result<SandboxPolicy> {
+    let mut policy = match cmd.sandbox.as_str() {
+        "restricted" => SandboxPolicy::restricted(),
+        "pure" => SandboxPolicy::pure(),
+        _ => SandboxPolicy::trusted(),
+    };
+    let allows =
+        !cmd.allow_command.is_empty() || !cmd.allow_dir.is_empty() || !cmd.allow_env.is_empty();
+    if allows && cmd.sandbox != "restricted" {
+        return Err(TemplarError::RenderFailure(format!(
+            "The --allow options only apply with --sandbox restricted, not --sandbox {}",
+            cmd.sandbox
+        )));
+    }
+    cmd.allow_command.iter().for_each(|c| {
+        policy.allow_command(c);
+    });
+    cmd.allow_dir.iter().for_each(|d| {
+        policy.allow_dir(d);
+    });
+    cmd.allow_env.iter().for_each(|e| {
+        policy.allow_env(e);
+    });
+    Ok(policy)
+}
+
 struct CommandContext {
     cmd: Command,
     ctx: StandardContext,
+    templar: Templar,
 }
 
 impl CommandContext {
     fn new(cmd: Command) -> Result<Self> {
-        let ctx = build_context(&cmd)?;
-        Ok(CommandContext { cmd, ctx })
+        let mut builder = TemplarBuilder::default();
+        if cmd.strict {
+            builder.set_undefined_policy(UndefinedPolicy::Strict);
+        }
+        builder.set_sandbox_policy(sandbox_policy(&cmd)?);
+        let templar = builder.build();
+        let ctx = build_context(&cmd, &templar)?;
+        Ok(CommandContext { cmd, ctx, templar })
     }
 
     fn run(&self) -> Result<()> {
+        match &self.cmd.subcommand {
+            Some(SubCommand::Vars { template }) => return self.exec_vars(template.as_ref()),
+            Some(SubCommand::Lint { template }) => return self.exec_lint(template.as_ref()),
+            Some(SubCommand::Fmt {
+                template,
+                indent,
+                write,
+            }) => return self.exec_fmt(template.as_ref(), *indent, *write),
+            None => {}
+        }
         match (&self.cmd.expr, &self.cmd.template) {
             (Some(ref text), None) => self.exec_expression(text),
             (None, Some(ref file)) => self.exec_path(file),
@@ -39,7 +83,7 @@ impl CommandContext {
     fn exec_path(&self, file: &PathBuf) -> Result<()> {
         if file.is_file() {
             let template_contents = read_file(file)?;
-            self.render_file(Templar::global().parse_template(&template_contents)?)
+            self.render_file(self.templar.parse_template(&template_contents)?)
         } else if file.is_dir() {
             match (
                 self.cmd.recursive,
@@ -69,54 +113,122 @@ impl CommandContext {
     }
 
     fn exec_expression(&self, text: &str) -> Result<()> {
-        self.render_file(Templar::global().parse_expression(text)?)
+        self.render_file(self.templar.parse_expression(text)?)
     }
 
     fn exec_stdin(&self) -> Result<()> {
         let template_contents = read_stdin()?;
-        self.render_file(Templar::global().parse_template(&template_contents)?)
+        self.render_file(self.templar.parse_template(&template_contents)?)
+    }
+
+    fn exec_vars(&self, file: Option<&PathBuf>) -> Result<()> {
+        let template_contents = match file {
+            Some(file) => read_file(file)?,
+            None => read_stdin()?,
+        };
+        let tpl = self.templar.parse_template(&template_contents)?;
+        let vars = serde_json::json!({
+            "paths": tpl.referenced_paths(),
+            "filters": tpl.referenced_filters(),
+            "functions": tpl.referenced_functions(),
+        });
+        let output = serde_json::to_string_pretty(&vars).wrap()? + "\n";
+        match self.cmd.destination {
+            Some(ref file) => write_file(file, &output),
+            None => write_stdout(&output),
+        }
+    }
+
+    fn exec_lint(&self, file: Option<&PathBuf>) -> Result<()> {
+        let template_contents = match file {
+            Some(file) => read_file(file)?,
+            None => read_stdin()?,
+        };
+        let diagnostics: Vec<_> = self
+            .templar
+            .lint(&template_contents)?
+            .into_iter()
+            .map(|d| {
+                let (line, column) = d.span.line_col(&template_contents);
+                serde_json::json!({
+                    "code": d.kind.code(),
+                    "message": d.message,
+                    "span": { "start": d.span.start, "end": d.span.end },
+                    "line": line + 1,
+                    "column": column + 1,
+                })
+            })
+            .collect();
+        let output = serde_json::to_string_pretty(&diagnostics).wrap()? + "\n";
+        match self.cmd.destination {
+            Some(ref file) => write_file(file, &output),
+            None => write_stdout(&output),
+        }
+    }
+
+    fn exec_fmt(&self, file: Option<&PathBuf>, indent: Option<usize>, write: bool) -> Result<()> {
+        let template_contents = match file {
+            Some(file) => read_file(file)?,
+            None => read_stdin()?,
+        };
+        let options = FormatOptions { indent };
+        let output = self.templar.format_template(&template_contents, &options)?;
+        match (write, file, self.cmd.destination.as_ref()) {
+            (true, Some(file), _) | (false, _, Some(file)) => write_file(file, &output),
+            _ => write_stdout(&output),
+        }
     }
 
     fn render_recursive(&self, src: &PathBuf, dst: &PathBuf) -> Result<()> {
         if src.is_dir() {
             if dst.is_file() && self.cmd.force {
-                remove_file(dst)?;
-            } else if !dst.exists() {
-                create_dir_all(dst)?;
+                // The file is only replaced once the whole directory has rendered
+                return replace_path(dst, |tmp| self.render_dir(src, tmp));
             }
-            for entry in src.read_dir()? {
-                let p = entry?.path();
-                let filename = p.file_name().unwrap();
-                let mut newsrc = src.clone();
-                let mut newdst = dst.clone();
-                newsrc.push(filename);
-                newdst.push(filename);
-                self.render_recursive(&newsrc, &newdst)?;
-            }
-            Ok(())
+            self.render_dir(src, dst)
         } else {
             let template_contents = read_file(src)?;
-            let tpl = Templar::global().parse_template(&template_contents)?;
-            let output = tpl.render(&self.ctx)?;
-            if dst.is_file() {
-                if self.cmd.force {
-                    remove_file(dst)?;
-                } else {
-                    return Err(TemplarError::RenderFailure(format!(
-                        "Destination file '{}' exists!",
-                        dst.file_name().unwrap_or_default().to_string_lossy()
-                    )));
-                }
+            let tpl = self.templar.parse_template(&template_contents)?;
+            if dst.is_file() && !self.cmd.force {
+                return Err(TemplarError::RenderFailure(format!(
+                    "Destination file '{}' exists!",
+                    dst.file_name().unwrap_or_default().to_string_lossy()
+                )));
             }
-            write_file(dst, &output)
+            self.stream_file(&tpl, dst)
         }
     }
 
+    fn render_dir(&self, src: &Path, dst: &PathBuf) -> Result<()> {
+        if !dst.exists() {
+            create_dir_all(dst)?;
+        }
+        for entry in src.read_dir()? {
+            let p = entry?.path();
+            let filename = p.file_name().unwrap();
+            self.render_recursive(&src.join(filename), &dst.join(filename))?;
+        }
+        Ok(())
+    }
+
     fn render_file(&self, tpl: Template) -> Result<()> {
-        let output = tpl.render(&self.ctx)?;
         match self.cmd.destination {
-            Some(ref file) => write_file(file, &output),
-            None => write_stdout(&output),
+            Some(ref file) => self.stream_file(&tpl, file),
+            None => {
+                let stdout = std::io::stdout();
+                let mut out = BufWriter::new(stdout.lock());
+                tpl.render_to(&mut out, &self.ctx)?;
+                Ok(out.flush()?)
+            }
         }
     }
+
+    /// Render into the destination, keeping what was there before if the render fails
+    fn stream_file(&self, tpl: &Template, file: &PathBuf) -> Result<()> {
+        replace_path(file, |tmp| {
+            let mut out = create_file(tmp)?;
+            tpl.render_to(&mut out, &self.ctx)?;
+            Ok(out.flush()?)
+        })
+    }
 }
@@ -1,5 +1,6 @@
 use super::*;
-use std::fs::File;
+use std::fs::{remove_dir_all, remove_file, rename, set_permissions, symlink_metadata, File};
+use std::io::BufWriter;
 use std::path::PathBuf;
 
 pub fn read_stdin() -> Result<String> {
@@ -25,3 +26,49 @@ pub fn write_file(file: &PathBuf, contents: &str) -> Result<()> {
     f.write_all(contents.as_bytes())?;
     Ok(())
 }
+
+pub fn create_file(file: &PathBuf) -> Result<BufWriter<File>> {
+    Ok(BufWriter::new(File::create(file)?))
+}
+
+/// Remove whatever `write` left at `path`
+fn remove_path(path: &PathBuf) {
+    let _ = if path.is_dir() {
+        remove_dir_all(path)
+    } else {
+        remove_file(path)
+    };
+}
+
+/// Write `dst`, leaving what was there before in place if `write` fails. A regular file is
+/// written by way of a temporary path next to it, which only replaces the file once `write` has
+/// succeeded. Anything else, such as a device or a symlink, is written in place, and a new path
+/// is removed again if `write` fails.
+pub fn replace_path(dst: &PathBuf, write: impl FnOnce(&PathBuf) -> Result<()>) -> Result<()> {
+    let permissions = match symlink_metadata(dst) {
+        Ok(meta) if meta.file_type().is_file() => meta.permissions(),
+        Ok(_) => return write(dst),
+        Err(_) => {
+            let result = write(dst);
+            if result.is_err() {
+                remove_path(dst);
+            }
+            return result;
+        }
+    };
+    let name = dst.file_name().unwrap_or_default().to_string_lossy();
+    let tmp = dst.with_file_name(format!(".{}.templar-{}", name, std::process::id()));
+    let result = write(&tmp).and_then(|_| {
+        if tmp.is_dir() {
+            // A directory can't be renamed over a file
+            remove_file(dst)?;
+        } else {
+            set_permissions(&tmp, permissions)?;
+        }
+        Ok(rename(&tmp, dst)?)
+    });
+    if result.is_err() {
+        remove_path(&tmp);
+    }
+    result
+}
@@ -0,0 +1,32 @@
+use super::*;
+use std::sync::Arc;
+
+/// A read-only set of values captured from another context, such as the values a lambda refers to
+#[derive(Debug, Clone, Default)]
+pub struct CapturedContext(Arc<ContextMap>);
+
+impl CapturedContext {
+    pub fn new(map: ContextMap) -> Self {
+        CapturedContext(Arc::new(map))
+    }
+}
+
+impl Context for CapturedContext {
+    fn set_path_inner(&self, _: &[&InnerData], _: ContextMapValue) -> Result<()> {
+        Err(TemplarError::ContextFailure(
+            "Captured values cannot be modified".into(),
+        ))
+    }
+
+    fn get_path_inner(&self, path: &[&InnerData], ctx: &impl Context) -> Data {
+        self.0.exec(ctx, path, None)
+    }
+
+    fn get_interned_inner(&self, path: &ContextPath, ctx: &impl Context) -> Data {
+        self.0.exec_path(ctx, path)
+    }
+
+    fn wrap(&self) -> ContextWrapper<'_> {
+        ContextWrapper::Captured(self)
+    }
+}
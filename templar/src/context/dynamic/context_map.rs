@@ -3,8 +3,16 @@ use super::*;
 #[derive(Clone, Debug, Default)]
 pub struct ContextMap {
     root: BTreeMap<InnerData, ContextMapValue>,
+    /// Bumped on every change, so that cached lookups from before it are ignored
+    version: u64,
 }
 
+/// The results of static lookups in a context map by the id of their path, together with the
+/// version of the map they were made at. Results from an older version are dropped as soon as a
+/// lookup is made at a newer one.
+#[derive(Debug, Default)]
+pub struct LookupCache(Mutex<(u64, HashMap<usize, Data>)>);
+
 impl ContextMap {
     pub fn new<T: Into<ContextMapValue>>(doc: T) -> Self {
         let mut result = ContextMap::default();
@@ -13,6 +21,7 @@ impl ContextMap {
     }
 
     pub fn set<T: Into<ContextMapValue>>(&mut self, value: T, path: &[&InnerData]) -> Result<()> {
+        self.version += 1;
         if path.is_empty() {
             let val: ContextMapValue = value.into();
             if let ContextMapValue::Map(map) = val {
@@ -31,22 +40,79 @@ impl ContextMap {
             .entry(path[0].clone())
             .or_insert_with(ContextMapValue::new_map);
         for p in path.iter().skip(1).take(path.len() - 1) {
-            target = target.get_or_add_key(*p);
+            target = target.get_or_add_key(p);
         }
         target.set(value.into());
         Ok(())
     }
 
-    pub fn exec(&self, ctx: &impl Context, path: &[&InnerData]) -> Data {
+    pub fn exec(&self, ctx: &impl Context, path: &[&InnerData], memo: Option<&Memo>) -> Data {
+        if path.is_empty() {
+            return ContextMapValue::exec_map(&self.root, ctx, memo, &ValueName::Root);
+        }
+        let name = PathName(path);
+        self.walk(ctx, path.iter().copied(), memo, &ValueName::Path(&name))
+            .exec(ctx)
+    }
+
+    /// Execute an interned path
+    pub fn exec_path(&self, ctx: &impl Context, path: &ContextPath) -> Data {
+        if path.is_empty() {
+            return ContextMapValue::exec_map(&self.root, ctx, None, &ValueName::Root);
+        }
+        let name = PathName(path);
+        self.walk(ctx, path.iter(), None, &ValueName::Path(&name))
+            .exec(ctx)
+    }
+
+    /// Execute an interned path, reusing the previous result if the path only reads static values
+    /// and the map has not changed since. Dynamic values are executed every time, as what they
+    /// read may have changed elsewhere, unless they are memoised.
+    pub fn exec_cached(
+        &self,
+        ctx: &impl Context,
+        path: &ContextPath,
+        cache: &LookupCache,
+        memo: Option<&Memo>,
+    ) -> Data {
         if path.is_empty() {
-            let copy = ContextMapValue::Map(self.root.clone());
-            return copy.exec(ctx);
+            return ContextMapValue::exec_map(&self.root, ctx, memo, &ValueName::Root);
         }
-        let walker = ContextWalk::from(self.root.get(&path[0]));
-        for p in path.iter().skip(1) {
+        if let Ok(cache) = cache.0.try_lock() {
+            if cache.0 == self.version {
+                if let Some(data) = cache.1.get(&path.id()) {
+                    return data.clone();
+                }
+            }
+        }
+        let path_name = PathName(path);
+        let name = ValueName::Path(&path_name);
+        let walker = self.walk(ctx, path.iter(), memo, &name);
+        let result = walker.exec(ctx);
+        if walker.is_static() {
+            if let Ok(mut cache) = cache.0.try_lock() {
+                if cache.0 != self.version {
+                    *cache = (self.version, HashMap::new());
+                }
+                cache.1.insert(path.id(), result.clone());
+            }
+        }
+        result
+    }
+
+    fn walk<'a>(
+        &'a self,
+        ctx: &impl Context,
+        mut path: impl Iterator<Item = &'a InnerData>,
+        memo: Option<&'a Memo>,
+        name: &'a ValueName<'a>,
+    ) -> ContextWalk<'a> {
+        let start = path.next().and_then(|key| self.root.get(key));
+        let mut walker = ContextWalk::new(start, memo, name);
+        for p in path {
             walker.walk(ctx, p);
         }
-        walker.exec(ctx)
+        walker
     }
 }
 
@@ -75,6 +141,15 @@ impl ContextMapValue {
         ContextMapValue::Map(BTreeMap::new())
     }
 
+    /// Whether this is a plain value rather than a template or a collection that may hold one
+    pub(crate) fn is_static(&self) -> bool {
+        match self {
+            ContextMapValue::Node(node) => matches!(**node, Node::Data(_)),
+            ContextMapValue::Empty => true,
+            _ => false,
+        }
+    }
+
     fn set<T: Into<ContextMapValue>>(&mut self, val: T) {
         drop(replace(self, val.into()));
     }
@@ -93,22 +168,39 @@ impl ContextMapValue {
     }
 
     pub fn exec(&self, ctx: &impl Context) -> Data {
+        self.exec_memo(ctx, None, &ValueName::Root)
+    }
+
+    /// Execute the value, reusing the results of dynamic values kept by `memo`. Dynamic values
+    /// are named by `name` if they turn out to depend on themselves.
+    pub(crate) fn exec_memo(
+        &self,
+        ctx: &impl Context,
+        memo: Option<&Memo>,
+        name: &ValueName<'_>,
+    ) -> Data {
         match self {
-            ContextMapValue::Node(node) => node.exec(ctx),
-            ContextMapValue::Map(map) => {
-                let mut result: BTreeMap<InnerData, InnerData> = BTreeMap::new();
-                for (k, v) in map.iter() {
-                    match v.exec(ctx).into_result() {
-                        Ok(d) => result.insert(k.clone(), d.into_inner()),
-                        Err(e) => return e.into(),
-                    };
-                }
-                result.into()
+            ContextMapValue::Node(node) if !self.is_static() => {
+                let resolving = match Resolving::enter(node, name) {
+                    Ok(resolving) => resolving,
+                    Err(e) => return e.into(),
+                };
+                let result = match memo {
+                    Some(memo) => memo.exec(node, ctx),
+                    None => node.exec(ctx),
+                };
+                resolving.finish(name, result)
             }
+            ContextMapValue::Node(node) => node.exec(ctx),
+            ContextMapValue::Map(map) => Self::exec_map(map, ctx, memo, name),
             ContextMapValue::Seq(s) => {
                 let result: Result<Vec<InnerData>> = s
                     .iter()
-                    .map(|v| Ok(v.exec(ctx).into_result()?.into_inner()))
+                    .enumerate()
+                    .map(|(i, v)| {
+                        let name = ValueName::Entry(name, &i);
+                        Ok(v.exec_memo(ctx, memo, &name).into_result()?.into_inner())
+                    })
                     .collect();
                 match result {
                     Ok(s) => Data::new(s),
@@ -118,22 +210,42 @@ impl ContextMapValue {
             _ => Data::empty(),
         }
     }
+
+    /// Execute every value of a map in place, building the result in a single pass over the
+    /// already sorted entries. The result is still a full copy of the map, as `Data` owns its
+    /// value, so every key and static value is cloned into it.
+    fn exec_map(
+        map: &BTreeMap<InnerData, ContextMapValue>,
+        ctx: &impl Context,
+        memo: Option<&Memo>,
+        name: &ValueName<'_>,
+    ) -> Data {
+        let result: Result<BTreeMap<InnerData, InnerData>> = map
+            .iter()
+            .map(|(k, v)| {
+                let name = ValueName::Entry(name, k);
+                Ok((
+                    k.clone(),
+                    v.exec_memo(ctx, memo, &name).into_result()?.into_inner(),
+                ))
+            })
+            .collect();
+        match result {
+            Ok(m) => m.into(),
+            Err(e) => e.into(),
+        }
+    }
 }
 
-#[derive(Clone, Debug)]
+#[derive(Clone, Debug, Default)]
 pub enum ContextMapValue {
     Seq(Vec<ContextMapValue>),
     Map(BTreeMap<InnerData, ContextMapValue>),
     Node(Arc<Node>),
+    #[default]
     Empty,
 }
 
-impl Default for ContextMapValue {
-    fn default() -> Self {
-        ContextMapValue::Empty
-    }
-}
-
 impl<T: Into<InnerData>> From<T> for ContextMapValue {
     fn from(val: T) -> Self {
         match val.into() {
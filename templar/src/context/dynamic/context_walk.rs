@@ -1,60 +1,69 @@
 use super::*;
 
 pub struct ContextWalk<'a> {
-    inner: std::cell::RefCell<ContextWalkValue<'a>>,
+    inner: ContextWalkValue<'a>,
+    dynamic: bool,
+    memo: Option<&'a Memo>,
+    /// The path being walked, to name the dynamic values executed along it
+    path: &'a ValueName<'a>,
 }
 
 impl<'a> ContextWalk<'a> {
+    pub fn new(
+        start: Option<&'a ContextMapValue>,
+        memo: Option<&'a Memo>,
+        path: &'a ValueName<'a>,
+    ) -> Self {
+        ContextWalk {
+            inner: start.into(),
+            dynamic: false,
+            memo,
+            path,
+        }
+    }
+
     pub fn exec(&self, ctx: &impl Context) -> Data {
-        let val = &*self.inner.borrow();
-        match val {
+        match &self.inner {
             ContextWalkValue::None => Data::empty(),
             ContextWalkValue::Owned(v) => v.exec(ctx),
-            ContextWalkValue::Ref(v) => v.exec(ctx),
+            ContextWalkValue::Ref(v) => v.exec_memo(ctx, self.memo, self.path),
         }
     }
 
-    pub fn walk(&'a self, ctx: &impl Context, key: &InnerData) {
-        let val = self.inner.borrow();
-        let mut new_walk = None;
-        let mut res = None;
-        match &*val {
-            ContextWalkValue::Ref(ContextMapValue::Map(m)) => new_walk = Some(m.get(key).into()),
-            ContextWalkValue::Ref(val) => res = Some(val.exec(ctx)),
-            ContextWalkValue::Owned(val) => res = Some(val.exec(ctx)),
-            _ => return,
+    pub fn walk(&mut self, ctx: &impl Context, key: &InnerData) {
+        let res = match replace(&mut self.inner, ContextWalkValue::None) {
+            ContextWalkValue::Ref(ContextMapValue::Map(m)) => {
+                self.inner = m.get(key).into();
+                return;
+            }
+            ContextWalkValue::Ref(val) => {
+                self.dynamic |= !val.is_static();
+                val.exec_memo(ctx, self.memo, self.path)
+            }
+            ContextWalkValue::Owned(val) => val.exec(ctx),
+            ContextWalkValue::None => return,
+        };
+        self.inner = if res.is_empty() {
+            ContextWalkValue::None
+        } else if res.is_failed() {
+            res.into()
+        } else {
+            match res.into_inner() {
+                InnerData::Map(mut m) => m.remove(key).into(),
+                InnerData::Err(e) => e.into(),
+                other => other.into(),
+            }
         };
-        drop(val);
-        if let Some(res) = res {
-            new_walk = Some(if res.is_failed() || res.is_empty() {
-                res.into()
-            } else {
-                match res.into_inner() {
-                    InnerData::Map(m) => m.get(key).cloned().into(),
-                    InnerData::Err(e) => e.into(),
-                    other => other.into(),
-                }
-            })
-        }
-        let inner_mut: &mut ContextWalkValue<'a> = &mut self.inner.borrow_mut();
-        drop(replace(inner_mut, new_walk.unwrap()));
-    }
-}
-
-impl<'a> From<ContextWalkValue<'a>> for ContextWalk<'a> {
-    fn from(val: ContextWalkValue<'a>) -> Self {
-        ContextWalk {
-            inner: std::cell::RefCell::new(val),
-        }
     }
-}
 
-impl<'a> From<Option<&'a ContextMapValue>> for ContextWalk<'a> {
-    fn from(val: Option<&'a ContextMapValue>) -> Self {
-        match val {
-            Some(v) => ContextWalkValue::Ref(v).into(),
-            None => ContextWalkValue::from(Data::empty()).into(),
-        }
+    /// Whether the walk only went through static values, so that executing it again will give
+    /// the same result for as long as the context is unchanged
+    pub fn is_static(&self) -> bool {
+        !self.dynamic
+            && match &self.inner {
+                ContextWalkValue::Ref(v) => v.is_static(),
+                _ => true,
+            }
     }
 }
 
@@ -0,0 +1,151 @@
+use super::*;
+use std::cell::RefCell;
+
+thread_local! {
+    /// The reads of each memoised value being executed on this thread, innermost last
+    static RECORDING: RefCell<Vec<Reads>> = const { RefCell::new(Vec::new()) };
+}
+
+/// The paths a dynamic value read while it was executed
+struct Reads {
+    paths: Vec<Vec<InnerData>>,
+    /// Cleared if the value read a scope or a volatile value, or failed
+    storable: bool,
+}
+
+/// Pops the reads of a value off the stack, even if executing it panicked
+struct Recording(usize);
+
+impl Recording {
+    fn start() -> Self {
+        RECORDING.with(|r| {
+            let mut r = r.borrow_mut();
+            r.push(Reads {
+                paths: vec![],
+                storable: true,
+            });
+            Recording(r.len() - 1)
+        })
+    }
+
+    fn finish(self) -> Reads {
+        RECORDING.with(|r| r.borrow_mut().remove(self.0))
+    }
+}
+
+impl Drop for Recording {
+    fn drop(&mut self) {
+        RECORDING.with(|r| r.borrow_mut().truncate(self.0));
+    }
+}
+
+/// Apply `f` to the reads of the innermost value being executed, if any
+fn recording(f: impl FnOnce(&mut Reads)) {
+    RECORDING.with(|r| {
+        if let Some(reads) = r.borrow_mut().last_mut() {
+            f(reads)
+        }
+    })
+}
+
+/// Note a read of the context, as a dependency of the value being executed
+pub(crate) fn record_read<'a>(path: impl Iterator<Item = &'a InnerData>) {
+    recording(|reads| reads.paths.push(path.cloned().collect()))
+}
+
+/// Note that the value being executed read a value bound in a scope, which a later read from
+/// somewhere else would not see
+pub(crate) fn record_scoped_read() {
+    recording(|reads| reads.storable = false)
+}
+
+/// The results of the dynamic values of a memoising context, see `StandardContext::memoized`
+#[derive(Debug, Default)]
+pub struct Memo(Mutex<Results>);
+
+#[derive(Debug, Default)]
+struct Results {
+    entries: HashMap<usize, Memoised>,
+    /// Bumped by every invalidation, so that a result computed while another thread wrote to the
+    /// context is not stored after the write has invalidated the results it affects
+    generation: u64,
+}
+
+#[derive(Debug)]
+struct Memoised {
+    /// Kept so that the address used as the key is not reused by another value
+    node: Arc<Node>,
+    result: Data,
+    reads: Arc<Vec<Vec<InnerData>>>,
+}
+
+impl Memo {
+    /// Execute a dynamic value, or reuse its result if nothing it read has been written since
+    pub(crate) fn exec(&self, node: &Arc<Node>, ctx: &impl Context) -> Data {
+        let key = Arc::as_ptr(node) as usize;
+        let (hit, generation) = match self.0.lock() {
+            Ok(memo) => (
+                memo.entries
+                    .get(&key)
+                    .map(|entry| (entry.result.clone(), entry.reads.clone())),
+                Some(memo.generation),
+            ),
+            Err(_) => (None, None),
+        };
+        // A scope binding one of the paths the value read would change its result
+        if let Some((result, reads)) = hit {
+            if !reads.iter().any(|path| ctx.shadows(path)) {
+                recording(|outer| outer.paths.extend(reads.iter().cloned()));
+                return result;
+            }
+        }
+        let started = Recording::start();
+        let result = node.exec(ctx);
+        let reads = started.finish();
+        let storable = reads.storable && !result.is_failed() && !is_volatile(node);
+        recording(|outer| {
+            outer.paths.extend(reads.paths.iter().cloned());
+            outer.storable &= storable;
+        });
+        if storable {
+            if let Ok(mut memo) = self.0.lock() {
+                if Some(memo.generation) == generation {
+                    let entry = Memoised {
+                        node: node.clone(),
+                        result: result.clone(),
+                        reads: Arc::new(reads.paths),
+                    };
+                    memo.entries.insert(key, entry);
+                }
+            }
+        }
+        result
+    }
+
+    /// Forget every result that read the written path, or anything beneath or above it. Results
+    /// of values that have been replaced are dropped as well.
+    pub(crate) fn invalidate(&self, written: &[&InnerData]) {
+        if let Ok(mut memo) = self.0.lock() {
+            memo.generation += 1;
+            memo.entries.retain(|_, entry| {
+                Arc::strong_count(&entry.node) > 1
+                    && !entry
+                        .reads
+                        .iter()
+                        .any(|read| read.iter().zip(written.iter()).all(|(r, w)| r == *w))
+            });
+        }
+    }
+}
+
+/// Whether the value calls `volatile`, and so must be executed on every read
+fn is_volatile(node: &Node) -> bool {
+    let mut volatile = false;
+    node.walk(&mut |n| {
+        if let Node::Operation(op) = n {
+            volatile |= op.name() == "volatile" && matches!(op.executor(), Executors::Function(_));
+        }
+        !volatile
+    });
+    volatile
+}
@@ -1,11 +1,17 @@
 mod context_map;
 mod context_walk;
+mod memo;
+mod resolving;
 
 pub use context_map::*;
 pub use context_walk::*;
+pub use memo::Memo;
+pub(crate) use memo::{record_read, record_scoped_read};
+pub(crate) use resolving::PathName;
+use resolving::{Resolving, ValueName};
 
 use super::*;
 use crate::execution::{Data, Node};
-use std::collections::BTreeMap;
+use std::collections::{BTreeMap, HashMap};
 use std::mem::replace;
-pub use unstructured::Document;
+use std::sync::{Arc, Mutex};
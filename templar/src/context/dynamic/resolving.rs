@@ -0,0 +1,132 @@
+use super::*;
+use std::borrow::Borrow;
+use std::cell::RefCell;
+use std::fmt;
+
+thread_local! {
+    /// The dynamic values being executed on this thread, outermost first
+    static RESOLVING: RefCell<Vec<usize>> = const { RefCell::new(Vec::new()) };
+    /// A cycle found on this thread that the values in it are still returning from
+    static CYCLE: RefCell<Option<Cycle>> = const { RefCell::new(None) };
+}
+
+/// The names of the values in a cycle, innermost first. Each value adds its name as it returns,
+/// as the names are only formatted once a cycle is found.
+struct Cycle {
+    /// Where the value the cycle starts and ends with is in `RESOLVING`
+    start: usize,
+    /// Where the next value to return that is part of the cycle is in `RESOLVING`
+    next: usize,
+    names: Vec<String>,
+}
+
+/// Pops a dynamic value off the stack once it has been executed, even if executing it panicked
+pub(crate) struct Resolving(usize);
+
+impl Resolving {
+    /// Note that `node` is being executed for a lookup of `name`. Fails if it is already being
+    /// executed further up the stack, as it would then only ever execute itself again.
+    pub(crate) fn enter(node: &Arc<Node>, name: &ValueName<'_>) -> Result<Self> {
+        let key = Arc::as_ptr(node) as usize;
+        RESOLVING.with(|r| {
+            let mut r = r.borrow_mut();
+            if let Some(start) = r.iter().position(|k| *k == key) {
+                let cycle = Cycle {
+                    start,
+                    next: r.len() - 1,
+                    names: vec![name.to_string()],
+                };
+                CYCLE.with(|c| *c.borrow_mut() = Some(cycle));
+                return Err(cycle_error(&[]));
+            }
+            r.push(key);
+            Ok(Resolving(r.len() - 1))
+        })
+    }
+
+    /// Finish executing the value looked up by `name`. If the value is part of a cycle its name is
+    /// added to it, and the value the cycle started with fails with all of their names.
+    pub(crate) fn finish(self, name: &ValueName<'_>, result: Data) -> Data {
+        CYCLE.with(|c| {
+            let mut c = c.borrow_mut();
+            let cycle = match c.as_mut() {
+                Some(cycle) if cycle.next == self.0 => cycle,
+                _ => return result,
+            };
+            cycle.names.push(name.to_string());
+            if cycle.start < self.0 {
+                cycle.next -= 1;
+                return result;
+            }
+            let names = c.take().map(|cycle| cycle.names).unwrap_or_default();
+            if result.is_failed() {
+                cycle_error(&names).into()
+            } else {
+                result
+            }
+        })
+    }
+}
+
+impl Drop for Resolving {
+    fn drop(&mut self) {
+        RESOLVING.with(|r| r.borrow_mut().truncate(self.0));
+        // A cycle that has not been finished by the value it started with has been left behind
+        CYCLE.with(|c| {
+            let mut c = c.borrow_mut();
+            if c.as_ref().is_some_and(|cycle| cycle.start >= self.0) {
+                *c = None;
+            }
+        });
+    }
+}
+
+/// The error for a cycle between the values with `names`, given innermost first
+fn cycle_error(names: &[String]) -> TemplarError {
+    let mut message = "Dynamic values refer to each other in a cycle".to_string();
+    if !names.is_empty() {
+        let names: Vec<&str> = names.iter().rev().map(String::as_str).collect();
+        message = format!("{}: {}", message, names.join(" -> "));
+    }
+    TemplarError::ContextFailure(message)
+}
+
+/// Names a value of the context for the cycle error, such as `user.name` or `servers.0.host`
+#[derive(Clone, Copy)]
+pub(crate) enum ValueName<'a> {
+    /// The whole context
+    Root,
+    /// A value looked up by its path
+    Path(&'a dyn fmt::Display),
+    /// An entry of a map or sequence, by its key or index
+    Entry(&'a ValueName<'a>, &'a dyn fmt::Display),
+}
+
+impl fmt::Display for ValueName<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ValueName::Root => write!(f, "."),
+            ValueName::Path(path) => write!(f, "{}", path),
+            ValueName::Entry(ValueName::Root, key) => write!(f, "{}", key),
+            ValueName::Entry(parent, key) => write!(f, "{}.{}", parent, key),
+        }
+    }
+}
+
+/// Displays the keys of a path the way they are written in a template, such as `user.name`
+pub(crate) struct PathName<'a, K>(pub(crate) &'a [K]);
+
+impl<K: Borrow<InnerData>> fmt::Display for PathName<'_, K> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if self.0.is_empty() {
+            return write!(f, ".");
+        }
+        for (i, key) in self.0.iter().enumerate() {
+            if i > 0 {
+                write!(f, ".")?;
+            }
+            write!(f, "{}", key.borrow())?;
+        }
+        Ok(())
+    }
+}
@@ -4,9 +4,11 @@ use std::fmt::Debug;
 mod dynamic;
 pub use dynamic::*;
 
+mod captured;
 mod scoped;
 mod standard;
 
+pub(crate) use captured::CapturedContext;
 pub(crate) use scoped::ScopedContext;
 pub use standard::StandardContext;
 
@@ -17,7 +19,16 @@ pub trait Context: Sized {
     #[doc(hidden)]
     fn get_path_inner(&self, path: &[&InnerData], ctx: &impl Context) -> Data;
     #[doc(hidden)]
-    fn wrap(&self) -> ContextWrapper;
+    fn wrap(&self) -> ContextWrapper<'_>;
+    #[doc(hidden)]
+    fn get_interned_inner(&self, path: &ContextPath, ctx: &impl Context) -> Data {
+        let refs: Vec<&InnerData> = path.iter().collect();
+        self.get_path_inner(&refs, ctx)
+    }
+    #[doc(hidden)]
+    fn shadows(&self, _path: &[InnerData]) -> bool {
+        false
+    }
 
     /// Merge the data into the root context
     #[inline]
@@ -55,7 +66,7 @@ pub trait Context: Sized {
 
     /// Enter a new scope
     #[inline]
-    fn create_scope(&self) -> ScopedContext {
+    fn create_scope(&self) -> ScopedContext<'_> {
         ScopedContext::new(self.wrap())
     }
 
@@ -65,7 +76,8 @@ pub trait Context: Sized {
         self.set_path_inner(path, doc.into())
     }
 
-    /// Get the root context value
+    /// Get the root context value. This builds a copy of the whole context, with every dynamic
+    /// value executed.
     #[inline]
     fn get(&self) -> Data {
         self.get_path_inner(&[], self)
@@ -82,6 +94,7 @@ pub trait Context: Sized {
 pub enum ContextWrapper<'a> {
     Standard(&'a StandardContext),
     Scope(&'a ScopedContext<'a>),
+    Captured(&'a CapturedContext),
 }
 
 impl<'a> Context for ContextWrapper<'a> {
@@ -89,6 +102,7 @@ impl<'a> Context for ContextWrapper<'a> {
         match self {
             Self::Standard(c) => c.set_path_inner(path, doc),
             Self::Scope(c) => c.set_path_inner(path, doc),
+            Self::Captured(c) => c.set_path_inner(path, doc),
         }
     }
 
@@ -96,13 +110,31 @@ impl<'a> Context for ContextWrapper<'a> {
         match self {
             Self::Standard(c) => c.get_path_inner(path, ctx),
             Self::Scope(c) => c.get_path_inner(path, ctx),
+            Self::Captured(c) => c.get_path_inner(path, ctx),
+        }
+    }
+
+    fn get_interned_inner(&self, path: &ContextPath, ctx: &impl Context) -> Data {
+        match self {
+            Self::Standard(c) => c.get_interned_inner(path, ctx),
+            Self::Scope(c) => c.get_interned_inner(path, ctx),
+            Self::Captured(c) => c.get_interned_inner(path, ctx),
+        }
+    }
+
+    fn shadows(&self, path: &[InnerData]) -> bool {
+        match self {
+            Self::Standard(c) => c.shadows(path),
+            Self::Scope(c) => c.shadows(path),
+            Self::Captured(c) => c.shadows(path),
         }
     }
 
-    fn wrap(&self) -> ContextWrapper {
+    fn wrap(&self) -> ContextWrapper<'_> {
         match self {
             Self::Standard(c) => c.wrap(),
             Self::Scope(c) => c.wrap(),
+            Self::Captured(c) => c.wrap(),
         }
     }
 }
@@ -18,15 +18,32 @@ impl<'a> Context for ScopedContext<'a> {
     }
 
     fn get_path_inner(&self, path: &[&InnerData], ctx: &impl Context) -> Data {
-        let local = self.1.borrow().exec(ctx, path);
+        let local = self.1.borrow().exec(ctx, path, None);
         if local.is_empty() {
             self.0.get_path_inner(path, ctx)
         } else {
+            record_scoped_read();
             local
         }
     }
 
-    fn wrap(&self) -> ContextWrapper {
+    /// Scopes change with every iteration of a loop, so only the context beneath them is cached
+    fn get_interned_inner(&self, path: &ContextPath, ctx: &impl Context) -> Data {
+        let local = self.1.borrow().exec_path(ctx, path);
+        if local.is_empty() {
+            self.0.get_interned_inner(path, ctx)
+        } else {
+            record_scoped_read();
+            local
+        }
+    }
+
+    fn shadows(&self, path: &[InnerData]) -> bool {
+        let refs: Vec<&InnerData> = path.iter().collect();
+        !self.1.borrow().exec(self, &refs, None).is_empty() || self.0.shadows(path)
+    }
+
+    fn wrap(&self) -> ContextWrapper<'_> {
         ContextWrapper::Scope(self)
     }
 }
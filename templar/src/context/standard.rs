@@ -4,7 +4,7 @@ use {parking_lot::RwLock, std::sync::Arc};
 
 /// This context type can be shared between threads safely
 #[derive(Debug, Clone)]
-pub struct StandardContext(Arc<RwLock<ContextMap>>);
+pub struct StandardContext(Arc<RwLock<ContextMap>>, Arc<LookupCache>, Option<Arc<Memo>>);
 
 impl Default for StandardContext {
     fn default() -> Self {
@@ -15,20 +15,60 @@ impl Default for StandardContext {
 impl StandardContext {
     /// Create a new empty shared context
     pub fn new() -> Self {
-        StandardContext(Arc::new(RwLock::new(ContextMap::new(InnerData::Null))))
+        StandardContext(
+            Arc::new(RwLock::new(ContextMap::new(InnerData::Null))),
+            Default::default(),
+            None,
+        )
+    }
+
+    /// Create a new empty shared context that memoises its dynamic values. The result of a
+    /// template set in the context is kept along with the paths it read, and reused until one of
+    /// those paths is written with `set_path`. Values that call `volatile()` are executed on every
+    /// read, as are values that read a variable bound in a scope, such as a loop variable.
+    ///
+    /// # Usage
+    ///
+    /// ```
+    /// # use templar::*;
+    /// let context = StandardContext::memoized();
+    /// context.set_path(&[&"name".into()], "world")?;
+    /// context.set_path(&[&"greeting".into()], Templar::global().parse("hello {{ name }}")?)?;
+    /// let template = Templar::global().parse("{{ greeting }}")?;
+    /// assert_eq!(template.render(&context)?, "hello world");
+    /// context.set_path(&[&"name".into()], "there")?;
+    /// assert_eq!(template.render(&context)?, "hello there");
+    /// # Ok::<(), templar::TemplarError>(())
+    /// ```
+    pub fn memoized() -> Self {
+        let mut context = Self::new();
+        context.2 = Some(Default::default());
+        context
     }
 }
 
 impl Context for StandardContext {
     fn set_path_inner(&self, path: &[&InnerData], doc: ContextMapValue) -> Result<()> {
-        self.0.write().set(doc, path)
+        let result = self.0.write().set(doc, path);
+        if let Some(memo) = &self.2 {
+            memo.invalidate(path);
+        }
+        result
     }
 
     fn get_path_inner(&self, path: &[&InnerData], ctx: &impl Context) -> Data {
-        self.0.read().exec(ctx, path)
+        record_read(path.iter().copied());
+        self.0.read().exec(ctx, path, self.2.as_deref())
+    }
+
+    fn get_interned_inner(&self, path: &ContextPath, ctx: &impl Context) -> Data {
+        record_read(path.iter());
+        self.0
+            .read()
+            .exec_cached(ctx, path, &self.1, self.2.as_deref())
     }
 
-    fn wrap(&self) -> ContextWrapper {
+    fn wrap(&self) -> ContextWrapper<'_> {
         ContextWrapper::Standard(self)
     }
 }
@@ -19,6 +19,16 @@ pub enum TemplarError {
     FilterNotFound(String),
     /// Function referred to by template is not available
     FunctionNotFound(String),
+    /// Test referred to by template is not available
+    TestNotFound(String),
+    /// A template rendered an undefined value while using the strict undefined policy
+    UndefinedValue(String),
+    /// A render went over one of its `RenderLimits`
+    LimitExceeded(String),
+    /// A function was called in a way the sandbox policy does not allow
+    PermissionDenied(String),
+    /// Named template could not be found by any of the configured loaders
+    TemplateNotFound(String),
     /// An I/O error occurred
     IO(String),
     /// Some other error, check the inner value
@@ -41,6 +51,17 @@ impl fmt::Display for TemplarError {
                 "Function '{}' was not found while building this expression",
                 s
             ),
+            TemplarError::TestNotFound(s) => write!(
+                f,
+                "Test '{}' was not found while building this expression",
+                s
+            ),
+            TemplarError::UndefinedValue(s) => {
+                write!(f, "Could not render template. '{}' is undefined", s)
+            }
+            TemplarError::LimitExceeded(s) => write!(f, "Render limit exceeded. {}", s),
+            TemplarError::PermissionDenied(s) => write!(f, "Permission denied. {}", s),
+            TemplarError::TemplateNotFound(s) => write!(f, "Template '{}' was not found", s),
             TemplarError::IO(s) => write!(f, "An IO Error occurred. {}", s),
             TemplarError::Other(e) => e.fmt(f),
         }
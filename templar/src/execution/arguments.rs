@@ -0,0 +1,91 @@
+use super::*;
+
+/// The arguments of a filter, function or test call that used named arguments, e.g. `join(sep=', ')`.
+///
+/// Calls made with only positional arguments are passed to the callee as they always have been, so
+/// `Data::into_arguments` should be used to handle both forms.
+#[derive(Clone, Debug, Default)]
+pub struct Arguments {
+    positional: Vec<InnerData>,
+    named: BTreeMap<String, InnerData>,
+}
+
+impl Arguments {
+    /// Create a new set of arguments
+    pub fn new(positional: Vec<InnerData>, named: BTreeMap<String, InnerData>) -> Self {
+        Arguments { positional, named }
+    }
+
+    /// The positional arguments, in order
+    pub fn positional(&self) -> &[InnerData] {
+        &self.positional
+    }
+
+    /// The named arguments
+    pub fn named(&self) -> &BTreeMap<String, InnerData> {
+        &self.named
+    }
+
+    /// Take the parameter at `index` whether it was passed by position or by `name`. Returns
+    /// an error if the parameter was passed both ways.
+    pub fn take(&mut self, index: usize, name: &str) -> Result<Option<InnerData>> {
+        let positional = self.positional.get_mut(index).map(|v| v.take());
+        match (positional, self.named.remove(name)) {
+            (Some(_), Some(_)) => Err(TemplarError::RenderFailure(format!(
+                "Argument '{}' was passed by position and by name",
+                name
+            ))),
+            (Some(v), None) | (None, Some(v)) => Ok(Some(v)),
+            (None, None) => Ok(None),
+        }
+    }
+
+    /// Take an argument that can only be passed by name
+    pub fn take_named(&mut self, name: &str) -> Option<InnerData> {
+        self.named.remove(name)
+    }
+
+    /// Check that every argument was used by a callee accepting `count` parameters
+    pub fn finish(self, count: usize) -> Result<()> {
+        if self.positional.len() > count {
+            return Err(TemplarError::RenderFailure(format!(
+                "This method expects at most {} arguments but {} were supplied",
+                count,
+                self.positional.len()
+            )));
+        }
+        match self.named.keys().next() {
+            Some(name) => Err(TemplarError::RenderFailure(format!(
+                "Unexpected argument '{}'",
+                name
+            ))),
+            None => Ok(()),
+        }
+    }
+}
+
+impl fmt::Display for Arguments {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let positional = self.positional.iter().map(|v| v.to_string());
+        let named = self.named.iter().map(|(k, v)| format!("{}={}", k, v));
+        let all: Vec<String> = positional.chain(named).collect();
+        write!(f, "({})", all.join(", "))
+    }
+}
+
+impl Data {
+    /// Interpret this data as the arguments of a call that accepts `count` parameters.
+    ///
+    /// Named arguments are passed as `Arguments` directly. Otherwise a callee accepting more than
+    /// one parameter receives a sequence of values, and a callee accepting one receives the value itself.
+    pub fn into_arguments(self, count: usize) -> Result<Arguments> {
+        let positional = match self.into_inner() {
+            InnerData::Other(OtherData::Arguments(args)) => return Ok(args),
+            InnerData::Err(e) => return Err(e),
+            InnerData::Unassigned => vec![],
+            InnerData::Seq(s) if count > 1 => s,
+            val => vec![val],
+        };
+        Ok(Arguments::new(positional, BTreeMap::new()))
+    }
+}
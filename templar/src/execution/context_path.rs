@@ -0,0 +1,76 @@
+use super::*;
+use std::collections::HashMap;
+use std::ops::Deref;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{Mutex, MutexGuard, Weak};
+
+lazy_static! {
+    /// The paths in use, each of which is removed again once the last template using it is dropped
+    static ref INTERNED: Mutex<HashMap<Vec<InnerData>, Weak<Interned>>> = Mutex::new(HashMap::new());
+}
+
+/// Ids are never reused, so a lookup cached for a path that was dropped can't be mistaken for one
+/// of a new path
+static NEXT_ID: AtomicUsize = AtomicUsize::new(0);
+
+fn interned() -> MutexGuard<'static, HashMap<Vec<InnerData>, Weak<Interned>>> {
+    INTERNED.lock().unwrap_or_else(|e| e.into_inner())
+}
+
+/// A path into the context such as `user.name`. Paths are interned when a template is parsed, so
+/// every use of the same path shares one set of keys and an id that lookups can be cached by.
+#[derive(Clone)]
+pub struct ContextPath(Arc<Interned>);
+
+struct Interned {
+    id: usize,
+    keys: Vec<InnerData>,
+}
+
+impl ContextPath {
+    /// Find the interned path with these keys, interning it if it is the only one in use
+    pub(crate) fn new(keys: Vec<InnerData>) -> Self {
+        let mut interned = interned();
+        if let Some(path) = interned.get(&keys).and_then(Weak::upgrade) {
+            return ContextPath(path);
+        }
+        let path = Arc::new(Interned {
+            id: NEXT_ID.fetch_add(1, Ordering::Relaxed),
+            keys: keys.clone(),
+        });
+        interned.insert(keys, Arc::downgrade(&path));
+        ContextPath(path)
+    }
+
+    /// The id shared by every path with the same keys
+    pub(crate) fn id(&self) -> usize {
+        self.0.id
+    }
+}
+
+impl Drop for Interned {
+    fn drop(&mut self) {
+        let mut interned = interned();
+        // The same keys may have been interned again since the last use of this path was dropped
+        if interned
+            .get(&self.keys)
+            .is_some_and(|path| path.strong_count() == 0)
+        {
+            interned.remove(&self.keys);
+        }
+    }
+}
+
+impl Deref for ContextPath {
+    type Target = [InnerData];
+
+    fn deref(&self) -> &[InnerData] {
+        &self.0.keys
+    }
+}
+
+impl fmt::Debug for ContextPath {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        self.0.keys.fmt(f)
+    }
+}
@@ -9,11 +9,16 @@ pub enum OtherData {
     // Expr(Vec<NodeData>),
     // Scope(Box<NodeData>),
     // Operation(Arc<Operation>),
+    Arguments(Arguments),
+    Lambda(Lambda),
 }
 
 impl fmt::Display for OtherData {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
-        write!(f, "<OtherData>")
+        match self {
+            OtherData::Arguments(args) => args.fmt(f),
+            OtherData::Lambda(lambda) => lambda.fmt(f),
+        }
     }
 }
 
@@ -71,12 +76,22 @@ impl<'a> Data {
     /// If this data is in an error state, the error is returned
     /// Otherwise, the rendered string is returned
     pub fn render(self) -> Result<String> {
+        let mut result = String::new();
+        self.render_into(&mut result)?;
+        Ok(result)
+    }
+
+    /// Render this result onto the end of a string
+    pub(crate) fn render_into(self, out: &mut String) -> Result<()> {
+        use std::fmt::Write;
         match self.inner {
-            InnerData::Err(e) => Err(e),
-            InnerData::Null => Ok("null".into()),
-            InnerData::Unassigned => Ok("".into()),
-            doc => Ok(doc.to_string()),
+            InnerData::Err(e) => return Err(e),
+            InnerData::Null => out.push_str("null"),
+            InnerData::Unassigned => {}
+            InnerData::String(s) if out.is_empty() => *out = s,
+            doc => write!(out, "{}", doc).expect("writing to a string cannot fail"),
         }
+        Ok(())
     }
 
     /// Check if this data struct has a failure
@@ -91,6 +106,14 @@ impl<'a> Data {
         matches!(self.inner, InnerData::Unassigned)
     }
 
+    /// Check whether this data counts as true in a condition such as `if`, `&&`, `||` or `!`.
+    ///
+    /// False, null, empty data, zero and empty strings, arrays and maps are falsy. Everything
+    /// else is truthy.
+    pub fn is_truthy(&self) -> bool {
+        truthy(&self.inner)
+    }
+
     /// Convert the data into a Result<Document>.
     /// In the case of empty data, an empty string is returned.
     pub fn into_result(self) -> Result<Self> {
@@ -109,7 +132,7 @@ impl<'a> Data {
     pub fn to_result(&'a self) -> Result<&'a InnerData> {
         match &self.inner {
             InnerData::Err(e) => Err(e.clone()),
-            ref dat => Ok(dat),
+            dat => Ok(dat),
         }
     }
 
@@ -131,7 +154,10 @@ impl<'a> Data {
     }
 
     pub(crate) fn from_vec(seq: Vec<Data>) -> Self {
-        let result: Result<Vec<InnerData>> = seq.into_iter().map(|d| Ok(d.inner)).collect();
+        let result: Result<Vec<InnerData>> = seq
+            .into_iter()
+            .map(|d| d.into_result().map(Data::into_inner))
+            .collect();
         match result {
             Ok(docs) => Data::new(docs),
             Err(e) => e.into(),
@@ -154,3 +180,18 @@ impl From<TemplarError> for Data {
         }
     }
 }
+
+fn truthy(inner: &InnerData) -> bool {
+    match inner {
+        InnerData::Bool(b) => *b,
+        InnerData::Unassigned | InnerData::Null | InnerData::Err(_) => false,
+        InnerData::Number(n) => *n != unstructured::Number::U8(0),
+        InnerData::String(s) => !s.is_empty(),
+        InnerData::Bytes(b) => !b.is_empty(),
+        InnerData::Seq(s) => !s.is_empty(),
+        InnerData::Map(m) => !m.is_empty(),
+        InnerData::Option(o) => o.as_deref().map(truthy).unwrap_or_default(),
+        InnerData::Newtype(n) => truthy(n),
+        InnerData::Char(_) | InnerData::Other(_) => true,
+    }
+}
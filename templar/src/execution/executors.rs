@@ -12,6 +12,9 @@ pub(crate) enum Executors {
     Loop(LoopExecutor),
     Filter(FilterExecutor),
     Function(FunctionExecutor),
+    Test(TestExecutor),
+    Macro(MacroExecutor),
+    Lambda(LambdaExecutor),
 }
 
 pub(crate) struct IndeterminateExecutor(fn(&ContextWrapper, input: &[Node]) -> Data);
@@ -96,6 +99,11 @@ impl FilterExecutor {
     pub fn new(new_fn: Arc<dyn Fn(Data, Data) -> Data + Sync + Send>) -> Self {
         Self(new_fn)
     }
+
+    #[inline]
+    pub fn func(&self) -> &Arc<dyn Fn(Data, Data) -> Data + Sync + Send> {
+        &self.0
+    }
 }
 
 pub(crate) struct FunctionExecutor(Arc<dyn Fn(Data) -> Data + Sync + Send>);
@@ -105,6 +113,77 @@ impl FunctionExecutor {
     pub fn new(new_fn: Arc<dyn Fn(Data) -> Data + Sync + Send>) -> Self {
         Self(new_fn)
     }
+
+    #[inline]
+    pub fn func(&self) -> &Arc<dyn Fn(Data) -> Data + Sync + Send> {
+        &self.0
+    }
+}
+
+#[derive(Clone)]
+pub(crate) struct TestExecutor {
+    test: Arc<tests::Test>,
+    negate: bool,
+}
+
+impl TestExecutor {
+    #[inline]
+    pub fn new(test: Arc<tests::Test>, negate: bool) -> Self {
+        Self { test, negate }
+    }
+
+    /// Run the test on values that have already been evaluated
+    #[inline]
+    pub fn check(&self, value: &Data, args: Data) -> Data {
+        match (self.test)(value, args) {
+            Ok(result) => (result != self.negate).into(),
+            Err(e) => e.into(),
+        }
+    }
+}
+
+pub(crate) struct MacroExecutor {
+    def: Arc<TemplateMacro>,
+    caller: Option<Arc<LambdaDef>>,
+}
+
+impl MacroExecutor {
+    /// The body of a call block is kept as a lambda without parameters, so that it runs with the
+    /// values of the calling context rather than those of the macro
+    #[inline]
+    pub fn new(def: Arc<TemplateMacro>, caller: Option<Node>) -> Self {
+        let caller = caller.map(|body| Arc::new(LambdaDef::new(vec![], body)));
+        Self { def, caller }
+    }
+}
+
+impl MacroExecutor {
+    pub fn def(&self) -> &TemplateMacro {
+        &self.def
+    }
+
+    pub fn caller(&self) -> Option<&Node> {
+        self.caller.as_ref().map(|c| c.body())
+    }
+
+    /// The macro body, parameter defaults and caller body
+    pub fn nodes(&self) -> impl Iterator<Item = &Node> {
+        self.def.nodes().chain(self.caller.iter().map(|c| c.body()))
+    }
+}
+
+pub(crate) struct LambdaExecutor(Arc<LambdaDef>);
+
+impl LambdaExecutor {
+    #[inline]
+    pub fn new(def: LambdaDef) -> Self {
+        Self(Arc::new(def))
+    }
+
+    #[inline]
+    pub fn def(&self) -> &LambdaDef {
+        &self.0
+    }
 }
 
 pub(crate) trait Executor {
@@ -156,6 +235,9 @@ impl Executor for Executors {
             Self::Loop(ref ex) => ex.exec(ctx, nodes),
             Self::Filter(ref ex) => ex.exec(ctx, nodes),
             Self::Function(ref ex) => ex.exec(ctx, nodes),
+            Self::Test(ref ex) => ex.exec(ctx, nodes),
+            Self::Macro(ref ex) => ex.exec(ctx, nodes),
+            Self::Lambda(ref ex) => ex.exec(ctx, nodes),
         }
     }
 }
@@ -163,21 +245,62 @@ impl Executor for Executors {
 impl Executor for IndeterminateExecutor {
     #[inline]
     fn exec(&self, ctx: &ContextWrapper, nodes: &[Node]) -> Data {
-        self.0(ctx, &nodes)
+        self.0(ctx, nodes)
     }
 }
 
 impl Executor for FilterExecutor {
     #[inline]
     fn exec(&self, ctx: &ContextWrapper, nodes: &[Node]) -> Data {
-        self.0(nodes[0].exec(ctx), nodes[1].exec(ctx))
+        limits::check_result(self.0(nodes[0].exec(ctx), nodes[1].exec(ctx)))
     }
 }
 
 impl Executor for FunctionExecutor {
     #[inline]
     fn exec(&self, ctx: &ContextWrapper, nodes: &[Node]) -> Data {
-        self.0(nodes[0].exec(ctx))
+        let args = nodes[0].exec(ctx);
+        if let Err(e) = limits::count_call() {
+            return e.into();
+        }
+        limits::check_result(self.0(args))
+    }
+}
+
+impl Executor for TestExecutor {
+    #[inline]
+    fn exec(&self, ctx: &ContextWrapper, nodes: &[Node]) -> Data {
+        // Unlike filters, empty values are still passed through so they can be tested
+        let value = nodes[0].exec(ctx);
+        if value.is_failed() {
+            return value;
+        }
+        let args = nodes[1].exec(ctx);
+        if args.is_failed() {
+            return args;
+        }
+        self.check(&value, args)
+    }
+}
+
+impl Executor for MacroExecutor {
+    #[inline]
+    fn exec(&self, ctx: &ContextWrapper, nodes: &[Node]) -> Data {
+        let caller = match &self.caller {
+            Some(caller) => match caller.capture(ctx).into_result() {
+                Ok(caller) => Some(caller),
+                Err(e) => return e.into(),
+            },
+            None => None,
+        };
+        self.def.call(ctx, nodes, caller)
+    }
+}
+
+impl Executor for LambdaExecutor {
+    #[inline]
+    fn exec(&self, ctx: &ContextWrapper, _: &[Node]) -> Data {
+        self.0.capture(ctx)
     }
 }
 
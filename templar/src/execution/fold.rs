@@ -0,0 +1,152 @@
+use super::*;
+use std::time::Duration;
+
+impl Node {
+    /// Simplify a freshly parsed tree before it is rendered. Sub-trees that only contain literals
+    /// and pure operations are evaluated once and replaced with their result, nested
+    /// concatenations are flattened and adjacent literals in a concatenation are joined.
+    ///
+    /// Sub-trees that evaluate to an error or go over the limits for folding are left alone so
+    /// they are evaluated when rendering, within the limits of that render.
+    /// The bodies of macros and lambdas are folded when they are parsed, and operations shared
+    /// with another template, such as an included one, were folded when that template was parsed.
+    pub(crate) fn fold(self, templar: &Templar) -> Node {
+        let node = match self {
+            Node::Operation(op) => match Arc::try_unwrap(op) {
+                Ok(op) => fold_operation(op, templar),
+                Err(shared) => return Node::Operation(shared),
+            },
+            Node::Scope(inner) => match (*inner).fold(templar) {
+                data @ Node::Data(_) => return data,
+                inner => return inner.into_scope(),
+            },
+            Node::Expr(nodes) => Node::Expr(fold_all(nodes, templar)),
+            Node::Array(nodes) => Node::Array(fold_all(nodes, templar)),
+            Node::Map(map) => {
+                Node::Map(map.into_iter().map(|(k, v)| (k, v.fold(templar))).collect())
+            }
+            Node::Data(_) | Node::Value(_) => return self,
+        };
+        if !node.is_constant(templar) {
+            return node;
+        }
+        match evaluate(&node, templar) {
+            Some(result) => result.into(),
+            None => node,
+        }
+    }
+
+    /// True if the node and everything under it can be evaluated without a context
+    fn is_constant(&self, templar: &Templar) -> bool {
+        let is_data = |n: &Node| matches!(n, Node::Data(_));
+        match self {
+            Node::Data(_) => true,
+            Node::Expr(nodes) | Node::Array(nodes) => nodes.iter().all(is_data),
+            Node::Map(map) => map.values().all(is_data),
+            Node::Operation(op) => op.is_pure(templar) && op.nodes().iter().all(is_data),
+            Node::Value(_) | Node::Scope(_) => false,
+        }
+    }
+}
+
+impl Operation {
+    /// True if the operation always gives the same result for the same inputs and does not touch
+    /// the context. The purity of filters and functions is recorded when they are added to
+    /// Templar, see `TemplarBuilder::add_pure_function`.
+    fn is_pure(&self, templar: &Templar) -> bool {
+        match self.executor() {
+            Executors::Piped(_) => self.name() != "Set",
+            Executors::Conditional(_) => true,
+            // Named arguments are only understood when they are passed straight to a call
+            Executors::Indeterminate(_) => {
+                !matches!(self.name(), "Include" | "IncludeOnly" | "Arguments")
+            }
+            Executors::Filter(_) => templar.pure_filters.contains(self.name()),
+            Executors::Function(_) => templar.pure_functions.contains(self.name()),
+            Executors::Loop(_)
+            | Executors::Test(_)
+            | Executors::Macro(_)
+            | Executors::Lambda(_) => false,
+        }
+    }
+}
+
+/// Evaluate a constant node within `fold_limits`, or `None` if that fails
+fn evaluate(node: &Node, templar: &Templar) -> Option<Data> {
+    fold_limits(templar)
+        .isolate(|| {
+            let result = node.exec(&StandardContext::new()).into_result()?;
+            if let InnerData::String(s) = result.inner_data() {
+                limits::check_output(s.len())?;
+            }
+            Ok(result)
+        })
+        .ok()
+}
+
+/// The most work folding a single node may do, which is also held to the render limits of
+/// `templar`. Larger results are cheaper to build when rendering than to keep in the template.
+fn fold_limits(templar: &Templar) -> RenderLimits {
+    let mut limits = RenderLimits::new();
+    limits
+        .set_max_loop_iterations(10_000)
+        .set_max_depth(256)
+        .set_max_output_bytes(64 * 1024)
+        .set_max_function_calls(100)
+        .set_timeout(Duration::from_millis(50));
+    match &templar.limits {
+        Some(render_limits) => limits.within(render_limits),
+        None => limits,
+    }
+}
+
+fn fold_all(nodes: Vec<Node>, templar: &Templar) -> Vec<Node> {
+    nodes.into_iter().map(|n| n.fold(templar)).collect()
+}
+
+fn fold_operation(mut op: Operation, templar: &Templar) -> Node {
+    let nodes = std::mem::take(op.nodes_mut());
+    *op.nodes_mut() = match op.name() {
+        "Concat" => flatten_concat(fold_all(nodes, templar)),
+        // The positional arguments are read as an array node rather than executed
+        "Arguments" => nodes
+            .into_iter()
+            .map(|node| match node {
+                Node::Array(items) => Node::Array(fold_all(items, templar)),
+                node => node.fold(templar),
+            })
+            .collect(),
+        _ => fold_all(nodes, templar),
+    };
+    Node::Operation(Arc::new(op))
+}
+
+/// Splice the parts of nested concatenations into their parent and join adjacent literals into a
+/// single string, which is how they are rendered
+fn flatten_concat(nodes: Vec<Node>) -> Vec<Node> {
+    let mut flat: Vec<Node> = vec![];
+    for node in nodes {
+        let parts = match node {
+            Node::Operation(op) if op.name() == "Concat" => match Arc::try_unwrap(op) {
+                Ok(mut op) => std::mem::take(op.nodes_mut()),
+                Err(shared) => vec![Node::Operation(shared)],
+            },
+            node => vec![node],
+        };
+        for part in parts {
+            match (flat.last_mut(), part) {
+                (Some(Node::Data(last)), Node::Data(next)) => {
+                    match (last.clone().render(), next.clone().render()) {
+                        (Ok(mut joined), Ok(next)) => {
+                            joined.push_str(&next);
+                            *last = joined.into();
+                        }
+                        _ => flat.push(Node::Data(next)),
+                    }
+                }
+                (_, part) => flat.push(part),
+            }
+        }
+    }
+    flat
+}
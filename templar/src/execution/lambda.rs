@@ -0,0 +1,146 @@
+use super::*;
+use std::collections::BTreeSet;
+
+/// The definition of a lambda expression such as `x => x.name` or `(acc, x) => acc + x`
+#[derive(Debug)]
+pub(crate) struct LambdaDef {
+    params: Vec<String>,
+    body: Node,
+    captures: Vec<InnerData>,
+    capture_root: bool,
+}
+
+impl LambdaDef {
+    /// Create a new lambda definition, finding the context values the body refers to so that they
+    /// can be captured when the lambda is evaluated.
+    pub fn new(params: Vec<String>, body: Node) -> Self {
+        let mut roots = BTreeSet::new();
+        let mut capture_root = false;
+        body.walk(&mut |node| match node {
+            Node::Value(path) => {
+                match path.first() {
+                    Some(root) => {
+                        roots.insert(root.clone());
+                    }
+                    None => capture_root = true,
+                }
+                true
+            }
+            Node::Operation(op) => match op.lambda() {
+                Some(inner) => {
+                    roots.extend(inner.captures.iter().cloned());
+                    capture_root |= inner.capture_root;
+                    false
+                }
+                None => true,
+            },
+            _ => true,
+        });
+        for param in params.iter() {
+            roots.remove(&InnerData::from(param.as_str()));
+        }
+        LambdaDef {
+            params,
+            body,
+            captures: roots.into_iter().collect(),
+            capture_root,
+        }
+    }
+
+    pub fn params(&self) -> &[String] {
+        &self.params
+    }
+
+    pub fn body(&self) -> &Node {
+        &self.body
+    }
+
+    /// Evaluate the lambda, capturing the values it refers to from the current context
+    pub fn capture(self: &Arc<Self>, ctx: &impl Context) -> Data {
+        let mut captured = ContextMap::default();
+        if self.capture_root {
+            match ctx.get().into_inner() {
+                InnerData::Err(e) => return e.into(),
+                root => {
+                    if let Err(e) = captured.set(root, &[]) {
+                        return e.into();
+                    }
+                }
+            }
+        }
+        for key in self.captures.iter() {
+            match ctx.get_path(&[key]).into_inner() {
+                InnerData::Err(e) => return e.into(),
+                InnerData::Unassigned => {}
+                value => {
+                    if let Err(e) = captured.set(value, &[key]) {
+                        return e.into();
+                    }
+                }
+            }
+        }
+        InnerData::Other(OtherData::Lambda(Lambda {
+            def: self.clone(),
+            context: CapturedContext::new(captured),
+        }))
+        .into()
+    }
+}
+
+/// A lambda expression that has been evaluated, e.g. the `x => x.name` in `items | map(x => x.name)`.
+///
+/// Any context values the lambda refers to are captured when it is evaluated, so a lambda can be
+/// invoked by filters and functions with `Lambda::call`.
+#[derive(Clone)]
+pub struct Lambda {
+    def: Arc<LambdaDef>,
+    context: CapturedContext,
+}
+
+impl Lambda {
+    /// The number of parameters this lambda accepts
+    pub fn arity(&self) -> usize {
+        self.def.params.len()
+    }
+
+    /// Invoke the lambda, binding each argument to the matching parameter in a new scope
+    pub fn call(&self, args: Vec<InnerData>) -> Data {
+        if args.len() != self.def.params.len() {
+            return TemplarError::RenderFailure(format!(
+                "Lambda expects {} arguments but {} were supplied",
+                self.def.params.len(),
+                args.len()
+            ))
+            .into();
+        }
+        let scope = self.context.create_scope();
+        for (param, arg) in self.def.params.iter().zip(args) {
+            if let Err(e) = scope.set_path(&[&param.as_str().into()], arg) {
+                return e.into();
+            }
+        }
+        self.def.body.exec(&scope)
+    }
+}
+
+impl fmt::Debug for Lambda {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "Lambda({:?})", self.def)
+    }
+}
+
+impl fmt::Display for Lambda {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "<lambda({})>", self.def.params.join(", "))
+    }
+}
+
+impl Data {
+    /// Retrieve the lambda held by this data, if any
+    pub fn as_lambda(&self) -> Option<&Lambda> {
+        match self.inner_data() {
+            InnerData::Other(OtherData::Lambda(lambda)) => Some(lambda),
+            _ => None,
+        }
+    }
+}
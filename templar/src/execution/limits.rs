@@ -0,0 +1,346 @@
+use super::*;
+use std::cell::{Cell, RefCell};
+use std::time::{Duration, Instant};
+
+/// How many nodes are executed between checks of the deadline, as reading the clock is slow
+/// compared to executing a node
+const TICKS_PER_CLOCK_CHECK: u32 = 64;
+
+thread_local! {
+    /// What is left of the limits of the render running on this thread, if it is limited
+    static BUDGET: RefCell<Option<Budget>> = const { RefCell::new(None) };
+    /// Whether `BUDGET` is set, which is much quicker to check on every node executed
+    static LIMITED: Cell<bool> = const { Cell::new(false) };
+}
+
+/// Limits on the work a single render may do, for templates that are not trusted. Renders that
+/// go over a limit fail with `TemplarError::LimitExceeded`. Limits can be set for every template
+/// parsed by a `Templar` with `TemplarBuilder::set_render_limits`, or for one render with
+/// `Template::render_with_limits`. Nothing is limited by default.
+///
+/// The limits cover the whole render, including included templates, macros and the dynamic values
+/// it reads from the context. A function that is already running is not interrupted when the
+/// deadline passes, the render fails once it returns.
+///
+/// # Usage
+///
+/// ```
+/// # use templar::*;
+/// let mut limits = RenderLimits::new();
+/// limits.set_max_loop_iterations(2);
+///
+/// let template = Templar::global().parse("{% for i in [1, 2, 3] %}{{ i }}{% end for %}")?;
+/// let result = template.render_with_limits(&StandardContext::new(), &limits);
+/// assert!(matches!(result, Err(TemplarError::LimitExceeded(_))));
+/// # Ok::<(), templar::TemplarError>(())
+/// ```
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct RenderLimits {
+    loop_iterations: Option<u64>,
+    depth: Option<usize>,
+    output_bytes: Option<usize>,
+    function_calls: Option<u64>,
+    timeout: Option<Duration>,
+}
+
+impl RenderLimits {
+    /// Create a set of limits that does not limit anything yet
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Limit the number of loop iterations, counted across every loop of the render
+    pub fn set_max_loop_iterations(&mut self, max: u64) -> &mut Self {
+        self.loop_iterations = Some(max);
+        self
+    }
+
+    /// Limit how deeply expressions, blocks, macro calls and dynamic context values may be nested
+    /// while they are executed. A single output block such as `{{ a + 1 }}` is a few levels deep.
+    pub fn set_max_depth(&mut self, max: usize) -> &mut Self {
+        self.depth = Some(max);
+        self
+    }
+
+    /// Limit the size of the rendered output in bytes. Strings built while rendering, such as the
+    /// result of a loop or a filter, are held to the same limit.
+    pub fn set_max_output_bytes(&mut self, max: usize) -> &mut Self {
+        self.output_bytes = Some(max);
+        self
+    }
+
+    /// Limit the number of function calls, such as `script()` or `file()`. Calls of pure
+    /// functions that were evaluated when the template was parsed are not counted.
+    pub fn set_max_function_calls(&mut self, max: u64) -> &mut Self {
+        self.function_calls = Some(max);
+        self
+    }
+
+    /// Limit how long a render may take
+    pub fn set_timeout(&mut self, timeout: Duration) -> &mut Self {
+        self.timeout = Some(timeout);
+        self
+    }
+
+    /// Run `render` within these limits. If a limited render is already running on this thread,
+    /// such as the one reading a dynamic value that this render belongs to, its limits are used
+    /// instead.
+    pub(crate) fn enforce<T>(&self, render: impl FnOnce() -> Result<T>) -> Result<T> {
+        let installed = BUDGET.with(|b| {
+            let mut b = b.borrow_mut();
+            if b.is_some() {
+                return None;
+            }
+            *b = Some(Budget::new(self));
+            LIMITED.with(|l| l.set(true));
+            Some(Installed)
+        });
+        let installed = match installed {
+            Some(installed) => installed,
+            None => return render(),
+        };
+        let result = render();
+        // Anything that went over a limit fails the render, even if the error it returned was
+        // replaced, e.g. by the `default` filter
+        match installed.finish() {
+            Some(e) => Err(e),
+            None => result,
+        }
+    }
+}
+
+impl RenderLimits {
+    /// The tighter of each of the limits in `self` and `other`
+    pub(crate) fn within(&self, other: &RenderLimits) -> RenderLimits {
+        fn min<T: Ord + Copy>(a: Option<T>, b: Option<T>) -> Option<T> {
+            match (a, b) {
+                (Some(a), Some(b)) => Some(a.min(b)),
+                (a, b) => a.or(b),
+            }
+        }
+        RenderLimits {
+            loop_iterations: min(self.loop_iterations, other.loop_iterations),
+            depth: min(self.depth, other.depth),
+            output_bytes: min(self.output_bytes, other.output_bytes),
+            function_calls: min(self.function_calls, other.function_calls),
+            timeout: min(self.timeout, other.timeout),
+        }
+    }
+
+    /// Run `f` within these limits, apart from any render already running on this thread. Work
+    /// done by `f` is not counted against that render, and `f` going over these limits does not
+    /// fail it.
+    pub(crate) fn isolate<T>(&self, f: impl FnOnce() -> Result<T>) -> Result<T> {
+        let outer = Isolated::install(Budget::new(self));
+        let result = f();
+        match outer.finish() {
+            Some(e) => Err(e),
+            None => result,
+        }
+    }
+}
+
+/// Puts back the budget of the render that was running before an isolated one, even if the
+/// isolated one panicked
+struct Isolated(Option<Budget>);
+
+impl Isolated {
+    fn install(budget: Budget) -> Self {
+        let outer = BUDGET.with(|b| b.borrow_mut().replace(budget));
+        LIMITED.with(|l| l.set(true));
+        Isolated(outer)
+    }
+
+    /// The limit the isolated work went over, if any
+    fn finish(self) -> Option<TemplarError> {
+        BUDGET
+            .with(|b| b.borrow_mut().take())
+            .and_then(|b| b.exceeded)
+    }
+}
+
+impl Drop for Isolated {
+    fn drop(&mut self) {
+        let outer = self.0.take();
+        LIMITED.with(|l| l.set(outer.is_some()));
+        BUDGET.with(|b| *b.borrow_mut() = outer);
+    }
+}
+
+/// Removes the budget once the render that installed it is done, even if it panicked
+struct Installed;
+
+impl Installed {
+    /// The limit the render went over, if any
+    fn finish(self) -> Option<TemplarError> {
+        LIMITED.with(|l| l.set(false));
+        BUDGET
+            .with(|b| b.borrow_mut().take())
+            .and_then(|b| b.exceeded)
+    }
+}
+
+impl Drop for Installed {
+    fn drop(&mut self) {
+        LIMITED.with(|l| l.set(false));
+        BUDGET.with(|b| b.borrow_mut().take());
+    }
+}
+
+struct Budget {
+    limits: RenderLimits,
+    deadline: Option<Instant>,
+    loop_iterations: u64,
+    function_calls: u64,
+    depth: usize,
+    /// Bytes written out by a streaming render
+    output_bytes: usize,
+    ticks: u32,
+    /// Set once a limit is gone over, after which nothing else may execute
+    exceeded: Option<TemplarError>,
+}
+
+impl Budget {
+    fn new(limits: &RenderLimits) -> Self {
+        Budget {
+            limits: limits.clone(),
+            deadline: limits.timeout.map(|t| Instant::now() + t),
+            loop_iterations: 0,
+            function_calls: 0,
+            depth: 0,
+            output_bytes: 0,
+            ticks: 0,
+            exceeded: None,
+        }
+    }
+
+    /// Fail with `message` unless `within` holds, and keep failing from then on
+    fn check(&mut self, within: bool, message: impl FnOnce() -> String) -> Result<()> {
+        if let Some(e) = &self.exceeded {
+            return Err(e.clone());
+        }
+        if within {
+            return Ok(());
+        }
+        let e = TemplarError::LimitExceeded(message());
+        self.exceeded = Some(e.clone());
+        Err(e)
+    }
+
+    fn enter(&mut self) -> Result<()> {
+        let depth = self.depth + 1;
+        let max = self.limits.depth;
+        self.check(max.is_none_or(|max| depth <= max), || {
+            format!("Nested more than {} levels deep", max.unwrap_or_default())
+        })?;
+        self.ticks = self.ticks.wrapping_add(1);
+        if let Some(deadline) = self.deadline {
+            let timeout = self.limits.timeout.unwrap_or_default();
+            let on_time =
+                !self.ticks.is_multiple_of(TICKS_PER_CLOCK_CHECK) || Instant::now() < deadline;
+            self.check(on_time, || format!("Took longer than {:?}", timeout))?;
+        }
+        self.depth = depth;
+        Ok(())
+    }
+}
+
+/// Apply `f` to the budget of the render running on this thread, if it is limited
+fn budget(f: impl FnOnce(&mut Budget) -> Result<()>) -> Result<()> {
+    if !is_limited() {
+        return Ok(());
+    }
+    BUDGET.with(|b| match b.borrow_mut().as_mut() {
+        Some(budget) => f(budget),
+        None => Ok(()),
+    })
+}
+
+/// Whether a limited render is running on this thread
+pub(crate) fn is_limited() -> bool {
+    LIMITED.with(|l| l.get())
+}
+
+/// One level of nesting, left again when dropped
+pub(crate) struct Nested(bool);
+
+impl Nested {
+    /// Go one level deeper, failing if that is too deep or the deadline has passed
+    #[inline]
+    pub(crate) fn enter() -> Result<Self> {
+        if !is_limited() {
+            return Ok(Nested(false));
+        }
+        BUDGET.with(|b| match b.borrow_mut().as_mut() {
+            Some(budget) => budget.enter().map(|_| Nested(true)),
+            None => Ok(Nested(false)),
+        })
+    }
+}
+
+impl Drop for Nested {
+    #[inline]
+    fn drop(&mut self) {
+        if self.0 {
+            BUDGET.with(|b| {
+                if let Some(budget) = b.borrow_mut().as_mut() {
+                    budget.depth = budget.depth.saturating_sub(1);
+                }
+            });
+        }
+    }
+}
+
+/// Count an iteration of a loop
+pub(crate) fn count_iteration() -> Result<()> {
+    budget(|b| {
+        b.loop_iterations += 1;
+        let max = b.limits.loop_iterations;
+        b.check(max.is_none_or(|max| b.loop_iterations <= max), || {
+            format!("More than {} loop iterations", max.unwrap_or_default())
+        })
+    })
+}
+
+/// Count a call of a function
+pub(crate) fn count_call() -> Result<()> {
+    budget(|b| {
+        b.function_calls += 1;
+        let max = b.limits.function_calls;
+        b.check(max.is_none_or(|max| b.function_calls <= max), || {
+            format!("More than {} function calls", max.unwrap_or_default())
+        })
+    })
+}
+
+/// Check the length of a string being built while rendering
+pub(crate) fn check_output(len: usize) -> Result<()> {
+    budget(|b| {
+        let max = b.limits.output_bytes;
+        b.check(max.is_none_or(|max| len <= max), || {
+            format!("Output longer than {} bytes", max.unwrap_or_default())
+        })
+    })
+}
+
+/// Check the length of a string returned by a filter or function, which may have been built from
+/// a much smaller input
+pub(crate) fn check_result(result: Data) -> Data {
+    if let InnerData::String(s) = result.inner_data() {
+        if let Err(e) = check_output(s.len()) {
+            return e.into();
+        }
+    }
+    result
+}
+
+/// Count bytes about to be written out by a streaming render
+pub(crate) fn count_output(len: usize) -> Result<()> {
+    budget(|b| {
+        b.output_bytes += len;
+        let (max, written) = (b.limits.output_bytes, b.output_bytes);
+        b.check(max.is_none_or(|max| written <= max), || {
+            format!("Output longer than {} bytes", max.unwrap_or_default())
+        })
+    })
+}
@@ -1,15 +1,33 @@
-use crate::context::ContextWrapper;
+use crate::context::{CapturedContext, ContextMap, ContextWrapper, PathName};
 use crate::*;
+pub use arguments::Arguments;
+pub use context_path::ContextPath;
 pub use data::*;
 pub(crate) use executors::*;
+pub use lambda::Lambda;
+pub(crate) use lambda::LambdaDef;
+pub(crate) use limits::Nested;
+pub use limits::RenderLimits;
 use std::collections::BTreeMap;
 use std::fmt;
 use std::sync::Arc;
 
 pub(crate) use node::Node;
 pub(crate) use operation::*;
+pub(crate) use references::References;
+pub(crate) use template_macro::*;
+pub use vm::CompiledTemplate;
 
+mod arguments;
+mod context_path;
 mod data;
 mod executors;
+mod fold;
+mod lambda;
+pub(crate) mod limits;
 mod node;
 mod operation;
+mod references;
+mod stream;
+mod template_macro;
+mod vm;
@@ -5,7 +5,7 @@ pub enum Node {
     Expr(Vec<Node>),
     Data(Data),
     Scope(Box<Node>),
-    Value(Vec<InnerData>),
+    Value(ContextPath),
     Operation(Arc<Operation>),
     Array(Vec<Node>),
     Map(BTreeMap<InnerData, Node>),
@@ -33,13 +33,14 @@ impl Default for Node {
 
 impl Node {
     pub(crate) fn exec(&self, ctx: &impl Context) -> Data {
+        let _nested = match Nested::enter() {
+            Ok(nested) => nested,
+            Err(e) => return e.into(),
+        };
         match self {
             Self::Data(d) => d.clone(),
             Self::Operation(op) => op.exec(ctx),
-            Self::Value(a) => {
-                let docs = a.iter().collect::<Vec<&InnerData>>();
-                ctx.get_path(&docs)
-            }
+            Self::Value(path) => ctx.get_interned_inner(path, ctx),
             Self::Scope(i) => {
                 let local_context = ctx.create_scope();
                 i.exec(&local_context)
@@ -68,6 +69,21 @@ impl Node {
         }
     }
 
+    /// Visit this node and every node beneath it, including the bodies of any macros it calls. The
+    /// children of a node are skipped if `visit` returns false for it.
+    pub(crate) fn walk(&self, visit: &mut dyn FnMut(&Node) -> bool) {
+        if !visit(self) {
+            return;
+        }
+        match self {
+            Self::Data(_) | Self::Value(_) => {}
+            Self::Scope(i) => i.walk(visit),
+            Self::Expr(nodes) | Self::Array(nodes) => nodes.iter().for_each(|n| n.walk(visit)),
+            Self::Map(m) => m.values().for_each(|n| n.walk(visit)),
+            Self::Operation(op) => op.children().for_each(|n| n.walk(visit)),
+        }
+    }
+
     pub(crate) fn set_operation(self, op: Operations) -> Node {
         match self {
             Node::Expr(nodes) => Node::Operation(Arc::new(op.build(nodes))),
@@ -89,7 +105,9 @@ impl Node {
     }
 
     pub fn render(&self, ctx: &impl Context) -> Result<String> {
-        self.exec(ctx).render()
+        let rendered = self.exec(ctx).render()?;
+        limits::check_output(rendered.len())?;
+        Ok(rendered)
     }
 }
 
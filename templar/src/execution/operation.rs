@@ -1,4 +1,5 @@
 use super::*;
+use std::fmt::Write;
 
 pub struct Operation {
     oper: Executors,
@@ -22,6 +23,41 @@ impl Operation {
         Executor::exec(&self.oper, &wrapper, &self.nodes)
     }
 
+    /// The name of the operation, or of the filter, function, test or macro it calls
+    pub(crate) fn name(&self) -> &str {
+        &self.name
+    }
+
+    pub(crate) fn nodes(&self) -> &[Node] {
+        &self.nodes
+    }
+
+    pub(crate) fn nodes_mut(&mut self) -> &mut Vec<Node> {
+        &mut self.nodes
+    }
+
+    pub(crate) fn executor(&self) -> &Executors {
+        &self.oper
+    }
+
+    /// Every node this operation may execute, including the bodies of macros and lambdas
+    pub(crate) fn children(&self) -> Box<dyn Iterator<Item = &Node> + '_> {
+        let extra: Box<dyn Iterator<Item = &Node>> = match &self.oper {
+            Executors::Macro(ex) => Box::new(ex.nodes()),
+            Executors::Lambda(ex) => Box::new(std::iter::once(ex.def().body())),
+            _ => Box::new(std::iter::empty()),
+        };
+        Box::new(self.nodes.iter().chain(extra))
+    }
+
+    /// The lambda definition if this operation evaluates a lambda expression
+    pub(crate) fn lambda(&self) -> Option<&LambdaDef> {
+        match &self.oper {
+            Executors::Lambda(ex) => Some(ex.def()),
+            _ => None,
+        }
+    }
+
     pub(crate) fn from_filter(mut name: String, ex: FilterExecutor, nodes: Vec<Node>) -> Self {
         name.shrink_to_fit();
         Operation {
@@ -39,6 +75,32 @@ impl Operation {
             nodes: vec![node],
         }
     }
+
+    pub(crate) fn from_test(mut name: String, ex: TestExecutor, nodes: Vec<Node>) -> Self {
+        name.shrink_to_fit();
+        Operation {
+            name,
+            oper: Executors::Test(ex),
+            nodes,
+        }
+    }
+
+    pub(crate) fn from_lambda(ex: LambdaExecutor) -> Self {
+        Operation {
+            name: "Lambda".into(),
+            oper: Executors::Lambda(ex),
+            nodes: vec![],
+        }
+    }
+
+    pub(crate) fn from_macro(mut name: String, ex: MacroExecutor, nodes: Vec<Node>) -> Self {
+        name.shrink_to_fit();
+        Operation {
+            name,
+            oper: Executors::Macro(ex),
+            nodes,
+        }
+    }
 }
 
 macro_rules! map_operations {
@@ -77,6 +139,8 @@ map_operations! {
     PipedExecutor: Modulus:modulus;
     PipedExecutor: And:and;
     PipedExecutor: Or:or;
+    PipedExecutor: StrictAnd:strict_and;
+    PipedExecutor: StrictOr:strict_or;
     PipedExecutor: Equals:equals;
     PipedExecutor: NotEquals:not_equals;
     PipedExecutor: GreaterThan:greater_than;
@@ -85,19 +149,48 @@ map_operations! {
     PipedExecutor: LessThanEquals:less_than_equals;
     PipedExecutor: Set:set;
     ConditionalExecutor: IfThen:if_then;
+    ConditionalExecutor: StrictIfThen:strict_if_then;
     IndeterminateExecutor: Concat:concat;
+    IndeterminateExecutor: Include:include;
+    IndeterminateExecutor: IncludeOnly:include_only;
+    IndeterminateExecutor: Arguments:arguments;
+    IndeterminateExecutor: Not:not;
+    IndeterminateExecutor: StrictNot:strict_not;
+    IndeterminateExecutor: StrictValue:strict_value;
+    IndeterminateExecutor: DebugUndefined:debug_undefined;
+    IndeterminateExecutor: Caller:caller;
     LoopExecutor: ForLoop:for_loop;
 }
 
 macro_rules! simple_pipe {
-    ( $( $pipe_name:ident ( $l:ident , $r:ident ) -> { $( $tail:tt )* } ; )* ) => {
+    ( $( $name:ident : $pipe_name:ident ( $l:ident , $r:ident ) -> { $( $tail:tt )* } ; )* ) => {
         $(
             fn $pipe_name(ctx: &ContextWrapper, left: &Node, right: &Node) -> Data {
-                match (data_unwrap!(left.exec(ctx)), data_unwrap!(right.exec(ctx))) {
-                    ($l, $r) => Data::from( $( $tail )* ),
-                }
+                values::$pipe_name(data_unwrap!(left.exec(ctx)), right.exec(ctx))
             }
         )*
+
+        /// The piped operations that always evaluate both sides, applied to values that have
+        /// already been evaluated
+        pub(crate) mod values {
+            use super::*;
+
+            $(
+                pub(crate) fn $pipe_name(left: Data, right: Data) -> Data {
+                    match (data_unwrap!(left), data_unwrap!(right)) {
+                        ($l, $r) => Data::from( $( $tail )* ),
+                    }
+                }
+            )*
+
+            /// The value level function for the piped operation with this name
+            pub(crate) fn by_name(name: &str) -> Option<fn(Data, Data) -> Data> {
+                match name {
+                    $( stringify!($name) => Some($pipe_name), )*
+                    _ => None,
+                }
+            }
+        }
     };
 }
 
@@ -113,23 +206,177 @@ macro_rules! number {
     };
 }
 
+/// Integer arithmetic that fails rather than overflowing or dividing by zero
+macro_rules! checked {
+    ($l:ident $symbol:literal $op:ident $r:ident) => {{
+        let (l, r) = (number!($l), number!($r));
+        match l.$op(r) {
+            Some(result) => Data::from(result),
+            None if r == 0 => TemplarError::RenderFailure("Division by zero".into()).into(),
+            None => {
+                TemplarError::RenderFailure(format!("Integer overflow in {} {} {}", l, $symbol, r))
+                    .into()
+            }
+        }
+    }};
+}
+
 simple_pipe! {
-    add (l, r) -> { number!(l) + number!(r) };
-    subtract(l, r) -> { number!(l) - number!(r) };
-    divide(l, r) -> { number!(l) / number!(r) };
-    multiply(l, r) -> { number!(l) * number!(r) };
-    modulus(l, r) -> { number!(l) % number!(r) };
-    and(l, r) -> { l.into_inner().cast::<bool>().unwrap_or_default() && r.into_inner().cast::<bool>().unwrap_or_default() };
-    or(l, r) -> { l.into_inner().cast::<bool>().unwrap_or_default() || r.into_inner().cast::<bool>().unwrap_or_default() };
-    equals(l, r) -> { l.into_inner() == r.into_inner() };
-    not_equals(l, r) -> { l.into_inner() != r.into_inner() };
-    greater_than(l, r) -> { l.into_inner() > r.into_inner() };
-    greater_than_equals(l, r) -> { l.into_inner() >= r.into_inner() };
-    less_than(l, r) -> { l.into_inner() < r.into_inner() };
-    less_than_equals(l, r) -> { l.into_inner() <= r.into_inner() };
+    Add: add (l, r) -> { checked!(l "+" checked_add r) };
+    Subtract: subtract(l, r) -> { checked!(l "-" checked_sub r) };
+    Divide: divide(l, r) -> { checked!(l "/" checked_div r) };
+    Multiply: multiply(l, r) -> { checked!(l "*" checked_mul r) };
+    Modulus: modulus(l, r) -> { checked!(l "%" checked_rem r) };
+    StrictAnd: strict_and(l, r) -> { l.into_inner().cast::<bool>().unwrap_or_default() && r.into_inner().cast::<bool>().unwrap_or_default() };
+    StrictOr: strict_or(l, r) -> { l.into_inner().cast::<bool>().unwrap_or_default() || r.into_inner().cast::<bool>().unwrap_or_default() };
+    Equals: equals(l, r) -> { l.into_inner() == r.into_inner() };
+    NotEquals: not_equals(l, r) -> { l.into_inner() != r.into_inner() };
+    GreaterThan: greater_than(l, r) -> { l.into_inner() > r.into_inner() };
+    GreaterThanEquals: greater_than_equals(l, r) -> { l.into_inner() >= r.into_inner() };
+    LessThan: less_than(l, r) -> { l.into_inner() < r.into_inner() };
+    LessThanEquals: less_than_equals(l, r) -> { l.into_inner() <= r.into_inner() };
+}
+
+/// Evaluates the right side only if the left side is truthy, producing the side that decided the
+/// result
+fn and(ctx: &ContextWrapper, left: &Node, right: &Node) -> Data {
+    let left = left.exec(ctx);
+    if left.is_failed() || !left.is_truthy() {
+        return left;
+    }
+    right.exec(ctx)
+}
+
+/// Evaluates the right side only if the left side is falsy, producing the side that decided the
+/// result, e.g. `user.nickname || user.name` falls back to the name
+fn or(ctx: &ContextWrapper, left: &Node, right: &Node) -> Data {
+    let left = left.exec(ctx);
+    if left.is_failed() || left.is_truthy() {
+        return left;
+    }
+    right.exec(ctx)
+}
+
+fn not(ctx: &ContextWrapper, input: &[Node]) -> Data {
+    let value = input[0].exec(ctx);
+    if value.is_failed() {
+        return value;
+    }
+    (!value.is_truthy()).into()
+}
+
+/// `caller()` inside a macro, which renders the body of the call block the macro was invoked with
+fn caller(ctx: &ContextWrapper, _: &[Node]) -> Data {
+    let caller = ctx.get_path(&[&"caller".into()]);
+    match caller.as_lambda() {
+        Some(lambda) => lambda.call(vec![]),
+        None if caller.is_failed() => caller,
+        None => TemplarError::RenderFailure(
+            "caller() can only be used in a macro that is invoked with a call block".into(),
+        )
+        .into(),
+    }
+}
+
+fn strict_not(ctx: &ContextWrapper, input: &[Node]) -> Data {
+    match input[0].exec(ctx).into_inner() {
+        InnerData::Bool(b) => (!b).into(),
+        InnerData::Err(e) => e.into(),
+        _ => TemplarError::RenderFailure("Not requires a boolean!".into()).into(),
+    }
+}
+
+/// A context lookup under the strict undefined policy, which fails if the path is not defined
+fn strict_value(ctx: &ContextWrapper, input: &[Node]) -> Data {
+    let value = input[0].exec(ctx);
+    match &input[0] {
+        Node::Value(path) if value.is_empty() => undefined_error(PathName(path).to_string()),
+        _ => value,
+    }
+}
+
+fn debug_undefined(ctx: &ContextWrapper, input: &[Node]) -> Data {
+    guard_undefined(&input[0], ctx, undefined_marker)
+}
+
+/// What the strict undefined policy renders in place of an undefined value
+pub(crate) fn undefined_error(path: String) -> Data {
+    TemplarError::UndefinedValue(path).into()
+}
+
+/// What the debug undefined policy renders in place of an undefined value
+pub(crate) fn undefined_marker(path: String) -> Data {
+    format!("{{{{ undefined: {} }}}}", path).into()
+}
+
+/// Execute the expression of an output block, replacing any undefined value it would render with
+/// the result of `undefined`. The parts of a concatenation are each checked as they are rendered.
+/// The strict policy checks each lookup instead, with `StrictValue`.
+fn guard_undefined(node: &Node, ctx: &ContextWrapper, undefined: fn(String) -> Data) -> Data {
+    if let Node::Operation(op) = node {
+        if op.name == "Concat" {
+            let mut result = String::new();
+            for node in op.nodes.iter() {
+                match guard_undefined(node, ctx, undefined).render() {
+                    Ok(s) => result.push_str(&s),
+                    Err(e) => return e.into(),
+                }
+                if let Err(e) = limits::check_output(result.len()) {
+                    return e.into();
+                }
+            }
+            return result.into();
+        }
+    }
+    let result = node.exec(ctx);
+    let empty = match result.inner_data() {
+        InnerData::Unassigned => true,
+        InnerData::String(s) => s.is_empty(),
+        _ => false,
+    };
+    if empty {
+        if let Some(path) = find_undefined(node, ctx) {
+            return undefined(path);
+        }
+    }
+    result
+}
+
+/// Find the first path in an expression that is undefined in the context. Macro and lambda bodies
+/// are skipped as they run with their own parameters, as is the target of a set operation and the
+/// input of `default` which handles undefined values itself.
+pub(crate) fn find_undefined(node: &Node, ctx: &ContextWrapper) -> Option<String> {
+    match node {
+        Node::Value(path) => {
+            let refs: Vec<&InnerData> = path.iter().collect();
+            if ctx.get_path(&refs).is_empty() {
+                Some(PathName(path).to_string())
+            } else {
+                None
+            }
+        }
+        Node::Operation(op) if op.name == "Set" => find_undefined(&op.nodes[1], ctx),
+        Node::Operation(op) if op.name == "default" => None,
+        Node::Operation(op) => op.nodes.iter().find_map(|n| find_undefined(n, ctx)),
+        Node::Expr(nodes) | Node::Array(nodes) => nodes.iter().find_map(|n| find_undefined(n, ctx)),
+        Node::Map(m) => m.values().find_map(|n| find_undefined(n, ctx)),
+        Node::Data(_) | Node::Scope(_) => None,
+    }
 }
 
 fn if_then(ctx: &ContextWrapper, cnd: &Node, p: &Node, n: &Node) -> Data {
+    let cnd = cnd.exec(ctx);
+    if cnd.is_failed() {
+        return cnd;
+    }
+    if cnd.is_truthy() {
+        p.exec(ctx)
+    } else {
+        n.exec(ctx)
+    }
+}
+
+fn strict_if_then(ctx: &ContextWrapper, cnd: &Node, p: &Node, n: &Node) -> Data {
     let cnd = cnd.exec(ctx).into_inner();
     match cnd {
         InnerData::Bool(true) => p.exec(ctx),
@@ -140,73 +387,132 @@ fn if_then(ctx: &ContextWrapper, cnd: &Node, p: &Node, n: &Node) -> Data {
 }
 
 fn concat(ctx: &ContextWrapper, input: &[Node]) -> Data {
-    let results: Result<Vec<String>> = input.iter().map(|node| Ok(node.render(ctx)?)).collect();
-    match results {
-        Ok(result) => result
-            .iter()
-            .fold(String::new(), |mut acc, s| {
-                acc.push_str(&s);
-                acc
-            })
-            .into(),
-        Err(err) => err.into(),
+    let mut result = String::new();
+    for node in input.iter() {
+        let rendered = node
+            .exec(ctx)
+            .render_into(&mut result)
+            .and_then(|_| limits::check_output(result.len()));
+        if let Err(e) = rendered {
+            return e.into();
+        }
     }
+    result.into()
+}
+
+fn include(ctx: &ContextWrapper, input: &[Node]) -> Data {
+    let scope = ctx.create_scope();
+    render_include(&scope, ctx, input)
+}
+
+fn include_only(ctx: &ContextWrapper, input: &[Node]) -> Data {
+    let isolated = StandardContext::new();
+    render_include(&isolated, ctx, input)
+}
+
+/// The nodes for an include are the variables to set followed by the included template
+fn render_include(target: &impl Context, ctx: &ContextWrapper, input: &[Node]) -> Data {
+    match input[0].exec(ctx).into_inner() {
+        InnerData::Err(e) => return e.into(),
+        InnerData::Unassigned => {}
+        InnerData::Map(vars) => {
+            for (k, v) in vars.into_iter() {
+                let r = target.set_path(&[&k], v);
+                if r.is_err() {
+                    return Data::check(r);
+                }
+            }
+        }
+        _ => {
+            return TemplarError::RenderFailure(
+                "Variables passed to an include must be a map".into(),
+            )
+            .into()
+        }
+    }
+    input[1].exec(target)
+}
+
+/// The nodes for named arguments are an array of the positional arguments followed by a map of the
+/// named arguments
+fn arguments(ctx: &ContextWrapper, input: &[Node]) -> Data {
+    let mut positional = vec![];
+    if let Node::Array(nodes) = &input[0] {
+        for node in nodes.iter() {
+            match node.exec(ctx).into_inner() {
+                InnerData::Err(e) => return e.into(),
+                d => positional.push(d),
+            }
+        }
+    }
+    let mut named = BTreeMap::new();
+    match input[1].exec(ctx).into_inner() {
+        InnerData::Err(e) => return e.into(),
+        InnerData::Map(map) => {
+            for (k, v) in map.into_iter() {
+                named.insert(k.to_string(), v);
+            }
+        }
+        _ => {}
+    }
+    InnerData::Other(OtherData::Arguments(Arguments::new(positional, named))).into()
 }
 
 fn for_loop(ctx: &ContextWrapper, val_name: &Node, array_path: &Node, exec: &Node) -> Data {
-    // Get the result for the value we're iterating over
-    let array_exec = array_path.exec(ctx).into_result();
-    if let Err(e) = array_exec {
-        return e.into();
+    let mut result = String::new();
+    let looped = bind_each(ctx, val_name, array_path, &mut || {
+        let res = exec.exec(ctx).into_result()?;
+        write!(result, "{}", res.inner_data()).expect("writing to a string cannot fail");
+        limits::check_output(result.len())
+    });
+    match looped {
+        Ok(true) => result.into(),
+        Ok(false) => exec.exec(ctx),
+        Err(e) => e.into(),
     }
-    let mut array = array_exec.unwrap();
+}
+
+/// Bind the loop variable to each item of the iterable in turn, calling `body` after every one. Map
+/// entries are bound as `{key, value}` maps. Any other value is bound whole without calling `body`,
+/// in which case `false` is returned and the loop takes the result of its body as it is.
+pub(crate) fn bind_each(
+    ctx: &ContextWrapper,
+    val_name: &Node,
+    array_path: &Node,
+    body: &mut dyn FnMut() -> Result<()>,
+) -> Result<bool> {
+    // Get the result for the value we're iterating over
+    let mut array = array_path.exec(ctx).into_result()?;
 
     // Now we get the path for the scope-local value and iterate whatever the result is
     match (val_name, &mut array.inner_data_mut()) {
         (Node::Value(set_path), InnerData::Seq(items)) => {
-            let mut result = String::new();
             let ref_vec: Vec<&InnerData> = set_path.iter().collect();
             for item in items.drain(0..) {
-                let r = ctx.set_path(&ref_vec, item);
-                if r.is_err() {
-                    return Data::check(r);
-                }
-                let res = exec.exec(ctx).into_result();
-                if let Err(e) = res {
-                    return e.into();
-                }
-                result.push_str(&res.unwrap().to_string());
+                limits::count_iteration()?;
+                ctx.set_path(&ref_vec, item)?;
+                body()?;
             }
-            result.into()
+            Ok(true)
         }
         (Node::Value(set_path), InnerData::Map(items)) => {
-            let mut result = String::new();
             let ref_vec: Vec<&InnerData> = set_path.iter().collect();
             for (k, v) in items.iter_mut() {
+                limits::count_iteration()?;
                 let mut entry = BTreeMap::new();
                 entry.insert("key".into(), k.clone()); //cloning the keys is better than rebalancing the tree
                 entry.insert("value".into(), v.take());
-                let r = ctx.set_path(&ref_vec, InnerData::from(entry));
-                if r.is_err() {
-                    return Data::check(r);
-                }
-                let res = exec.exec(ctx).into_result();
-                if let Err(e) = res {
-                    return e.into();
-                }
-                result.push_str(&res.unwrap().to_string());
+                ctx.set_path(&ref_vec, InnerData::from(entry))?;
+                body()?;
             }
-            result.into()
+            Ok(true)
         }
         (Node::Value(ref set_path), _) => {
             let ref_vec: Vec<&InnerData> = set_path.iter().collect();
-            let r = ctx.set_path(&ref_vec, array);
-            if r.is_err() {
-                return Data::check(r);
-            }
-            exec.exec(ctx)
+            ctx.set_path(&ref_vec, array)?;
+            Ok(false)
         }
-        _ => Data::from(TemplarError::RenderFailure(
+        _ => Err(TemplarError::RenderFailure(
             "Unexpected render failure in for loop".into(),
         )),
     }
@@ -0,0 +1,148 @@
+use super::*;
+use std::collections::BTreeSet;
+
+/// The context values, filters and functions a template refers to. Names bound by the template
+/// itself, such as loop variables, set values and macro or lambda parameters, are not included.
+#[derive(Debug, Default)]
+pub(crate) struct References {
+    pub paths: BTreeSet<String>,
+    pub filters: BTreeSet<String>,
+    pub functions: BTreeSet<String>,
+}
+
+/// The names bound by the template in each scope that is currently open
+type Locals = Vec<BTreeSet<InnerData>>;
+
+impl References {
+    pub fn of(node: &Node) -> Self {
+        let mut refs = References::default();
+        refs.visit(node, &mut vec![BTreeSet::new()]);
+        refs
+    }
+
+    fn visit(&mut self, node: &Node, locals: &mut Locals) {
+        match node {
+            Node::Data(_) => {}
+            Node::Value(path) => {
+                let local = path
+                    .first()
+                    .map(|root| locals.iter().any(|scope| scope.contains(root)))
+                    .unwrap_or(false);
+                if !local {
+                    self.paths.insert(path_name(path));
+                }
+            }
+            Node::Scope(inner) => {
+                self.scoped(locals, vec![], |refs, locals| refs.visit(inner, locals))
+            }
+            Node::Expr(nodes) | Node::Array(nodes) => {
+                nodes.iter().for_each(|n| self.visit(n, locals))
+            }
+            Node::Map(m) => m.values().for_each(|n| self.visit(n, locals)),
+            Node::Operation(op) => self.visit_operation(op, locals),
+        }
+    }
+
+    fn visit_operation(&mut self, op: &Operation, locals: &mut Locals) {
+        let nodes = op.nodes();
+        match op.executor() {
+            Executors::Filter(_) => {
+                self.filters.insert(op.name().into());
+            }
+            Executors::Function(_) => {
+                self.functions.insert(op.name().into());
+            }
+            Executors::Lambda(ex) => {
+                let params = ex.def().params().iter().map(|p| p.as_str().into());
+                let body = ex.def().body();
+                return self.scoped(locals, params.collect(), |refs, locals| {
+                    refs.visit(body, locals)
+                });
+            }
+            Executors::Macro(ex) => {
+                nodes.iter().for_each(|n| self.visit(n, locals));
+                let def = ex.def();
+                let mut params: Vec<InnerData> = def
+                    .params()
+                    .iter()
+                    .map(|p| p.name.as_str().into())
+                    .collect();
+                params.push("caller".into());
+                self.scoped(locals, params, |refs, locals| {
+                    for default in def.params().iter().filter_map(|p| p.default.as_ref()) {
+                        refs.visit(default, locals);
+                    }
+                    refs.visit(def.body(), locals);
+                });
+                if let Some(caller) = ex.caller() {
+                    self.visit(caller, locals);
+                }
+                return;
+            }
+            _ => {}
+        }
+        match (op.name(), nodes) {
+            ("Set", [target, value]) => {
+                self.visit(value, locals);
+                match target {
+                    Node::Value(path) if !path.is_empty() => {
+                        if let Some(scope) = locals.last_mut() {
+                            scope.insert(path[0].clone());
+                        }
+                    }
+                    _ => self.visit(target, locals),
+                }
+            }
+            ("ForLoop", [binding, iterable, body]) => {
+                self.visit(iterable, locals);
+                let bound = match binding {
+                    Node::Value(path) => path.first().cloned().into_iter().collect(),
+                    _ => vec![],
+                };
+                self.scoped(locals, bound, |refs, locals| refs.visit(body, locals));
+            }
+            ("Include", [vars, body]) => {
+                self.visit(vars, locals);
+                self.scoped(locals, map_keys(vars), |refs, locals| {
+                    refs.visit(body, locals)
+                });
+            }
+            ("IncludeOnly", [vars, body]) => {
+                // The body can only see the variables passed to it, so only the filters and
+                // functions it uses are of interest
+                self.visit(vars, locals);
+                let isolated = References::of(body);
+                self.filters.extend(isolated.filters);
+                self.functions.extend(isolated.functions);
+            }
+            _ => nodes.iter().for_each(|n| self.visit(n, locals)),
+        }
+    }
+
+    fn scoped(
+        &mut self,
+        locals: &mut Locals,
+        bound: Vec<InnerData>,
+        visit: impl FnOnce(&mut Self, &mut Locals),
+    ) {
+        locals.push(bound.into_iter().collect());
+        visit(self, locals);
+        locals.pop();
+    }
+}
+
+fn map_keys(node: &Node) -> Vec<InnerData> {
+    match node {
+        Node::Map(m) => m.keys().cloned().collect(),
+        _ => vec![],
+    }
+}
+
+/// A path written the way it would be in a template, e.g. `server.host`
+fn path_name(path: &[InnerData]) -> String {
+    if path.is_empty() {
+        return ".".into();
+    }
+    let names: Vec<String> = path.iter().map(|p| p.to_string()).collect();
+    names.join(".")
+}
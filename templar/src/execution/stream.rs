@@ -0,0 +1,97 @@
+use super::*;
+use std::borrow::Cow;
+use std::fmt::Write;
+
+/// How a streamed value is written out
+#[derive(Clone, Copy)]
+enum Mode {
+    /// As rendered by `Data::render`, for the parts of a template
+    Render,
+    /// As displayed, for the result of each iteration of a loop
+    Display,
+}
+
+impl Node {
+    /// Render the node onto `out`. Concatenations, loops and scopes are written out part by part
+    /// as they are executed instead of being built up in memory first, everything else is executed
+    /// and written whole. Output written before an error is not taken back.
+    pub(crate) fn render_to(&self, ctx: &ContextWrapper, out: &mut dyn Write) -> Result<()> {
+        stream(self, ctx, out, Mode::Render, None)
+    }
+}
+
+fn stream(
+    node: &Node,
+    ctx: &ContextWrapper,
+    out: &mut dyn Write,
+    mode: Mode,
+    guard: Option<fn(String) -> Data>,
+) -> Result<()> {
+    let op = match node {
+        Node::Operation(op) => op,
+        // An undefined policy never looks inside a scope
+        Node::Scope(inner) => {
+            let local = ctx.create_scope();
+            return stream(inner, &local.wrap(), out, mode, None);
+        }
+        _ => return write_node(node, ctx, out, mode, guard),
+    };
+    match (op.name(), op.nodes()) {
+        ("Concat", parts) => {
+            for part in parts.iter() {
+                stream(part, ctx, out, Mode::Render, guard)?;
+            }
+            Ok(())
+        }
+        ("DebugUndefined", [inner]) => stream(inner, ctx, out, mode, Some(undefined_marker)),
+        ("ForLoop", [val_name, array_path, body]) if guard.is_none() => {
+            let looped = bind_each(ctx, val_name, array_path, &mut || {
+                stream(body, ctx, out, Mode::Display, None)
+            })?;
+            if looped {
+                Ok(())
+            } else {
+                stream(body, ctx, out, mode, None)
+            }
+        }
+        _ => write_node(node, ctx, out, mode, guard),
+    }
+}
+
+/// Execute a node and write out its result. Under an undefined policy, an empty result that
+/// reads an undefined path is replaced with what the policy renders instead.
+fn write_node(
+    node: &Node,
+    ctx: &ContextWrapper,
+    out: &mut dyn Write,
+    mode: Mode,
+    guard: Option<fn(String) -> Data>,
+) -> Result<()> {
+    let data = node.exec(ctx);
+    if let Some(undefined) = guard {
+        let empty = match data.inner_data() {
+            InnerData::Unassigned => true,
+            InnerData::String(s) => s.is_empty(),
+            _ => false,
+        };
+        if empty {
+            if let Some(path) = find_undefined(node, ctx) {
+                return write_data(undefined(path), out, mode);
+            }
+        }
+    }
+    write_data(data, out, mode)
+}
+
+fn write_data(data: Data, out: &mut dyn Write, mode: Mode) -> Result<()> {
+    let text: Cow<'_, str> = match (data.into_inner(), mode) {
+        (InnerData::Err(e), _) => return Err(e),
+        (InnerData::Null, Mode::Render) => "null".into(),
+        (InnerData::Unassigned, Mode::Render) => return Ok(()),
+        (InnerData::String(s), _) => s.into(),
+        (doc, _) => doc.to_string().into(),
+    };
+    limits::count_output(text.len())?;
+    out.write_str(&text)
+        .map_err(|_| TemplarError::IO("Failed to write the rendered output".into()))
+}
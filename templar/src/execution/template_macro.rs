@@ -0,0 +1,87 @@
+use super::*;
+
+/// A single parameter of a template macro, with an optional default expression
+#[derive(Debug)]
+pub(crate) struct MacroParam {
+    pub name: String,
+    pub default: Option<Node>,
+}
+
+/// A reusable template fragment defined with `{% macro name(args) %}...{% end macro %}`
+#[derive(Debug)]
+pub(crate) struct TemplateMacro {
+    name: String,
+    params: Vec<MacroParam>,
+    body: Node,
+}
+
+impl TemplateMacro {
+    pub fn new(name: String, params: Vec<MacroParam>, body: Node) -> Self {
+        TemplateMacro { name, params, body }
+    }
+
+    #[inline]
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    pub fn params(&self) -> &[MacroParam] {
+        &self.params
+    }
+
+    pub fn body(&self) -> &Node {
+        &self.body
+    }
+
+    /// The parameter defaults and body of the macro
+    pub fn nodes(&self) -> impl Iterator<Item = &Node> {
+        self.params
+            .iter()
+            .filter_map(|p| p.default.as_ref())
+            .chain(std::iter::once(&self.body))
+    }
+
+    /// Run the macro body in its own scope. Arguments are evaluated in the calling context, while
+    /// defaults are evaluated inside the macro scope so they may refer to earlier parameters.
+    /// `caller` is the lambda made from the body of a call block, if there is one.
+    pub fn call(&self, ctx: &ContextWrapper, args: &[Node], caller: Option<Data>) -> Data {
+        if args.len() > self.params.len() {
+            return TemplarError::RenderFailure(format!(
+                "Macro '{}' takes at most {} arguments but {} were supplied",
+                self.name,
+                self.params.len(),
+                args.len()
+            ))
+            .into();
+        }
+        let scope = ctx.create_scope();
+        for (i, param) in self.params.iter().enumerate() {
+            let value = match (args.get(i), &param.default) {
+                (Some(arg), _) => arg.exec(ctx),
+                (None, Some(default)) => default.exec(&scope),
+                (None, None) => {
+                    return TemplarError::RenderFailure(format!(
+                        "Macro '{}' is missing a value for parameter '{}'",
+                        self.name, param.name
+                    ))
+                    .into()
+                }
+            };
+            let value = match value.into_result() {
+                Ok(v) => v,
+                Err(e) => return e.into(),
+            };
+            let r = scope.set_path(&[&param.name.as_str().into()], value);
+            if r.is_err() {
+                return Data::check(r);
+            }
+        }
+        // Without a call block, null hides the caller of any macro this one is called from
+        let caller = caller.map_or(InnerData::Null, Data::into_inner);
+        let r = scope.set_path(&[&"caller".into()], caller);
+        if r.is_err() {
+            return Data::check(r);
+        }
+        self.body.exec(&scope)
+    }
+}
@@ -0,0 +1,854 @@
+use super::*;
+use std::cell::RefCell;
+use std::fmt::Write;
+
+type FilterFn = Arc<dyn Fn(Data, Data) -> Data + Sync + Send>;
+type FunctionFn = Arc<dyn Fn(Data) -> Data + Sync + Send>;
+
+/// A template lowered to a flat list of instructions for a small stack machine, created with
+/// `Template::compile`.
+///
+/// Rendering a compiled template gives exactly the same result as rendering the template it was
+/// compiled from, but avoids walking the tree of operations. Text, output expressions, conditions,
+/// loops, filters, functions and tests are run by the machine. Assignments, includes, macro calls,
+/// lambdas and the undefined value policies are handed back to the tree walker. Renders within
+/// `RenderLimits` are run by the tree walker from start to end.
+///
+/// # Usage
+///
+/// ```
+/// # use templar::*;
+/// let context = StandardContext::new();
+/// context.set_path(&[&"name".into()], "world")?;
+///
+/// let template = Templar::global().parse("{% for i in [1, 2] %}{{ i }}:{{ name }} {% end for %}")?;
+/// let compiled = template.compile();
+/// assert_eq!(compiled.render(&context)?, "1:world 2:world ");
+/// # Ok::<(), templar::TemplarError>(())
+/// ```
+#[derive(Clone)]
+pub struct CompiledTemplate(Arc<Program>, Template);
+
+impl CompiledTemplate {
+    pub(crate) fn new(template: &Template) -> Self {
+        let mut compiler = Compiler::default();
+        compiler.chunk(&template.root_node(), false);
+        CompiledTemplate(Arc::new(compiler.program), template.clone())
+    }
+
+    /// Render the compiled template as a string, the same as `Template::render`
+    pub fn render(&self, ctx: &impl Context) -> Result<String> {
+        if self.1.is_limited() || limits::is_limited() {
+            return self.1.render(ctx);
+        }
+        self.exec(ctx).render()
+    }
+
+    /// Execute the compiled template, the same as `Template::exec`
+    pub fn exec(&self, ctx: &impl Context) -> Data {
+        // Limits are only counted by the tree walker
+        if self.1.is_limited() || limits::is_limited() {
+            return self.1.exec(ctx);
+        }
+        // Templates that were folded into a constant when parsed don't need the machine
+        if let [Instr::Const(i)] = self.0.chunks[0][..] {
+            return self.0.constants[i].clone();
+        }
+        MACHINE.with(|machine| match machine.try_borrow_mut() {
+            Ok(mut machine) => self.0.run(0, &ctx.wrap(), &mut machine),
+            // A filter or function is rendering another compiled template
+            Err(_) => self.0.run(0, &ctx.wrap(), &mut Machine::default()),
+        })
+    }
+}
+
+thread_local! {
+    /// The stacks are kept between runs so rendering does not allocate them each time
+    static MACHINE: RefCell<Machine> = RefCell::new(Machine::default());
+}
+
+impl fmt::Debug for CompiledTemplate {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("CompiledTemplate")
+            .field("chunks", &self.0.chunks)
+            .finish()
+    }
+}
+
+/// Jump targets are indexes into the chunk the instruction is in
+#[derive(Debug, Clone, Copy)]
+enum Instr {
+    /// Push a constant
+    Const(usize),
+    /// Push the value at a context path
+    Load(usize),
+    /// Push the value at a path that starts with a loop variable held in a register
+    LoadVar(usize),
+    /// Execute a node with the tree walker and push the result
+    Exec(usize),
+    /// Run a chunk in a new scope and push its result
+    Scope(usize),
+    /// Replace the top `n` values with a sequence of them
+    Array(usize),
+    /// Start building a map
+    BeginMap,
+    /// Pop a value into the map being built, or replace the map with the value and jump if it
+    /// failed
+    Insert {
+        key: usize,
+        fail: usize,
+    },
+    /// Push the map being built
+    EndMap,
+    /// Start building a string
+    BeginConcat,
+    /// Add pre-rendered text to the string being built
+    Text(usize),
+    /// Pop a value and render it onto the string being built, or replace the string with the
+    /// error and jump if it fails to render
+    Append(usize),
+    /// Push the string being built
+    EndConcat,
+    /// Jump if the top value is empty or failed, leaving it in place
+    Guard(usize),
+    /// Jump if the top value failed, leaving it in place
+    JumpIfFailed(usize),
+    /// Replace the top two values with the result of a piped operation
+    Binary(fn(Data, Data) -> Data),
+    /// Jump if the top value failed or is falsy, leaving it in place, or pop it
+    And(usize),
+    /// Jump if the top value failed or is truthy, leaving it in place, or pop it
+    Or(usize),
+    Not,
+    StrictNot,
+    /// Replace the top value with an undefined value error for the path if it is empty
+    Defined(usize),
+    /// Pop a condition, continuing if it is truthy or jumping to `otherwise` if it is not. A failed
+    /// condition is pushed back before jumping to `end`.
+    Branch {
+        otherwise: usize,
+        end: usize,
+    },
+    /// The same as `Branch` for conditions that must be booleans
+    StrictBranch {
+        otherwise: usize,
+        end: usize,
+    },
+    Jump(usize),
+    /// Replace the input and arguments on the top of the stack with the result of a filter
+    Filter(usize),
+    /// Replace the arguments on the top of the stack with the result of a function
+    Function(usize),
+    /// Replace the value and arguments on the top of the stack with the result of a test
+    Test(usize),
+    /// Pop the value to iterate over and bind the first item, jumping to `exit` with the result
+    /// if there is nothing to iterate
+    LoopStart {
+        bind: Bind,
+        exit: usize,
+    },
+    /// Pop the result of the loop body and bind the next item, jumping back to `body`, or push
+    /// the result of the loop
+    LoopNext {
+        bind: Bind,
+        body: usize,
+    },
+}
+
+/// Where a loop binds its items
+#[derive(Debug, Clone, Copy)]
+enum Bind {
+    /// A path in the context
+    Path(usize),
+    /// A register, for loops that nothing but their own paths can observe
+    Register(usize),
+}
+
+/// A path starting with a loop variable. Like nested scopes, the registers of the loops binding
+/// the variable are tried from the innermost out, then the context.
+struct Var {
+    slots: Vec<usize>,
+    path: usize,
+}
+
+#[derive(Default)]
+struct Program {
+    /// The first chunk is the template itself, the rest are the bodies of scopes
+    chunks: Vec<Vec<Instr>>,
+    constants: Vec<Data>,
+    text: Vec<String>,
+    paths: Vec<ContextPath>,
+    vars: Vec<Var>,
+    keys: Vec<InnerData>,
+    nodes: Vec<Node>,
+    filters: Vec<FilterFn>,
+    functions: Vec<FunctionFn>,
+    tests: Vec<TestExecutor>,
+}
+
+/// The state of a running loop. Values that are not sequences or maps are bound as they are and
+/// the body runs once, without items.
+struct Loop {
+    items: Option<std::vec::IntoIter<InnerData>>,
+    result: String,
+}
+
+/// The stacks used while running a program. Scopes run on the same stacks as the chunk they are
+/// in, and every chunk leaves exactly one value on the stack. A run only touches what it pushed,
+/// so anything left behind by a filter that panicked is ignored.
+#[derive(Default)]
+struct Machine {
+    stack: Vec<Data>,
+    strings: Vec<String>,
+    maps: Vec<BTreeMap<InnerData, InnerData>>,
+    loops: Vec<Loop>,
+    registers: Vec<InnerData>,
+}
+
+impl Program {
+    fn run(&self, chunk: usize, ctx: &ContextWrapper, machine: &mut Machine) -> Data {
+        let code = &self.chunks[chunk];
+        let base = machine.stack.len();
+        let mut pc = 0;
+        while let Some(instr) = code.get(pc) {
+            pc += 1;
+            let Machine {
+                stack,
+                strings,
+                maps,
+                loops,
+                registers,
+            } = &mut *machine;
+            match *instr {
+                Instr::Const(i) => stack.push(self.constants[i].clone()),
+                Instr::Load(i) => stack.push(ctx.get_interned_inner(&self.paths[i], ctx)),
+                Instr::LoadVar(i) => {
+                    let var = &self.vars[i];
+                    let path = &self.paths[var.path];
+                    let found = var
+                        .slots
+                        .iter()
+                        .map(|slot| lookup(&registers[*slot], &path[1..]))
+                        .find(|d| !d.is_empty());
+                    stack.push(found.unwrap_or_else(|| ctx.get_interned_inner(path, ctx)));
+                }
+                Instr::Exec(i) => stack.push(self.nodes[i].exec(ctx)),
+                Instr::Scope(i) => {
+                    let local_context = ctx.create_scope();
+                    let result = self.run(i, &local_context.wrap(), machine);
+                    machine.stack.push(result);
+                }
+                Instr::Array(n) => {
+                    let items = stack.split_off(stack.len() - n);
+                    stack.push(Data::from_vec(items));
+                }
+                Instr::BeginMap => maps.push(BTreeMap::new()),
+                Instr::Insert { key, fail } => match pop(stack).into_inner() {
+                    InnerData::Err(e) => {
+                        maps.pop();
+                        stack.push(e.into());
+                        pc = fail;
+                    }
+                    d => {
+                        let map = maps.last_mut().expect("no map to insert into");
+                        map.insert(self.keys[key].clone(), d);
+                    }
+                },
+                Instr::EndMap => stack.push(maps.pop().expect("no map to end").into()),
+                Instr::BeginConcat => strings.push(String::new()),
+                Instr::Text(i) => {
+                    let string = strings.last_mut().expect("no string to append to");
+                    string.push_str(&self.text[i]);
+                }
+                Instr::Append(fail) => {
+                    let string = strings.last_mut().expect("no string to append to");
+                    if let Err(e) = pop(stack).render_into(string) {
+                        strings.pop();
+                        stack.push(e.into());
+                        pc = fail;
+                    }
+                }
+                Instr::EndConcat => stack.push(strings.pop().expect("no string to end").into()),
+                Instr::Guard(end) => {
+                    let top = peek(stack);
+                    if top.is_empty() || top.is_failed() {
+                        pc = end;
+                    }
+                }
+                Instr::JumpIfFailed(end) => {
+                    if peek(stack).is_failed() {
+                        pc = end;
+                    }
+                }
+                Instr::Binary(op) => {
+                    let right = pop(stack);
+                    let left = pop(stack);
+                    stack.push(op(left, right));
+                }
+                Instr::And(end) => {
+                    let left = peek(stack);
+                    if left.is_failed() || !left.is_truthy() {
+                        pc = end;
+                    } else {
+                        stack.pop();
+                    }
+                }
+                Instr::Or(end) => {
+                    let left = peek(stack);
+                    if left.is_failed() || left.is_truthy() {
+                        pc = end;
+                    } else {
+                        stack.pop();
+                    }
+                }
+                Instr::Not => {
+                    let value = pop(stack);
+                    if value.is_failed() {
+                        stack.push(value);
+                    } else {
+                        stack.push((!value.is_truthy()).into());
+                    }
+                }
+                Instr::Defined(i) => {
+                    if peek(stack).is_empty() {
+                        stack.pop();
+                        stack.push(undefined_error(PathName(&self.paths[i]).to_string()));
+                    }
+                }
+                Instr::StrictNot => {
+                    let result = match pop(stack).into_inner() {
+                        InnerData::Bool(b) => (!b).into(),
+                        InnerData::Err(e) => e.into(),
+                        _ => TemplarError::RenderFailure("Not requires a boolean!".into()).into(),
+                    };
+                    stack.push(result);
+                }
+                Instr::Branch { otherwise, end } => {
+                    let cnd = pop(stack);
+                    if cnd.is_failed() {
+                        stack.push(cnd);
+                        pc = end;
+                    } else if !cnd.is_truthy() {
+                        pc = otherwise;
+                    }
+                }
+                Instr::StrictBranch { otherwise, end } => match pop(stack).into_inner() {
+                    InnerData::Bool(true) => {}
+                    InnerData::Bool(false) => pc = otherwise,
+                    InnerData::Err(e) => {
+                        stack.push(e.into());
+                        pc = end;
+                    }
+                    _ => {
+                        stack.push(
+                            TemplarError::RenderFailure(
+                                "If condition must evaluate to boolean!".into(),
+                            )
+                            .into(),
+                        );
+                        pc = end;
+                    }
+                },
+                Instr::Jump(target) => pc = target,
+                Instr::Filter(i) => {
+                    let args = pop(stack);
+                    let input = pop(stack);
+                    stack.push(self.filters[i](input, args));
+                }
+                Instr::Function(i) => {
+                    let args = pop(stack);
+                    stack.push(self.functions[i](args));
+                }
+                Instr::Test(i) => {
+                    let args = pop(stack);
+                    let value = pop(stack);
+                    if args.is_failed() {
+                        stack.push(args);
+                    } else {
+                        stack.push(self.tests[i].check(&value, args));
+                    }
+                }
+                Instr::LoopStart { bind, exit } => {
+                    let array = match pop(stack).into_result() {
+                        Ok(array) => array,
+                        Err(e) => {
+                            stack.push(e.into());
+                            pc = exit;
+                            continue;
+                        }
+                    };
+                    let items: Vec<InnerData> = match array.into_inner() {
+                        InnerData::Seq(items) => items,
+                        InnerData::Map(items) => items
+                            .into_iter()
+                            .map(|(k, v)| {
+                                let mut entry = BTreeMap::new();
+                                entry.insert("key".into(), k);
+                                entry.insert("value".into(), v);
+                                InnerData::from(entry)
+                            })
+                            .collect(),
+                        value => {
+                            let r = self.bind(ctx, registers, bind, value);
+                            if r.is_err() {
+                                stack.push(Data::check(r));
+                                pc = exit;
+                            } else {
+                                loops.push(Loop {
+                                    items: None,
+                                    result: String::new(),
+                                });
+                            }
+                            continue;
+                        }
+                    };
+                    let mut items = items.into_iter();
+                    match items.next() {
+                        None => {
+                            stack.push(String::new().into());
+                            pc = exit;
+                        }
+                        Some(item) => {
+                            let r = self.bind(ctx, registers, bind, item);
+                            if r.is_err() {
+                                stack.push(Data::check(r));
+                                pc = exit;
+                            } else {
+                                loops.push(Loop {
+                                    items: Some(items),
+                                    result: String::new(),
+                                });
+                            }
+                        }
+                    }
+                }
+                Instr::LoopNext { bind, body } => {
+                    let res = pop(stack);
+                    let state = loops.last_mut().expect("no loop to continue");
+                    let items = match &mut state.items {
+                        Some(items) => items,
+                        None => {
+                            loops.pop();
+                            stack.push(res);
+                            continue;
+                        }
+                    };
+                    match res.into_result() {
+                        Err(e) => {
+                            loops.pop();
+                            stack.push(e.into());
+                            continue;
+                        }
+                        Ok(res) => write!(state.result, "{}", res.inner_data())
+                            .expect("writing to a string cannot fail"),
+                    }
+                    match items.next() {
+                        Some(item) => {
+                            let r = self.bind(ctx, registers, bind, item);
+                            if r.is_err() {
+                                loops.pop();
+                                stack.push(Data::check(r));
+                            } else {
+                                pc = body;
+                            }
+                        }
+                        None => {
+                            let state = loops.pop().expect("no loop to end");
+                            stack.push(state.result.into());
+                        }
+                    }
+                }
+            }
+        }
+        let stack = &mut machine.stack;
+        if stack.len() > base {
+            pop(stack)
+        } else {
+            Data::empty()
+        }
+    }
+
+    fn bind(
+        &self,
+        ctx: &ContextWrapper,
+        registers: &mut Vec<InnerData>,
+        bind: Bind,
+        mut value: InnerData,
+    ) -> Result<()> {
+        match bind {
+            Bind::Path(path) => with_path(&self.paths[path], |p| ctx.set_path(p, value)),
+            Bind::Register(slot) => {
+                if registers.len() <= slot {
+                    registers.resize(slot + 1, InnerData::Unassigned);
+                }
+                unwrap_newtypes(&mut value);
+                registers[slot] = value;
+                Ok(())
+            }
+        }
+    }
+}
+
+/// Binding a value in the context drops any newtype wrappers in it, see `ContextMapValue`
+fn unwrap_newtypes(value: &mut InnerData) {
+    match value {
+        InnerData::Newtype(inner) => {
+            let inner = inner.take();
+            *value = inner;
+            unwrap_newtypes(value)
+        }
+        InnerData::Seq(items) => items.iter_mut().for_each(unwrap_newtypes),
+        InnerData::Map(items) => items.values_mut().for_each(unwrap_newtypes),
+        _ => {}
+    }
+}
+
+/// Read a path from a loop variable the same way the context reads it from a bound value. Keys
+/// are looked up in maps and any other value is the result for every key under it, and the first
+/// error in a sequence or map fails the whole value.
+fn lookup(value: &InnerData, path: &[InnerData]) -> Data {
+    let mut current = value;
+    for key in path.iter() {
+        match current {
+            InnerData::Map(map) => match map.get(key) {
+                Some(v) => current = v,
+                None => return Data::empty(),
+            },
+            _ => break,
+        }
+    }
+    match first_error(current) {
+        Some(e) => e.clone().into(),
+        None => Data::new(current.clone()),
+    }
+}
+
+fn first_error(value: &InnerData) -> Option<&TemplarError> {
+    match value {
+        InnerData::Err(e) => Some(e),
+        InnerData::Seq(items) => items.iter().find_map(first_error),
+        InnerData::Map(items) => items.values().find_map(first_error),
+        _ => None,
+    }
+}
+
+/// Call `f` with the references to the parts of a path that the context expects, without
+/// allocating for short paths
+fn with_path<T>(path: &[InnerData], f: impl FnOnce(&[&InnerData]) -> T) -> T {
+    const SHORT: usize = 4;
+    match path.first() {
+        Some(first) if path.len() <= SHORT => {
+            let mut refs = [first; SHORT];
+            for (r, p) in refs.iter_mut().zip(path.iter()) {
+                *r = p;
+            }
+            f(&refs[..path.len()])
+        }
+        _ => f(&path.iter().collect::<Vec<_>>()),
+    }
+}
+
+fn pop(stack: &mut Vec<Data>) -> Data {
+    stack.pop().expect("compiled template stack underflow")
+}
+
+fn peek(stack: &[Data]) -> &Data {
+    stack.last().expect("compiled template stack underflow")
+}
+
+#[derive(Default)]
+struct Compiler {
+    program: Program,
+    /// The variables of the register loops being compiled, indexed by register
+    registers: Vec<InnerData>,
+}
+
+impl Compiler {
+    /// Compile a node into a new chunk, returning its index. A loop that is the whole of a scope
+    /// keeps its variable in a register if it can, as the binding disappears with the scope.
+    fn chunk(&mut self, node: &Node, scoped: bool) -> usize {
+        let index = self.program.chunks.len();
+        self.program.chunks.push(vec![]);
+        let mut code = vec![];
+        match (node, register_loop(node)) {
+            (Node::Operation(op), Some(name)) if scoped => {
+                self.for_loop(op.nodes(), Some(name.clone()), &mut code)
+            }
+            _ => self.node(node, &mut code),
+        }
+        self.program.chunks[index] = code;
+        index
+    }
+
+    fn node(&mut self, node: &Node, code: &mut Vec<Instr>) {
+        match node {
+            Node::Data(d) => code.push(Instr::Const(push(&mut self.program.constants, d.clone()))),
+            Node::Value(path) => {
+                let slots: Vec<usize> = match path.first() {
+                    Some(name) => (0..self.registers.len())
+                        .rev()
+                        .filter(|slot| self.registers[*slot] == *name)
+                        .collect(),
+                    None => vec![],
+                };
+                let path = push(&mut self.program.paths, path.clone());
+                code.push(match slots.is_empty() {
+                    true => Instr::Load(path),
+                    false => Instr::LoadVar(push(&mut self.program.vars, Var { slots, path })),
+                });
+            }
+            Node::Scope(inner) => {
+                let chunk = self.chunk(inner, true);
+                code.push(Instr::Scope(chunk));
+            }
+            Node::Expr(nodes) if nodes.is_empty() => self.node(&Node::default(), code),
+            Node::Expr(nodes) if nodes.len() == 1 => self.node(&nodes[0], code),
+            Node::Expr(nodes) | Node::Array(nodes) => {
+                nodes.iter().for_each(|n| self.node(n, code));
+                code.push(Instr::Array(nodes.len()));
+            }
+            Node::Map(map) => {
+                code.push(Instr::BeginMap);
+                let mut inserts = vec![];
+                for (key, value) in map.iter() {
+                    self.node(value, code);
+                    let key = push(&mut self.program.keys, key.clone());
+                    inserts.push(code.len());
+                    code.push(Instr::Insert { key, fail: 0 });
+                }
+                code.push(Instr::EndMap);
+                let fail = code.len();
+                for at in inserts {
+                    if let Instr::Insert { key, .. } = code[at] {
+                        code[at] = Instr::Insert { key, fail };
+                    }
+                }
+            }
+            Node::Operation(op) => {
+                if !self.operation(op, code) {
+                    code.push(Instr::Exec(push(&mut self.program.nodes, node.clone())));
+                }
+            }
+        }
+    }
+
+    /// Compile an operation, returning false if it is left to the tree walker
+    fn operation(&mut self, op: &Operation, code: &mut Vec<Instr>) -> bool {
+        if !lowered(op) {
+            return false;
+        }
+        let nodes = op.nodes();
+        match (op.executor(), op.name()) {
+            (Executors::Piped(_), "And") | (Executors::Piped(_), "Or") => {
+                self.node(&nodes[0], code);
+                let at = code.len();
+                code.push(Instr::Jump(0));
+                self.node(&nodes[1], code);
+                let end = code.len();
+                code[at] = match op.name() {
+                    "And" => Instr::And(end),
+                    _ => Instr::Or(end),
+                };
+            }
+            (Executors::Piped(_), name) => {
+                let binary = values::by_name(name).expect("checked by lowered");
+                self.node(&nodes[0], code);
+                let at = code.len();
+                code.push(Instr::Guard(0));
+                self.node(&nodes[1], code);
+                code.push(Instr::Binary(binary));
+                code[at] = Instr::Guard(code.len());
+            }
+            (Executors::Conditional(_), name) => {
+                self.node(&nodes[0], code);
+                let at = code.len();
+                code.push(Instr::Jump(0));
+                self.node(&nodes[1], code);
+                let jump = code.len();
+                code.push(Instr::Jump(0));
+                let otherwise = code.len();
+                self.node(&nodes[2], code);
+                let end = code.len();
+                code[jump] = Instr::Jump(end);
+                code[at] = match name {
+                    "StrictIfThen" => Instr::StrictBranch { otherwise, end },
+                    _ => Instr::Branch { otherwise, end },
+                };
+            }
+            (Executors::Indeterminate(_), "Concat") => {
+                code.push(Instr::BeginConcat);
+                let mut appends = vec![];
+                for node in nodes.iter() {
+                    if let Node::Data(d) = node {
+                        if let Ok(text) = d.clone().render() {
+                            code.push(Instr::Text(push(&mut self.program.text, text)));
+                            continue;
+                        }
+                    }
+                    self.node(node, code);
+                    appends.push(code.len());
+                    code.push(Instr::Append(0));
+                }
+                code.push(Instr::EndConcat);
+                let fail = code.len();
+                for at in appends {
+                    code[at] = Instr::Append(fail);
+                }
+            }
+            (Executors::Indeterminate(_), "Not") => {
+                self.node(&nodes[0], code);
+                code.push(Instr::Not);
+            }
+            (Executors::Indeterminate(_), "StrictNot") => {
+                self.node(&nodes[0], code);
+                code.push(Instr::StrictNot);
+            }
+            (Executors::Indeterminate(_), "StrictValue") => {
+                self.node(&nodes[0], code);
+                if let Node::Value(path) = &nodes[0] {
+                    code.push(Instr::Defined(push(&mut self.program.paths, path.clone())));
+                }
+            }
+            (Executors::Loop(_), _) => self.for_loop(nodes, None, code),
+            (Executors::Filter(ex), _) => {
+                self.node(&nodes[0], code);
+                self.node(&nodes[1], code);
+                code.push(Instr::Filter(push(
+                    &mut self.program.filters,
+                    ex.func().clone(),
+                )));
+            }
+            (Executors::Function(ex), _) => {
+                self.node(&nodes[0], code);
+                code.push(Instr::Function(push(
+                    &mut self.program.functions,
+                    ex.func().clone(),
+                )));
+            }
+            (Executors::Test(ex), _) => {
+                self.node(&nodes[0], code);
+                let at = code.len();
+                code.push(Instr::Jump(0));
+                self.node(&nodes[1], code);
+                code.push(Instr::Test(push(&mut self.program.tests, ex.clone())));
+                code[at] = Instr::JumpIfFailed(code.len());
+            }
+            _ => unreachable!("checked by lowered"),
+        }
+        true
+    }
+
+    /// Compile a loop, binding its items to a register if `register` names the loop variable
+    fn for_loop(&mut self, nodes: &[Node], register: Option<InnerData>, code: &mut Vec<Instr>) {
+        let bind = match (&register, &nodes[0]) {
+            (Some(_), _) => Bind::Register(self.registers.len()),
+            (None, Node::Value(path)) => Bind::Path(push(&mut self.program.paths, path.clone())),
+            (None, _) => unreachable!("checked by lowered"),
+        };
+        self.node(&nodes[1], code);
+        let start = code.len();
+        code.push(Instr::Jump(0));
+        let body = code.len();
+        let registered = register.is_some();
+        self.registers.extend(register);
+        self.node(&nodes[2], code);
+        if registered {
+            self.registers.pop();
+        }
+        code.push(Instr::LoopNext { bind, body });
+        let exit = code.len();
+        code[start] = Instr::LoopStart { bind, exit };
+    }
+}
+
+/// True if the compiler has instructions for an operation, otherwise it is left to the tree
+/// walker
+fn lowered(op: &Operation) -> bool {
+    let nodes = op.nodes();
+    match op.executor() {
+        Executors::Piped(_) => {
+            matches!(op.name(), "And" | "Or") || values::by_name(op.name()).is_some()
+        }
+        Executors::Conditional(_) => nodes.len() == 3,
+        Executors::Indeterminate(_) => match op.name() {
+            "Concat" => true,
+            "Not" | "StrictNot" => nodes.len() == 1,
+            "StrictValue" => matches!(nodes, [Node::Value(_)]),
+            _ => false,
+        },
+        Executors::Loop(_) => nodes.len() == 3 && matches!(nodes[0], Node::Value(_)),
+        Executors::Filter(_) | Executors::Test(_) => nodes.len() == 2,
+        Executors::Function(_) => nodes.len() == 1,
+        Executors::Macro(_) | Executors::Lambda(_) => false,
+    }
+}
+
+/// The name of the loop variable if the node is a loop that can keep its variable in a register.
+/// Nothing in the loop may touch the context other than by reading paths below the root, so
+/// nothing else can bind a value that would shadow the variable or read it from the context. The
+/// body may only read the loop variables themselves, as any other path could hold a dynamic value
+/// that reads the variable from the context.
+fn register_loop(node: &Node) -> Option<&InnerData> {
+    let op = match node {
+        Node::Operation(op) if matches!(op.executor(), Executors::Loop(_)) && lowered(op) => op,
+        _ => return None,
+    };
+    match &op.nodes()[0] {
+        Node::Value(path)
+            if path.len() == 1
+                && !op.nodes()[1..].iter().any(touches_context)
+                && reads_only(&op.nodes()[2], &mut vec![&path[0]]) =>
+        {
+            Some(&path[0])
+        }
+        _ => None,
+    }
+}
+
+/// True if every path the node reads starts with one of the loop variables in `vars`
+fn reads_only<'a>(node: &'a Node, vars: &mut Vec<&'a InnerData>) -> bool {
+    match node {
+        Node::Data(_) => true,
+        Node::Value(path) => path.first().is_some_and(|key| vars.contains(&key)),
+        Node::Scope(inner) => match register_loop(inner) {
+            Some(var) => {
+                let loop_nodes = match &**inner {
+                    Node::Operation(op) => op.nodes(),
+                    _ => return false,
+                };
+                if !reads_only(&loop_nodes[1], vars) {
+                    return false;
+                }
+                vars.push(var);
+                let body = reads_only(&loop_nodes[2], vars);
+                vars.pop();
+                body
+            }
+            None => reads_only(inner, vars),
+        },
+        Node::Expr(nodes) | Node::Array(nodes) => nodes.iter().all(|n| reads_only(n, vars)),
+        Node::Map(map) => map.values().all(|n| reads_only(n, vars)),
+        Node::Operation(op) => op.nodes().iter().all(|n| reads_only(n, vars)),
+    }
+}
+
+fn touches_context(node: &Node) -> bool {
+    match node {
+        Node::Data(_) => false,
+        Node::Value(path) => path.is_empty(),
+        Node::Scope(inner) => register_loop(inner).is_none() && touches_context(inner),
+        Node::Expr(nodes) | Node::Array(nodes) => nodes.iter().any(touches_context),
+        Node::Map(map) => map.values().any(touches_context),
+        Node::Operation(op) => {
+            !lowered(op)
+                || matches!(op.executor(), Executors::Loop(_))
+                || op.nodes().iter().any(touches_context)
+        }
+    }
+}
+
+/// Add an item to a pool, returning its index
+fn push<T>(pool: &mut Vec<T>, item: T) -> usize {
+    pool.push(item);
+    pool.len() - 1
+}
@@ -57,16 +57,29 @@ pub fn b64decode(in_string: String) -> std::result::Result<String, Box<dyn std::
     Ok(base64::encode(&in_string))
 }
 
+/// Retrieve the single optional argument of a filter, which may be passed by position or by name
+fn optional_arg(args: Data, name: &str) -> Result<Option<InnerData>> {
+    let mut args = args.into_arguments(1)?;
+    let result = args.take(0, name)?;
+    args.finish(1)?;
+    Ok(result)
+}
+
+/// Render the delimiter argument used by split and join, which defaults to a newline
+fn delimiter(args: Data) -> Result<String> {
+    match optional_arg(args, "sep")? {
+        Some(sep) => match Data::new(sep).render()? {
+            s if s.is_empty() => Ok("\n".into()),
+            s => Ok(s),
+        },
+        None => Ok("\n".into()),
+    }
+}
+
 pub fn split(value: Data, args: Data) -> Data {
-    let delim = match args.render() {
-        Ok(s) => {
-            if s.is_empty() {
-                "\n".to_string()
-            } else {
-                s
-            }
-        }
-        _ => "\n".to_string(),
+    let delim = match delimiter(args) {
+        Ok(delim) => delim,
+        Err(e) => return e.into(),
     };
     match value.into_result() {
         Ok(d) => InnerData::Seq(
@@ -82,31 +95,33 @@ pub fn split(value: Data, args: Data) -> Data {
 }
 
 pub fn join(value: Data, args: Data) -> Data {
-    let delim = match args.render() {
-        Ok(s) => {
-            if s.is_empty() {
-                "\n".to_string()
-            } else {
-                s
-            }
-        }
-        _ => "\n".to_string(),
+    let delim = match delimiter(args) {
+        Ok(delim) => delim,
+        Err(e) => return e.into(),
     };
     match value.into_inner() {
-        InnerData::Seq(s) => s
-            .iter()
-            .map(|i| i.to_string())
-            .collect::<Vec<String>>()
-            .join(&delim)
-            .into(),
+        InnerData::Seq(s) => {
+            // Checked as it grows, as the items may be much longer once rendered
+            let mut result = String::new();
+            for (i, item) in s.iter().enumerate() {
+                if i > 0 {
+                    result.push_str(&delim);
+                }
+                result.push_str(&item.to_string());
+                if let Err(e) = limits::check_output(result.len()) {
+                    return e.into();
+                }
+            }
+            result.into()
+        }
         InnerData::Err(e) => e.into(),
         v => v.into(),
     }
 }
 
 pub fn index(value: Data, args: Data) -> Data {
-    let arg = match args.into_result() {
-        Ok(i) => i.as_usize(),
+    let arg = match optional_arg(args, "index") {
+        Ok(i) => i.and_then(|i| i.as_usize()),
         Err(e) => return e.into(),
     };
     if let Some(i) = arg {
@@ -124,12 +139,18 @@ pub fn json(value: Data, args: Data) -> Data {
     match value.into_inner() {
         InnerData::Err(e) => e.into(),
         val => {
-            let arg = args.render().unwrap_or_default();
-            match arg.as_str() {
-                "pretty" => serde_json::to_string_pretty(&val)
+            let pretty = match optional_arg(args, "pretty") {
+                Ok(Some(InnerData::Bool(pretty))) => pretty,
+                Ok(Some(InnerData::String(s))) => s == "pretty",
+                Ok(_) => false,
+                Err(e) => return e.into(),
+            };
+            if pretty {
+                serde_json::to_string_pretty(&val)
                     .unwrap_or_default()
-                    .into(),
-                _ => serde_json::to_string(&val).unwrap_or_default().into(),
+                    .into()
+            } else {
+                serde_json::to_string(&val).unwrap_or_default().into()
             }
         }
     }
@@ -147,14 +168,18 @@ pub fn yaml(value: Data, _: Data) -> Data {
 pub fn string(in_string: String) -> String { in_string }
 
 pub fn key(value: Data, args: Data) -> Data {
-    if args.is_empty() || args.is_failed() {
-        return TemplarError::RenderFailure(
-            "Attempted to retrieve a key on a value that is not a map".into(),
-        )
-        .into();
-    }
+    let key = match optional_arg(args, "name") {
+        Ok(Some(key)) if key != InnerData::Unassigned => key,
+        Err(e) => return e.into(),
+        _ => {
+            return TemplarError::RenderFailure(
+                "Attempted to retrieve a key on a value that is not a map".into(),
+            )
+            .into()
+        }
+    };
     match value.into_inner() {
-        InnerData::Map(map) => map[&args.into_result().unwrap()].clone().into(),
+        InnerData::Map(map) => map[&key].clone().into(),
         _ => TemplarError::RenderFailure(
             "Attempted to retrieve a key on a value that is not a map".into(),
         )
@@ -164,7 +189,10 @@ pub fn key(value: Data, args: Data) -> Data {
 
 pub fn default(value: Data, args: Data) -> Data {
     if value.is_empty() || value.is_failed() {
-        args
+        match optional_arg(args, "value") {
+            Ok(default) => Data::new(default.unwrap_or_default()),
+            Err(e) => e.into(),
+        }
     } else {
         value
     }
@@ -197,13 +225,51 @@ pub fn require(value: Data, _: Data) -> Data {
 }
 
 #[templar_filter]
-pub fn replace(filter_in: String, old: String, new: String) -> String {
-    filter_in.replace(&old, &new)
+pub fn replace(filter_in: String, old: String, new: String) -> Result<String> {
+    // The size of the result is checked before building it, as every match may grow the input
+    let grown = new.len().saturating_sub(old.len());
+    let matches = if grown > 0 {
+        filter_in.matches(old.as_str()).count()
+    } else {
+        0
+    };
+    limits::check_output(grown.saturating_mul(matches).saturating_add(filter_in.len()))?;
+    Ok(filter_in.replace(&old, &new))
+}
+
+#[templar_filter]
+pub fn indent(filter_in: String, width: u64) -> Result<String> {
+    let width = width as usize;
+    let indented = filter_in
+        .split('\n')
+        .skip(1)
+        .filter(|line| !line.trim().is_empty())
+        .count();
+    // The size of the result is checked before building it, as a wide indent can be far larger
+    // than the input
+    let len = width.saturating_mul(indented).saturating_add(filter_in.len());
+    limits::check_output(len)?;
+    let mut lines = filter_in.split('\n');
+    let mut result = String::with_capacity(len);
+    result.push_str(lines.next().unwrap_or_default());
+    for line in lines {
+        result.push('\n');
+        if !line.trim().is_empty() {
+            result.extend(std::iter::repeat_n(' ', width));
+        }
+        result.push_str(line);
+    }
+    Ok(result)
 }
 
 #[templar_filter]
 pub fn truncate(filter_in: String, size: u64) -> String {
     let mut res = filter_in;
-    res.truncate(size as usize);
+    // Cut before the character the size falls within, rather than in the middle of it
+    let mut end = (size as usize).min(res.len());
+    while !res.is_char_boundary(end) {
+        end -= 1;
+    }
+    res.truncate(end);
     res
 }
@@ -0,0 +1,169 @@
+use crate::*;
+use std::collections::BTreeMap;
+
+/// Retrieve the items to iterate for a higher-order filter. Maps are iterated as entries with a
+/// `key` and a `value`, in the same way as for loops.
+fn items(value: Data) -> Result<Vec<InnerData>> {
+    match value.into_inner() {
+        InnerData::Err(e) => Err(e),
+        InnerData::Unassigned | InnerData::Null => Ok(vec![]),
+        InnerData::Seq(s) => Ok(s),
+        InnerData::Map(m) => Ok(m
+            .into_iter()
+            .map(|(k, v)| {
+                let mut entry = BTreeMap::new();
+                entry.insert("key".into(), k);
+                entry.insert("value".into(), v);
+                InnerData::from(entry)
+            })
+            .collect()),
+        _ => Err(TemplarError::RenderFailure(
+            "This filter requires an array or a map".into(),
+        )),
+    }
+}
+
+/// Retrieve the lambda argument at `index` which can also be passed by `name`
+fn lambda_arg(args: &mut Arguments, index: usize, name: &str) -> Result<Option<Lambda>> {
+    match args.take(index, name)? {
+        None | Some(InnerData::Unassigned) => Ok(None),
+        Some(arg) => match Data::new(arg).as_lambda() {
+            Some(lambda) => Ok(Some(lambda.clone())),
+            None => Err(TemplarError::RenderFailure(format!(
+                "Argument '{}' must be a lambda, e.g. `x => x.name`",
+                name
+            ))),
+        },
+    }
+}
+
+fn required_lambda(args: Data) -> Result<Lambda> {
+    let mut args = args.into_arguments(1)?;
+    let lambda = lambda_arg(&mut args, 0, "fn")?;
+    args.finish(1)?;
+    lambda.ok_or_else(|| TemplarError::RenderFailure("This filter requires a lambda".into()))
+}
+
+fn optional_lambda(args: Data) -> Result<Option<Lambda>> {
+    let mut args = args.into_arguments(1)?;
+    let lambda = lambda_arg(&mut args, 0, "fn")?;
+    args.finish(1)?;
+    Ok(lambda)
+}
+
+/// Call a lambda with a single item, or return the item itself if there is no lambda
+fn apply(lambda: Option<&Lambda>, item: &InnerData) -> Result<InnerData> {
+    match lambda {
+        Some(lambda) => Ok(lambda.call(vec![item.clone()]).into_result()?.into_inner()),
+        None => Ok(item.clone()),
+    }
+}
+
+fn truthy(value: InnerData) -> bool {
+    Data::new(value).is_truthy()
+}
+
+macro_rules! lambda_filter {
+    ($( $name:ident ( $value:ident , $args:ident ) -> $body:block )*) => {
+        $(
+            pub fn $name($value: Data, $args: Data) -> Data {
+                let result: Result<InnerData> = (|| $body)();
+                Data::from_result(result)
+            }
+        )*
+    };
+}
+
+lambda_filter! {
+    map(value, args) -> {
+        let lambda = required_lambda(args)?;
+        items(value)?
+            .iter()
+            .map(|item| apply(Some(&lambda), item))
+            .collect::<Result<Vec<InnerData>>>()
+            .map(InnerData::Seq)
+    }
+
+    select(value, args) -> {
+        let lambda = optional_lambda(args)?;
+        let mut result = vec![];
+        for item in items(value)?.into_iter() {
+            if truthy(apply(lambda.as_ref(), &item)?) {
+                result.push(item);
+            }
+        }
+        Ok(InnerData::Seq(result))
+    }
+
+    reject(value, args) -> {
+        let lambda = optional_lambda(args)?;
+        let mut result = vec![];
+        for item in items(value)?.into_iter() {
+            if !truthy(apply(lambda.as_ref(), &item)?) {
+                result.push(item);
+            }
+        }
+        Ok(InnerData::Seq(result))
+    }
+
+    sort_by(value, args) -> {
+        let lambda = required_lambda(args)?;
+        let mut keyed = items(value)?
+            .into_iter()
+            .map(|item| Ok((apply(Some(&lambda), &item)?, item)))
+            .collect::<Result<Vec<(InnerData, InnerData)>>>()?;
+        keyed.sort_by(|a, b| a.0.cmp(&b.0));
+        Ok(InnerData::Seq(keyed.into_iter().map(|(_, item)| item).collect()))
+    }
+
+    group_by(value, args) -> {
+        let lambda = required_lambda(args)?;
+        let mut groups = BTreeMap::<InnerData, Vec<InnerData>>::new();
+        for item in items(value)?.into_iter() {
+            let key = apply(Some(&lambda), &item)?;
+            groups.entry(key).or_default().push(item);
+        }
+        let groups: BTreeMap<InnerData, InnerData> = groups
+            .into_iter()
+            .map(|(k, v)| (k, InnerData::Seq(v)))
+            .collect();
+        Ok(InnerData::from(groups))
+    }
+
+    any(value, args) -> {
+        let lambda = optional_lambda(args)?;
+        for item in items(value)?.iter() {
+            if truthy(apply(lambda.as_ref(), item)?) {
+                return Ok(true.into());
+            }
+        }
+        Ok(false.into())
+    }
+
+    all(value, args) -> {
+        let lambda = optional_lambda(args)?;
+        for item in items(value)?.iter() {
+            if !truthy(apply(lambda.as_ref(), item)?) {
+                return Ok(false.into());
+            }
+        }
+        Ok(true.into())
+    }
+
+    reduce(value, args) -> {
+        let mut args = args.into_arguments(2)?;
+        let lambda = lambda_arg(&mut args, 0, "fn")?
+            .ok_or_else(|| TemplarError::RenderFailure("reduce requires a lambda".into()))?;
+        let initial = args.take(1, "initial")?;
+        args.finish(2)?;
+        let mut items = items(value)?.into_iter();
+        let mut acc = match initial.or_else(|| items.next()) {
+            Some(acc) => acc,
+            None => return Ok(InnerData::Unassigned),
+        };
+        for item in items {
+            acc = lambda.call(vec![acc, item]).into_result()?.into_inner();
+        }
+        Ok(acc)
+    }
+}
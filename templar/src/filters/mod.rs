@@ -8,30 +8,51 @@ Filters are used to process the result of an expression in a template.
 As an example, the expression `{{ 'hello' | upper }}` uses the "upper" filter to create
 the upper case result "HELLO".
 
+Arguments can be passed by position or by name, e.g. `{{ items | join(sep=', ') }}`. Custom filters
+can use `Data::into_arguments` to accept both forms.
+
+Filters applied to literals, e.g. `{{ 'title' | upper }}`, are evaluated once when the template is parsed. Custom
+filters are only evaluated early if they are added with `TemplarBuilder::add_pure_filter`.
+
+Filters such as `map` and `select` take a lambda, e.g. `{{ services | select(s => s.port > 1024) | map(s => s.name) }}`.
+A lambda with more than one parameter is written as `(acc, x) => acc + x`. Lambdas capture any values they refer to from
+the surrounding context, and maps are passed to them as entries with a `key` and a `value`. Custom filters can invoke
+a lambda argument with `Data::as_lambda` and `Lambda::call`.
+
 # Built in filters
 
 - require: Will throw an error if the result is empty or null
-- default(any): Replaces empty, null, or error types with the default value from the args
+- default(value): Replaces empty, null, or error types with the default value from the args
 - length: Returns the length of a string or array
 - lower: Lowercase the rendered result
 - upper: Uppercase the rendered result
 - trim: Trim whitespace off the rendered result
-- split(str?): Split a string into an array. Delimited by newline, but an arg can be used to override the delimiter.
-- index(int): Retrieve the int index from the array
-- join(str?): Join an array with the provided string. Defaults to newline
+- split(sep?): Split a string into an array. Delimited by newline, but an arg can be used to override the delimiter.
+- index(index): Retrieve the int index from the array
+- join(sep?): Join an array with the provided string. Defaults to newline
 - string: Forces the result into a string type, usually by rendering it
-- key(str): Retrieve the value of the specified key from the dictionary
+- key(name): Retrieve the value of the specified key from the dictionary
 - escape_html: (alias 'e') Render the result and escape HTML characters
 - replace: Replace occurrences of a character in a string
+- indent(int): Indent every line after the first by the number of spaces specified. Blank lines are left alone
+- map(fn): Call the lambda with each item of an array, returning an array of the results
+- select(fn?): Keep the items of an array where the lambda returns true, or the items that are true if no lambda is given
+- reject(fn?): Remove the items of an array where the lambda returns true, or the items that are true if no lambda is given
+- sort_by(fn): Sort an array by the result of the lambda for each item
+- group_by(fn): Group the items of an array into a map keyed by the result of the lambda for each item
+- any(fn?): True if the lambda returns true for any item
+- all(fn?): True if the lambda returns true for every item
+- reduce(fn, initial?): Combine the items of an array with a two parameter lambda, e.g. `reduce((acc, x) => acc + x)`
 - yaml: (alias yml) Serialize the data into a YAML string. Requires"yaml-extension" feature (default on)
-- json(str?): Serialize the data into a JSON string. Set str to 'pretty' to print with indentation. Requires the "json-extension" feature (default on)
+- json(pretty?): Serialize the data into a JSON string. Set pretty to true (or 'pretty') to print with indentation. Requires the "json-extension" feature (default on)
 - base64(str?): Encode the result as Base64. If the optional string parameter is set to "decode" then it will try to decode instead. Requires "base64-extension" feature (default on)
 */
 
 mod common;
+mod lambda;
 
 use crate::*;
-use std::collections::HashMap;
+use std::collections::{HashMap, HashSet};
 
 /// This is the definition used when adding filters to Templar
 pub type Filter = dyn Fn(Data, Data) -> Data + Send + Sync;
@@ -46,6 +67,16 @@ macro_rules! builtin_filters {
             )*
             res
         }
+
+        /// Every built in filter only depends on its input and arguments
+        pub(crate) fn default_pure_filters() -> HashSet<String> {
+            let mut res = HashSet::new();
+            $(
+                $( #[ $attr ] )*
+                res.insert($name.into());
+            )*
+            res
+        }
     };
 }
 
@@ -66,6 +97,15 @@ builtin_filters! {
     "e": common::escape_html;
     "replace": common::replace;
     "truncate": common::truncate;
+    "indent": common::indent;
+    "map": lambda::map;
+    "select": lambda::select;
+    "reject": lambda::reject;
+    "sort_by": lambda::sort_by;
+    "group_by": lambda::group_by;
+    "any": lambda::any;
+    "all": lambda::all;
+    "reduce": lambda::reduce;
 
     #[cfg(feature = "yaml-extension")]
     "yaml": common::yaml;
@@ -32,33 +32,54 @@ pub fn env(env_var: String) -> Result<String> {
     std::env::var(env_var).wrap()
 }
 
+/// Only marks the value it is used in as volatile, see `StandardContext::memoized`
+pub fn volatile(args: Data) -> Data {
+    args
+}
+
 pub fn script(args: Data) -> Data {
-    let mut sh_args = vec![InnerData::String("sh".into()), "-c".into()];
-    match args.into_result().map(|i| i.into_inner()) {
-        Ok(InnerData::Seq(s)) => {
-            for arg in s.iter() {
-                sh_args.push(arg.clone())
-            }
-        }
+    let mut sh_args = vec!["sh".to_string(), "-c".into()];
+    let cwd = match command_args(args, &mut sh_args) {
+        Ok(cwd) => cwd,
         Err(e) => return e.into(),
-        Ok(other) => sh_args.push(other),
-    }
-    command(sh_args.into())
+    };
+    run(sh_args, cwd)
 }
 
 pub fn command(args: Data) -> Data {
-    let mut sh_args = vec![];
-    match args.into_result().map(|i| i.into_inner()) {
-        Ok(InnerData::Seq(s)) => {
-            for arg in s.iter() {
-                sh_args.push(arg.to_string())
-            }
-        }
+    let mut cmd_args = vec![];
+    let cwd = match command_args(args, &mut cmd_args) {
+        Ok(cwd) => cwd,
         Err(e) => return e.into(),
-        Ok(other) => sh_args.push(other.to_string()),
+    };
+    run(cmd_args, cwd)
+}
+
+/// Collect the positional arguments of a command into `target`, returning the optional working
+/// directory passed with `cwd`
+pub(super) fn command_args(args: Data, target: &mut Vec<String>) -> Result<Option<String>> {
+    let mut args = args.into_arguments(usize::MAX)?;
+    let cwd = match args.take_named("cwd") {
+        Some(cwd) => Some(Data::new(cwd).render()?),
+        None => None,
+    };
+    for arg in args.positional().iter() {
+        match arg {
+            InnerData::Seq(s) => target.extend(s.iter().map(|a| a.to_string())),
+            other => target.push(other.to_string()),
+        }
+    }
+    args.finish(usize::MAX)?;
+    Ok(cwd)
+}
+
+fn run(args: Vec<String>, cwd: Option<String>) -> Data {
+    let mut command = Command::new("/usr/bin/env");
+    command.args(args);
+    if let Some(cwd) = cwd {
+        command.current_dir(cwd);
     }
-    let result = Command::new("/usr/bin/env").args(sh_args).output();
-    match result {
+    match command.output() {
         Ok(result) => {
             let mut map = BTreeMap::<InnerData, InnerData>::new();
             map.insert(
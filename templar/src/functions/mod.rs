@@ -17,26 +17,43 @@ The above will creat a map with two fields "filename" and "content" with content
 contents the file `settings.json`. Then we pass this map to the filter `yml` to then render
 that map into a serialized YML string.
 
+Arguments can be passed by position or by name, e.g. `command('ls', cwd='/tmp')`. Custom functions
+can use `Data::into_arguments` to accept both forms.
+
+Calls to `json` and `yaml` with literal arguments are evaluated once when the template is parsed. Functions that read
+files, the environment or run commands are called on every render. Custom functions are treated the same way unless
+they are added with `TemplarBuilder::add_pure_function`.
+
+Which commands, files and environment variables `script`, `command`, `file` and `env` may use is set with
+`TemplarBuilder::set_sandbox_policy`, see `SandboxPolicy`. By default they may use any.
+
 # Built in functions
 
 - file(str): Open file and read contents to a string
 - env(str): Read the named environment variable
-- script(str): Execute the string as a shell script. Returns a map with keys "stdout", "stderr", "status"
-- command(str, str[]?): Execute the supplied command with the supplied arguments. Returns a map with keys "stdout", "stderr", "status"
+- script(str, cwd=str?): Execute the string as a shell script. Returns a map with keys "stdout", "stderr", "status"
+- command(str, str[]?, cwd=str?): Execute the supplied command with the supplied arguments. Returns a map with keys "stdout", "stderr", "status"
+- volatile(value?): Returns the value unchanged. A dynamic context value that calls it is executed on every read, even in a memoising context
 - json(str): Parse the supplied JSON string into a map. Requires "json-extension" feature (default on)
 - yaml(str): (alias yml) Parse the supplied YML string into a map. Requires "yaml-extension" feature (default on)
 */
 
 mod common;
+mod sandbox;
+
+pub use sandbox::SandboxPolicy;
+pub(crate) use sandbox::{sandboxed_functions, SANDBOXED_FUNCTIONS};
 
 use crate::*;
-use std::collections::HashMap;
+use std::collections::{HashMap, HashSet};
 
 /// This is the definition used when adding functions to Templar
 pub type Function = dyn Fn(Data) -> Data + Send + Sync;
 
 macro_rules! builtin_functions {
-    ($( $( #[ $attr:meta ] )* $name:literal : $method:path ; )*) => {
+    (@pure pure) => { true };
+    (@pure) => { false };
+    ($( $( #[ $attr:meta ] )* $( $pure:ident )? $name:literal : $method:path ; )*) => {
         pub(crate) fn default_functions() -> HashMap<String, Arc<Function>> {
             let mut res = HashMap::new();
             $(
@@ -45,6 +62,18 @@ macro_rules! builtin_functions {
             )*
             res
         }
+
+        /// The built in functions that are marked `pure`, which only depend on their arguments
+        pub(crate) fn default_pure_functions() -> HashSet<String> {
+            let mut res = HashSet::new();
+            $(
+                $( #[ $attr ] )*
+                if builtin_functions!(@pure $( $pure )?) {
+                    res.insert($name.into());
+                }
+            )*
+            res
+        }
     };
 }
 
@@ -53,11 +82,12 @@ builtin_functions! {
     "env": common::env;
     "script": common::script;
     "command": common::command;
+    "volatile": common::volatile;
 
     #[cfg(feature = "json-extension")]
-    "json": common::json;
+    pure "json": common::json;
     #[cfg(feature = "yaml-extension")]
-    "yaml": common::yaml;
+    pure "yaml": common::yaml;
     #[cfg(feature = "yaml-extension")]
-    "yml": common::yaml;
+    pure "yml": common::yaml;
 }
@@ -0,0 +1,239 @@
+use super::*;
+use std::collections::BTreeSet;
+use std::path::{Path, PathBuf};
+
+/// Which side effects the built in `file`, `env`, `script` and `command` functions may have, set
+/// with `TemplarBuilder::set_sandbox_policy`. Calls the policy does not allow fail with
+/// `TemplarError::PermissionDenied`.
+///
+/// - `SandboxPolicy::trusted()` allows everything and is the default
+/// - `SandboxPolicy::restricted()` allows nothing until commands, directories and environment
+///   variables are added with `allow_command`, `allow_dir` and `allow_env`
+/// - `SandboxPolicy::pure()` allows nothing at all, whatever is added
+///
+/// Commands are allowed by the exact name they are called with, e.g. allowing `git` does not
+/// allow `/usr/bin/git`. `script()` runs its script with `sh`, so scripts are only allowed if `sh`
+/// is. Files are allowed if they are inside one of the allowed directories once any symlinks and
+/// `..` have been resolved.
+///
+/// # Usage
+///
+/// ```
+/// # use templar::*;
+/// let mut policy = SandboxPolicy::restricted();
+/// policy.allow_command("echo").allow_env("HOME");
+/// let mut builder = TemplarBuilder::default();
+/// builder.set_sandbox_policy(policy);
+/// let templar = builder.build();
+/// # let context = StandardContext::new();
+///
+/// let allowed = templar.parse("{{ command('echo', '-n', 'hi') | key('stdout') }}")?;
+/// assert_eq!(allowed.render(&context)?, "hi");
+///
+/// let denied = templar.parse("{{ command('rm', ['-rf', '/']) }}")?;
+/// assert!(matches!(denied.render(&context), Err(TemplarError::PermissionDenied(_))));
+/// # Ok::<(), templar::TemplarError>(())
+/// ```
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SandboxPolicy {
+    preset: Preset,
+    commands: BTreeSet<String>,
+    dirs: Vec<PathBuf>,
+    env_vars: BTreeSet<String>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Preset {
+    Trusted,
+    Restricted,
+    Pure,
+}
+
+impl Default for SandboxPolicy {
+    fn default() -> Self {
+        Self::trusted()
+    }
+}
+
+impl SandboxPolicy {
+    fn new(preset: Preset) -> Self {
+        SandboxPolicy {
+            preset,
+            commands: BTreeSet::new(),
+            dirs: vec![],
+            env_vars: BTreeSet::new(),
+        }
+    }
+
+    /// Allow every command, file and environment variable, for templates that are trusted
+    pub fn trusted() -> Self {
+        Self::new(Preset::Trusted)
+    }
+
+    /// Only allow the commands, directories and environment variables that are added
+    pub fn restricted() -> Self {
+        Self::new(Preset::Restricted)
+    }
+
+    /// Allow no commands, files or environment variables, so that rendering has no side effects
+    /// and only depends on the context
+    pub fn pure() -> Self {
+        Self::new(Preset::Pure)
+    }
+
+    /// Allow `command()` to run the named command, and `script()` if the name is `sh`
+    pub fn allow_command(&mut self, name: &str) -> &mut Self {
+        self.commands.insert(name.into());
+        self
+    }
+
+    /// Allow `file()` to read files within a directory
+    pub fn allow_dir<P: Into<PathBuf>>(&mut self, dir: P) -> &mut Self {
+        self.dirs.push(dir.into());
+        self
+    }
+
+    /// Allow `env()` to read the named environment variable
+    pub fn allow_env(&mut self, name: &str) -> &mut Self {
+        self.env_vars.insert(name.into());
+        self
+    }
+
+    fn allows(&self, allowed: bool) -> bool {
+        match self.preset {
+            Preset::Trusted => true,
+            Preset::Restricted => allowed,
+            Preset::Pure => false,
+        }
+    }
+
+    fn check_command(&self, name: &str) -> Result<()> {
+        if self.allows(self.commands.contains(name)) {
+            return Ok(());
+        }
+        Err(TemplarError::PermissionDenied(format!(
+            "Command '{}' is not allowed by the sandbox policy",
+            name
+        )))
+    }
+
+    fn check_script(&self) -> Result<()> {
+        if self.allows(self.commands.contains("sh")) {
+            return Ok(());
+        }
+        Err(TemplarError::PermissionDenied(
+            "Scripts are not allowed by the sandbox policy".into(),
+        ))
+    }
+
+    fn check_env(&self, name: &str) -> Result<()> {
+        if self.allows(self.env_vars.contains(name)) {
+            return Ok(());
+        }
+        Err(TemplarError::PermissionDenied(format!(
+            "Environment variable '{}' is not allowed by the sandbox policy",
+            name
+        )))
+    }
+
+    /// The path of the file to read in place of `path`, with any symlinks resolved so that the
+    /// file that was checked is the one that is read
+    fn check_file(&self, path: &str) -> Result<PathBuf> {
+        if self.preset == Preset::Trusted {
+            return Ok(path.into());
+        }
+        // A file that does not exist is reported the same as one outside of the directories, so
+        // that templates can't find out which files exist elsewhere
+        let resolved = Path::new(path).canonicalize().ok().filter(|file| {
+            self.dirs
+                .iter()
+                .filter_map(|dir| dir.canonicalize().ok())
+                .any(|dir| file.starts_with(dir))
+        });
+        match resolved {
+            Some(file) if self.allows(true) => Ok(file),
+            _ => Err(TemplarError::PermissionDenied(format!(
+                "File '{}' is not within the directories allowed by the sandbox policy",
+                path
+            ))),
+        }
+    }
+}
+
+/// The built in functions with side effects, checking every call against `policy` first
+/// The names of the built in functions that a sandbox policy applies to
+pub(crate) const SANDBOXED_FUNCTIONS: [&str; 4] = ["file", "env", "script", "command"];
+
+pub(crate) fn sandboxed_functions(policy: &SandboxPolicy) -> Vec<(&'static str, Arc<Function>)> {
+    if policy.preset == Preset::Trusted {
+        return vec![
+            ("file", Arc::new(common::file)),
+            ("env", Arc::new(common::env)),
+            ("script", Arc::new(common::script)),
+            ("command", Arc::new(common::command)),
+        ];
+    }
+    let policy = Arc::new(policy.clone());
+    vec![
+        ("file", guard(&policy, check_file, common::file)),
+        ("env", guard(&policy, check_env, common::env)),
+        (
+            "script",
+            guard(
+                &policy,
+                |p, args| p.check_script().map(|_| args),
+                common::script,
+            ),
+        ),
+        ("command", guard(&policy, check_command, common::command)),
+    ]
+}
+
+/// Wrap `function` so that its arguments are first checked by `check`, which may also replace
+/// them with what the function should be called with
+fn guard(
+    policy: &Arc<SandboxPolicy>,
+    check: fn(&SandboxPolicy, Data) -> Result<Data>,
+    function: fn(Data) -> Data,
+) -> Arc<Function> {
+    let policy = policy.clone();
+    Arc::new(move |args| match check(&policy, args) {
+        Ok(args) => function(args),
+        Err(e) => e.into(),
+    })
+}
+
+fn check_file(policy: &SandboxPolicy, args: Data) -> Result<Data> {
+    let path = single_arg(args, "path")?;
+    let file = policy.check_file(&path)?;
+    Ok(Data::new(file.to_string_lossy().into_owned()))
+}
+
+fn check_env(policy: &SandboxPolicy, args: Data) -> Result<Data> {
+    policy.check_env(&single_arg(args.clone(), "env_var")?)?;
+    Ok(args)
+}
+
+fn check_command(policy: &SandboxPolicy, args: Data) -> Result<Data> {
+    let mut command = vec![];
+    common::command_args(args.clone(), &mut command)?;
+    match command.first() {
+        Some(name) => policy.check_command(name)?,
+        None => {
+            return Err(TemplarError::RenderFailure(
+                "Missing value for argument 'command'".into(),
+            ))
+        }
+    }
+    Ok(args)
+}
+
+/// The value of a function's only parameter, passed by position or by name, as a string
+fn single_arg(args: Data, name: &str) -> Result<String> {
+    let mut args = args.into_arguments(1)?;
+    let value = args.take(0, name)?.ok_or_else(|| {
+        TemplarError::RenderFailure(format!("Missing value for argument '{}'", name))
+    })?;
+    args.finish(1)?;
+    Data::new(value).render()
+}
@@ -57,10 +57,22 @@ of the currently supported features.
       bracket syntax. e.g. `{{ .['565'] }}`. This also allows array access and identifier of non-standard types (such as boolean).
     * Inline arrays: `{{ [1,2,3,4] }}` and complex nesting also possible e.g. `{{ [1,2, script("echo 'hello world!'"), (5 + 5 | base64)] }}`
     * Inline maps: `{{ {'key': 'value', 'otherKey': { 'nested': 'map' } } }}`
+    * Undefined values render as an empty string by default. `TemplarBuilder::set_undefined_policy` can instead make them
+      an error naming the missing path, or render a visible `{{ undefined: a.b.c }}` marker.
 * Control flow can be done using the `{% %}` syntax
     * If/else if: `{% if 10/2 == 5 %}The world is sane!{% else if false %}What universe are we in?{% end if %}`
     * Scoping can be done manually: `{% scope %}I'm in a scope!{% end scope %}`
     * For loops: `{% for thing in lots.of.stuff %} {{ thing['name'] }} {% end for %}`. For loops always enter a new scope.
+    * Filter blocks: `{% filter indent(4) | upper %}{{ file('section.yml') }}{% end filter %}` renders the body and passes it through the filter chain.
+    * Macros: `{% macro field(name, value='unset') %}{{ name }}: {{ value }}{% end macro %}` defines a reusable fragment that can be called
+      like a function, e.g. `{{ field('port', 8080) }}`. Macros run in their own scope and must be defined before they are used.
+      A body can be passed to a macro with `{% call wrap('b') %}text{% end call %}` and rendered inside the macro with `caller()`. The body sees the variables of the template that calls the macro, not those of the macro.
+    * Includes: `{% include 'partials/header.tmpl' %}` renders another template from the configured loaders using the current context.
+      Add `ignore missing` to skip templates that do not exist, `with { 'key': 'value' }` to pass extra variables, or `only` to
+      hide the current context. Names are resolved relative to the including template first and included at parse time.
+    * Inheritance: `{% extends 'base.tmpl' %}` renders a parent template, replacing any `{% block name %}...{% end block %}`
+      sections it defines with the blocks of the same name from the child. `{{ super() }}` renders the parent's version of the
+      current block. The extends tag must come first and any content outside of blocks in the child is ignored.
 * Comments use the `{# #}` syntax and will be remitted from the output.
 * Whitespace control can be accomplished by adding a `-` to any of the above blocks e.g. `{{- 'no whitespace! -}}`.
     * Whitespace control can be added to one or both sides of the tags. All spaces, new lines, or other whitespace on the side with the `-`
@@ -74,11 +86,15 @@ Everything inside the standard `{{ }}` block is an expression. Each block holds
 many individual operations. A quick overview:
 
 * Math operations: `+ - * / %` these operations are only valid with numeric types
-* Equality: `== != < <= > >= && ||`
+* Equality: `== != < <= > >=`
+* Logic: `&& || !` e.g. `{{ user.nickname || user.name }}`, the right side of `&&` and `||` is only evaluated when needed
 * Value setting: `=` the left side of this operation must be some identifier e.g. `{{ some.val.path = 'hello world!' }}`
 * String concatenation: `~` e.g. `{{ 'Hello' ~ ' ' ~ 'world!' }}` prints "Hello world!"
 * Functions: `ident()` e.g. `{{ env('USER') }}` would retrieve the value of the environment variable "USER".
 * Filters: `|` e.g. `{{ 'hello world' | upper }}` would use the 'upper' filter to print "HELLO WORLD"
+* Tests: `is` e.g. `{{ user.name is defined }}` or `{{ 5 is not even }}` would use the 'defined' and 'even' tests to produce a boolean
+* Named arguments: arguments to filters, functions and tests can be passed by name after any positional arguments e.g. `{{ items | join(sep=', ') }}`
+* Lambdas: `=>` e.g. `{{ users | map(u => u.name) }}` creates a function that filters such as 'map', 'select' and 'sort_by' can call
 
 As documentation is still in progress, see the [expression tests](./src/test/expressions.rs) for examples of expression usage.
 
@@ -114,8 +130,10 @@ pub use {
     self::{
         context::{Context, StandardContext},
         error::TemplarError,
-        execution::{Data, InnerData},
-        templar::{Templar, TemplarBuilder, Template, TemplateTree},
+        execution::{Arguments, CompiledTemplate, Data, InnerData, Lambda, RenderLimits},
+        functions::SandboxPolicy,
+        loader::{EmbeddedLoader, FileSystemLoader, MemoryLoader, TemplateLoader},
+        templar::{Templar, TemplarBuilder, Template, TemplateTree, UndefinedPolicy},
     },
     unstructured::Document,
 };
@@ -123,7 +141,9 @@ pub use {
 // #[cfg(feature = "shared-context")]
 // pub use context::SharedContext;
 
+pub mod ast;
 pub mod error;
+pub mod loader;
 
 #[cfg(test)]
 mod test;
@@ -136,3 +156,4 @@ mod templar;
 // We export these for documentation purposes, but they have no directly usable code
 pub mod filters;
 pub mod functions;
+pub mod tests;
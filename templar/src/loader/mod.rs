@@ -0,0 +1,177 @@
+/*!
+Templar template loaders
+
+Loaders are used to look up the source of a template by name. A `Templar` instance can be configured with
+any number of loaders using `TemplarBuilder::add_loader`, after which templates can be retrieved with
+`Templar::get_template`. Loaded templates are parsed once and then cached by name.
+
+# Overview
+
+```
+# use templar::*;
+let mut loader = MemoryLoader::new();
+loader.add("greeting.tmpl", "Hello {{ name }}!");
+
+let mut builder = TemplarBuilder::default();
+builder.add_loader(loader);
+let templar = builder.build();
+
+let context = StandardContext::new();
+context.set_path(&[&"name".into()], "world")?;
+
+let template = templar.get_template("greeting.tmpl")?;
+assert_eq!(template.render(&context)?, "Hello world!");
+# Ok::<(), templar::TemplarError>(())
+```
+
+# Built in loaders
+
+- FileSystemLoader: Loads templates from files under a base directory
+- MemoryLoader: Loads templates from an in-memory map of names to sources
+- EmbeddedLoader: Loads templates compiled into the binary, e.g. with `include_str!`
+*/
+
+use crate::*;
+use std::fs::File;
+use std::io::{ErrorKind, Read};
+use std::path::{Component, Path, PathBuf};
+
+/// A source of named templates
+pub trait TemplateLoader: Send + Sync {
+    /// Load the source of the template with the specified name.
+    ///
+    /// Loaders should return `TemplarError::TemplateNotFound` if they do not have a template by this name
+    /// so that the next loader can be tried.
+    fn load(&self, name: &str) -> Result<String>;
+}
+
+/// Loads templates from files relative to a base directory. Names that are absolute or contain `..`
+/// are not found, so that templates cannot be loaded from outside of the base directory.
+#[derive(Debug, Clone)]
+pub struct FileSystemLoader {
+    base: PathBuf,
+}
+
+impl FileSystemLoader {
+    /// Create a loader for templates under the specified directory
+    pub fn new<T: Into<PathBuf>>(base: T) -> Self {
+        FileSystemLoader { base: base.into() }
+    }
+}
+
+impl TemplateLoader for FileSystemLoader {
+    fn load(&self, name: &str) -> Result<String> {
+        let within_base = Path::new(name)
+            .components()
+            .all(|c| matches!(c, Component::Normal(_) | Component::CurDir));
+        if !within_base {
+            return Err(TemplarError::TemplateNotFound(name.into()));
+        }
+        let mut file = File::open(self.base.join(name)).map_err(|e| match e.kind() {
+            ErrorKind::NotFound => TemplarError::TemplateNotFound(name.into()),
+            _ => e.into(),
+        })?;
+        let mut result = String::new();
+        file.read_to_string(&mut result)?;
+        Ok(result)
+    }
+}
+
+/// Loads templates from an in-memory map of names to template sources
+#[derive(Debug, Clone, Default)]
+pub struct MemoryLoader {
+    templates: HashMap<String, String>,
+}
+
+impl MemoryLoader {
+    /// Create a new empty loader
+    pub fn new() -> Self {
+        Default::default()
+    }
+
+    /// Add a template source with the specified name
+    pub fn add<T: Into<String>, U: Into<String>>(&mut self, name: T, source: U) -> &mut Self {
+        self.templates.insert(name.into(), source.into());
+        self
+    }
+
+    /// Remove the template with the specified name
+    pub fn remove(&mut self, name: &str) -> &mut Self {
+        self.templates.remove(name);
+        self
+    }
+}
+
+impl<T: Into<String>, U: Into<String>> std::iter::FromIterator<(T, U)> for MemoryLoader {
+    fn from_iter<I: IntoIterator<Item = (T, U)>>(iter: I) -> Self {
+        MemoryLoader {
+            templates: iter
+                .into_iter()
+                .map(|(k, v)| (k.into(), v.into()))
+                .collect(),
+        }
+    }
+}
+
+impl TemplateLoader for MemoryLoader {
+    fn load(&self, name: &str) -> Result<String> {
+        self.templates
+            .get(name)
+            .cloned()
+            .ok_or_else(|| TemplarError::TemplateNotFound(name.into()))
+    }
+}
+
+/// Loads templates that are compiled into the binary
+///
+/// # Usage
+///
+/// ```
+/// # use templar::*;
+/// static TEMPLATES: &[(&str, &str)] = &[
+///     ("header.tmpl", "# {{ title }}"), // e.g. include_str!("templates/header.tmpl")
+/// ];
+///
+/// let mut builder = TemplarBuilder::default();
+/// builder.add_loader(EmbeddedLoader::new(TEMPLATES));
+/// # Ok::<(), templar::TemplarError>(())
+/// ```
+#[derive(Debug, Clone, Copy)]
+pub struct EmbeddedLoader {
+    templates: &'static [(&'static str, &'static str)],
+}
+
+impl EmbeddedLoader {
+    /// Create a loader from a static list of name and source pairs
+    pub const fn new(templates: &'static [(&'static str, &'static str)]) -> Self {
+        EmbeddedLoader { templates }
+    }
+}
+
+impl TemplateLoader for EmbeddedLoader {
+    fn load(&self, name: &str) -> Result<String> {
+        self.templates
+            .iter()
+            .find(|(n, _)| *n == name)
+            .map(|(_, source)| source.to_string())
+            .ok_or_else(|| TemplarError::TemplateNotFound(name.into()))
+    }
+}
+
+/// Resolve `name` relative to the directory of the template named `parent`, normalizing any `.` and
+/// `..` segments. Returns `None` if the result would escape the root of the loader.
+pub(crate) fn resolve_relative(parent: &str, name: &str) -> Option<String> {
+    let mut segments: Vec<&str> = parent.split('/').collect();
+    segments.pop();
+    for segment in name.split('/') {
+        match segment {
+            "" | "." => {}
+            ".." => {
+                segments.pop()?;
+            }
+            s => segments.push(s),
+        }
+    }
+    segments.retain(|s| !s.is_empty());
+    Some(segments.join("/"))
+}
@@ -0,0 +1,536 @@
+use super::*;
+use crate::ast::{
+    ArrayExpr, Ast, BinaryExpr, BinaryOp, CallArgs, CallBlock, Comment, ElseBranch, Expression,
+    ExtendsBlock, FilterBlock, FilterCall, FilterExpr, ForBlock, FunctionCall, GroupExpr, Ident,
+    IfBlock, IfBranch, IncludeBlock, LambdaExpr, Literal, MacroBlock, MapExpr, NamedArg,
+    NamedBlock, NotExpr, Output, Path, ScopeBlock, Span, Tag, TemplateNode, TestExpr, Text,
+};
+use pest::iterators::Pairs;
+
+fn span_of(pair: &Pair<'_, Rule>) -> Span {
+    let span = pair.as_span();
+    Span::new(span.start(), span.end())
+}
+
+/// The tag covering `start..end`, which must be within the source of `pair`
+fn tag_in(pair: &Pair<'_, Rule>, start: usize, end: usize) -> Tag {
+    let offset = pair.as_span().start();
+    let text = pair.as_str()[start - offset..end - offset].trim();
+    let marker = text.len().saturating_sub(3);
+    Tag {
+        span: Span::new(start, end),
+        trim_left: text.get(2..3) == Some("-"),
+        trim_right: text.len() > 4 && text.get(marker..marker + 1) == Some("-"),
+    }
+}
+
+fn tag(pair: &Pair<'_, Rule>) -> Tag {
+    let span = span_of(pair);
+    tag_in(pair, span.start, span.end)
+}
+
+/// The opening and closing tags of a block, on either side of its body
+fn block_tags(pair: &Pair<'_, Rule>) -> (Tag, Tag) {
+    let span = span_of(pair);
+    let children = pair.clone().into_inner();
+    let body = match children
+        .clone()
+        .find(|p| p.as_rule() == Rule::template_inner)
+    {
+        Some(inner) => span_of(&inner),
+        // Scope blocks hold their templates directly
+        None => children
+            .map(|p| span_of(&p))
+            .fold(None, |acc: Option<Span>, s| {
+                Some(acc.map_or(s, |a| a.join(s)))
+            })
+            .unwrap_or(Span::new(span.end, span.end)),
+    };
+    (
+        tag_in(pair, span.start, body.start),
+        tag_in(pair, body.end, span.end),
+    )
+}
+
+fn unexpected<T>(pair: &Pair<'_, Rule>) -> Result<T> {
+    Err(TemplarError::ParseFailure(format!(
+        "Unexpected rule while building syntax tree: {:?}",
+        pair.as_rule()
+    )))
+}
+
+/// Build the syntax tree of a template from its source
+pub(crate) fn build(source: &str) -> Result<Ast> {
+    let pairs = TemplarParser::parse(Rule::template_root, source)
+        .map_err(|e| TemplarError::ParseFailure(format!("{}", e)))?;
+    Ok(Ast {
+        source: source.into(),
+        nodes: body(pairs)?,
+    })
+}
+
+pub(super) fn body(pairs: Pairs<'_, Rule>) -> Result<Vec<TemplateNode>> {
+    let mut nodes = vec![];
+    for pair in pairs {
+        if pair.as_rule() != Rule::EOI {
+            nodes.push(template_node(pair)?);
+        }
+    }
+    Ok(nodes)
+}
+
+fn template_node(pair: Pair<'_, Rule>) -> Result<TemplateNode> {
+    let span = span_of(&pair);
+    Ok(match pair.as_rule() {
+        Rule::content => TemplateNode::Text(Text {
+            span,
+            text: pair.as_str().into(),
+        }),
+        Rule::comment_block => TemplateNode::Comment(Comment {
+            span,
+            text: pair.as_str().into(),
+        }),
+        Rule::template_block => TemplateNode::Output(Output {
+            span,
+            tag: tag(&pair),
+            expr: expression(pair.into_inner().next().unwrap())?,
+        }),
+        Rule::ctrl_block_if => TemplateNode::If(if_block(pair)?),
+        Rule::ctrl_block_scope => {
+            let (open, close) = block_tags(&pair);
+            TemplateNode::Scope(ScopeBlock {
+                span,
+                open,
+                close,
+                body: body(pair.into_inner())?,
+            })
+        }
+        Rule::ctrl_block_loop => {
+            let (open, close) = block_tags(&pair);
+            let mut inner = pair.into_inner();
+            TemplateNode::For(ForBlock {
+                span,
+                open,
+                close,
+                binding: path(inner.next().unwrap()),
+                iterable: expression(inner.next().unwrap())?,
+                body: body(inner.next().unwrap().into_inner())?,
+            })
+        }
+        Rule::ctrl_block_filter => {
+            let (open, close) = block_tags(&pair);
+            let mut filters = vec![];
+            let mut nodes = vec![];
+            for inner in pair.into_inner() {
+                match inner.as_rule() {
+                    Rule::filter_head | Rule::filter => filters.push(filter_call(inner)?),
+                    Rule::template_inner => nodes = body(inner.into_inner())?,
+                    _ => return unexpected(&inner),
+                }
+            }
+            TemplateNode::Filter(FilterBlock {
+                span,
+                open,
+                close,
+                filters,
+                body: nodes,
+            })
+        }
+        Rule::ctrl_block_macro => {
+            let (open, close) = block_tags(&pair);
+            let mut inner = pair.into_inner();
+            let name = ident(inner.next().unwrap());
+            let mut params = vec![];
+            let mut nodes = vec![];
+            for inner in inner {
+                match inner.as_rule() {
+                    Rule::macro_param => {
+                        let span = span_of(&inner);
+                        let mut parts = inner.into_inner();
+                        params.push(crate::ast::MacroParam {
+                            span,
+                            name: ident(parts.next().unwrap()),
+                            default: parts.next().map(expression).transpose()?,
+                        });
+                    }
+                    Rule::template_inner => nodes = body(inner.into_inner())?,
+                    _ => return unexpected(&inner),
+                }
+            }
+            TemplateNode::Macro(MacroBlock {
+                span,
+                open,
+                close,
+                name,
+                params,
+                body: nodes,
+            })
+        }
+        Rule::ctrl_block_call => {
+            let (open, close) = block_tags(&pair);
+            let mut inner = pair.into_inner();
+            TemplateNode::Call(CallBlock {
+                span,
+                open,
+                close,
+                call: function_call(inner.next().unwrap())?,
+                body: body(inner.next().unwrap().into_inner())?,
+            })
+        }
+        Rule::ctrl_block_include => {
+            let tag = tag(&pair);
+            let mut name = None;
+            let mut ignore_missing = false;
+            let mut vars = None;
+            let mut only = false;
+            for inner in pair.into_inner() {
+                match inner.as_rule() {
+                    Rule::include_name => name = Some(template_name(inner)),
+                    Rule::include_ignore_missing => ignore_missing = true,
+                    Rule::include_with_context => {}
+                    Rule::include_with_vars => {
+                        vars = Some(expression(inner.into_inner().next().unwrap())?)
+                    }
+                    Rule::include_only => only = true,
+                    _ => return unexpected(&inner),
+                }
+            }
+            TemplateNode::Include(IncludeBlock {
+                span,
+                tag,
+                name: name.unwrap(),
+                ignore_missing,
+                vars,
+                only,
+            })
+        }
+        Rule::ctrl_block_block => {
+            let (open, close) = block_tags(&pair);
+            let mut inner = pair.into_inner();
+            TemplateNode::Block(NamedBlock {
+                span,
+                open,
+                close,
+                name: ident(inner.next().unwrap()),
+                body: body(inner.next().unwrap().into_inner())?,
+            })
+        }
+        Rule::ctrl_block_extends => TemplateNode::Extends(ExtendsBlock {
+            span,
+            tag: tag(&pair),
+            name: template_name(pair.into_inner().next().unwrap()),
+        }),
+        _ => return unexpected(&pair),
+    })
+}
+
+/// An if block and its else branches are nested in the grammar, but are flattened into branches here
+fn if_block(pair: Pair<'_, Rule>) -> Result<IfBlock> {
+    let span = span_of(&pair);
+    let mut branches = vec![];
+    let mut otherwise = None;
+    let mut end_tag = Tag::default();
+    let mut next = Some(pair);
+    while let Some(pair) = next.take() {
+        let branch_span = span_of_branch(&pair);
+        let branch_tag = block_tags(&pair).0;
+        let mut condition = None;
+        for inner in pair.into_inner() {
+            match inner.as_rule() {
+                Rule::kw_if => {}
+                Rule::expression_cap => condition = Some(expression(inner)?),
+                Rule::template_inner => {
+                    let nodes = body(inner.into_inner())?;
+                    match condition.take() {
+                        Some(condition) => branches.push(IfBranch {
+                            span: branch_span,
+                            tag: branch_tag,
+                            condition,
+                            body: nodes,
+                        }),
+                        None => {
+                            otherwise = Some(ElseBranch {
+                                span: branch_span,
+                                tag: branch_tag,
+                                body: nodes,
+                            })
+                        }
+                    }
+                }
+                Rule::ctrl_block_else => next = Some(inner),
+                Rule::ctrl_block_end_if => end_tag = tag(&inner),
+                _ => return unexpected(&inner),
+            }
+        }
+    }
+    Ok(IfBlock {
+        span,
+        branches,
+        otherwise,
+        end_tag,
+    })
+}
+
+/// The span of a single branch of an if block, up to the start of the next branch or end tag
+fn span_of_branch(pair: &Pair<'_, Rule>) -> Span {
+    let start = pair.as_span().start();
+    let end = pair
+        .clone()
+        .into_inner()
+        .find(|p| matches!(p.as_rule(), Rule::ctrl_block_else | Rule::ctrl_block_end_if))
+        .map(|p| p.as_span().start())
+        .unwrap_or_else(|| pair.as_span().end());
+    Span::new(start, end)
+}
+
+fn ident(pair: Pair<'_, Rule>) -> Ident {
+    Ident {
+        span: span_of(&pair),
+        name: pair.as_str().into(),
+    }
+}
+
+fn template_name(pair: Pair<'_, Rule>) -> Literal {
+    literal(pair.into_inner().next().unwrap()).unwrap()
+}
+
+fn path(pair: Pair<'_, Rule>) -> Path {
+    let span = span_of(&pair);
+    let mut segments: Vec<InnerData> = vec![];
+    for inner in pair.into_inner() {
+        match inner.as_rule() {
+            Rule::ident => segments.push(inner.as_str().into()),
+            Rule::value_key => {
+                segments.push(string_value(inner.into_inner().next().unwrap()).into())
+            }
+            _ => {}
+        }
+    }
+    Path { span, segments }
+}
+
+/// The value of a string literal, without the quotes
+fn string_value(pair: Pair<'_, Rule>) -> String {
+    let quoted = pair.as_str();
+    let inner = &quoted[1..quoted.len() - 1];
+    match &quoted[..1] {
+        "'" => inner.replace("\\'", "'"),
+        "\"" => inner.replace("\\\"", "\""),
+        _ => inner.replace("\\`", "`"),
+    }
+}
+
+fn literal(pair: Pair<'_, Rule>) -> Result<Literal> {
+    let span = span_of(&pair);
+    let value = match pair.as_rule() {
+        Rule::true_lit => true.into(),
+        Rule::false_lit => false.into(),
+        Rule::null_lit => InnerData::Null,
+        Rule::string_lit => string_value(pair).into(),
+        Rule::number_lit => pair
+            .as_str()
+            .parse::<i64>()
+            .map_err(|e| TemplarError::ParseFailure(format!("{}", e)))?
+            .into(),
+        _ => return unexpected(&pair),
+    };
+    Ok(Literal { span, value })
+}
+
+fn call_args(pair: Pair<'_, Rule>) -> Result<CallArgs> {
+    let mut args = CallArgs {
+        span: span_of(&pair),
+        ..Default::default()
+    };
+    for inner in pair.into_inner() {
+        match inner.as_rule() {
+            Rule::expression_cap => args.positional.push(expression(inner)?),
+            Rule::named_arg => {
+                let span = span_of(&inner);
+                let mut parts = inner.into_inner();
+                args.named.push(NamedArg {
+                    span,
+                    name: ident(parts.next().unwrap()),
+                    value: expression(parts.next().unwrap())?,
+                });
+            }
+            _ => return unexpected(&inner),
+        }
+    }
+    Ok(args)
+}
+
+fn function_call(pair: Pair<'_, Rule>) -> Result<FunctionCall> {
+    let span = span_of(&pair);
+    let mut inner = pair.into_inner();
+    Ok(FunctionCall {
+        span,
+        name: ident(inner.next().unwrap()),
+        args: call_args(inner.next().unwrap())?,
+    })
+}
+
+fn filter_call(pair: Pair<'_, Rule>) -> Result<FilterCall> {
+    let span = span_of(&pair);
+    let mut inner = pair.into_inner();
+    Ok(FilterCall {
+        span,
+        name: ident(inner.next().unwrap()),
+        args: inner.next().map(call_args).transpose()?,
+    })
+}
+
+/// Build an expression. Operators and filters are applied to everything before them, which is
+/// the same order they are executed in.
+fn expression(pair: Pair<'_, Rule>) -> Result<Expression> {
+    let mut current: Option<Expression> = None;
+    let mut pending: Option<(BinaryOp, Span)> = None;
+    for inner in pair.into_inner() {
+        let op = match inner.as_rule() {
+            Rule::op_add => Some(BinaryOp::Add),
+            Rule::op_sub => Some(BinaryOp::Subtract),
+            Rule::op_mlt => Some(BinaryOp::Multiply),
+            Rule::op_div => Some(BinaryOp::Divide),
+            Rule::op_mod => Some(BinaryOp::Modulus),
+            Rule::op_and => Some(BinaryOp::And),
+            Rule::op_or => Some(BinaryOp::Or),
+            Rule::op_eq => Some(BinaryOp::Equals),
+            Rule::op_ne => Some(BinaryOp::NotEquals),
+            Rule::op_gt => Some(BinaryOp::GreaterThan),
+            Rule::op_gte => Some(BinaryOp::GreaterThanEquals),
+            Rule::op_lt => Some(BinaryOp::LessThan),
+            Rule::op_lte => Some(BinaryOp::LessThanEquals),
+            Rule::op_cat => Some(BinaryOp::Concat),
+            Rule::op_set => Some(BinaryOp::Set),
+            _ => None,
+        };
+        if let Some(op) = op {
+            if pending.is_some() || current.is_none() {
+                return Err(TemplarError::ParseFailure(format!(
+                    "Operator '{}' requires an expression on both sides",
+                    op
+                )));
+            }
+            pending = Some((op, span_of(&inner)));
+            continue;
+        }
+        match inner.as_rule() {
+            Rule::filter | Rule::test => {
+                let input = current.take().ok_or_else(|| {
+                    TemplarError::ParseFailure("Filters and tests require an input".into())
+                })?;
+                current = Some(match inner.as_rule() {
+                    Rule::filter => {
+                        let filter = filter_call(inner)?;
+                        Expression::Filter(FilterExpr {
+                            span: input.span().join(filter.span),
+                            input: Box::new(input),
+                            filter,
+                        })
+                    }
+                    _ => test(input, inner)?,
+                });
+            }
+            _ => {
+                let operand = primary(inner)?;
+                current = Some(match (current.take(), pending.take()) {
+                    (Some(left), Some((op, op_span))) => Expression::Binary(BinaryExpr {
+                        span: left.span().join(operand.span()),
+                        op,
+                        op_span,
+                        left: Box::new(left),
+                        right: Box::new(operand),
+                    }),
+                    (None, None) => operand,
+                    _ => {
+                        return Err(TemplarError::ParseFailure(
+                            "Expected an operator between expressions".into(),
+                        ))
+                    }
+                });
+            }
+        }
+    }
+    match (current, pending) {
+        (Some(expr), None) => Ok(expr),
+        (_, Some((op, _))) => Err(TemplarError::ParseFailure(format!(
+            "Operator '{}' requires an expression on both sides",
+            op
+        ))),
+        (None, None) => Err(TemplarError::ParseFailure("Empty expression".into())),
+    }
+}
+
+fn test(input: Expression, pair: Pair<'_, Rule>) -> Result<Expression> {
+    let span = input.span().join(span_of(&pair));
+    let mut negated = false;
+    let mut name = None;
+    let mut args = None;
+    for inner in pair.into_inner() {
+        match inner.as_rule() {
+            Rule::test_not => negated = true,
+            Rule::ident => name = Some(ident(inner)),
+            Rule::args => args = Some(call_args(inner)?),
+            _ => return unexpected(&inner),
+        }
+    }
+    Ok(Expression::Test(TestExpr {
+        span,
+        input: Box::new(input),
+        negated,
+        name: name.unwrap(),
+        args,
+    }))
+}
+
+fn primary(pair: Pair<'_, Rule>) -> Result<Expression> {
+    let span = span_of(&pair);
+    Ok(match pair.as_rule() {
+        Rule::value => Expression::Path(path(pair)),
+        Rule::function => Expression::Function(function_call(pair)?),
+        Rule::expression_cap => Expression::Group(GroupExpr {
+            span: Span::new(span.start - 1, span.end + 1),
+            expr: Box::new(expression(pair)?),
+        }),
+        Rule::not_expr => Expression::Not(NotExpr {
+            span,
+            expr: Box::new(primary(pair.into_inner().next().unwrap())?),
+        }),
+        Rule::lambda => {
+            let mut params = vec![];
+            let mut body = None;
+            for inner in pair.into_inner() {
+                match inner.as_rule() {
+                    Rule::ident => params.push(ident(inner)),
+                    Rule::expression_cap => body = Some(expression(inner)?),
+                    _ => return unexpected(&inner),
+                }
+            }
+            Expression::Lambda(LambdaExpr {
+                span,
+                params,
+                body: Box::new(body.unwrap()),
+            })
+        }
+        Rule::array_lit => Expression::Array(ArrayExpr {
+            span,
+            items: pair
+                .into_inner()
+                .map(expression)
+                .collect::<Result<Vec<_>>>()?,
+        }),
+        Rule::map_lit => {
+            let mut entries = vec![];
+            let mut key = None;
+            for inner in pair.into_inner() {
+                match inner.as_rule() {
+                    Rule::literal_cap => key = Some(literal(inner.into_inner().next().unwrap())?),
+                    Rule::expression_cap => entries.push((key.take().unwrap(), expression(inner)?)),
+                    _ => return unexpected(&inner),
+                }
+            }
+            Expression::Map(MapExpr { span, entries })
+        }
+        _ => Expression::Literal(literal(pair)?),
+    })
+}
@@ -0,0 +1,157 @@
+use super::*;
+use pest::iterators::Pairs;
+
+/// Block definitions from more derived templates, keyed by block name. Each list is ordered
+/// from the nearest child outwards, which is the order the overrides are applied in.
+pub(crate) type BlockOverrides<'a> = HashMap<String, Vec<BlockOverride<'a>>>;
+
+/// The body of a block from a child template along with the state it must be parsed in
+#[derive(Clone)]
+pub(crate) struct BlockOverride<'a> {
+    state: &'a ParseState<'a>,
+    body: Pair<'a, Rule>,
+}
+
+impl<'a> ParseState<'a> {
+    /// Parse the top level of a template. If the template extends another, the parent is parsed
+    /// in its place with this template's blocks substituted in, producing a single tree.
+    pub fn parse_root<'i>(&self, pairs: Pairs<'i, Rule>) -> Result<Node> {
+        let mut extends = None;
+        for pair in pairs.clone() {
+            match pair.as_rule() {
+                Rule::ctrl_block_extends => {
+                    extends = Some(pair);
+                    break;
+                }
+                Rule::content if pair.as_str().trim().is_empty() => {}
+                Rule::comment_block => {}
+                _ => break,
+            }
+        }
+        match extends {
+            Some(extends) => self.parse_extends(extends, pairs),
+            None => self.parse_match(pairs),
+        }
+    }
+
+    fn parse_extends<'i>(&self, extends: Pair<'i, Rule>, pairs: Pairs<'i, Rule>) -> Result<Node> {
+        let name = match extends.into_inner().next() {
+            Some(pair) => self
+                .parse_match(pair.into_inner())?
+                .into_document()?
+                .render()?,
+            None => return Err(TemplarError::ParseFailure("Missing template name".into())),
+        };
+
+        // Macros defined at the top level are still available to this template's blocks, but any
+        // other content outside of a block is discarded.
+        let mut blocks = vec![];
+        let mut extended = false;
+        for pair in pairs {
+            match pair.as_rule() {
+                Rule::ctrl_block_extends if extended => {
+                    return Err(TemplarError::ParseFailure(
+                        "A template can only extend one parent".into(),
+                    ))
+                }
+                Rule::ctrl_block_extends => extended = true,
+                Rule::ctrl_block_macro => {
+                    self.parse_match(Some(pair))?;
+                }
+                _ => collect_blocks(pair, &mut blocks),
+            }
+        }
+
+        let mut overrides: BlockOverrides<'_> = HashMap::new();
+        for (block_name, body) in blocks.into_iter() {
+            let defs = overrides.entry(block_name.clone()).or_default();
+            if !defs.is_empty() {
+                return Err(TemplarError::ParseFailure(format!(
+                    "Block '{}' is defined more than once",
+                    block_name
+                )));
+            }
+            defs.push(BlockOverride { state: self, body });
+        }
+        if let Some(inherited) = self.overrides {
+            for (block_name, defs) in inherited.iter() {
+                overrides
+                    .entry(block_name.clone())
+                    .or_default()
+                    .extend(defs.iter().cloned());
+            }
+        }
+
+        let (parent_name, source) = self.resolve(&name, |candidate| {
+            if self.parents.iter().any(|p| p == candidate) {
+                return Err(TemplarError::ParseFailure(format!(
+                    "Recursive template reference: {} -> {}",
+                    self.parents.join(" -> "),
+                    candidate
+                )));
+            }
+            Ok((candidate.to_string(), self.templar.load_source(candidate)?))
+        })?;
+        let mut stack = self.parents.to_vec();
+        stack.push(parent_name.clone());
+        let parent = ParseState {
+            name: Some(&parent_name),
+            parents: &stack,
+            overrides: Some(&overrides),
+            ..ParseState::new(self.templar)
+        };
+        parent.parse_root(
+            TemplarParser::parse(Rule::template_root, &source)
+                .map_err(|e| TemplarError::ParseFailure(format!("{}: {}", parent_name, e)))?,
+        )
+    }
+
+    /// Parse a block, applying the overrides from any child templates in order. Each override can
+    /// render the block it replaces with `super()`.
+    pub fn parse_block(&self, name: &str, body: Pair<'_, Rule>) -> Result<Node> {
+        let mut node = self.parse_block_body(body, None)?;
+        if let Some(defs) = self.overrides.and_then(|o| o.get(name)) {
+            for def in defs.iter() {
+                node = def.state.parse_block_body(def.body.clone(), Some(node))?;
+            }
+        }
+        Ok(node)
+    }
+
+    fn parse_block_body(&self, body: Pair<'_, Rule>, parent: Option<Node>) -> Result<Node> {
+        self.supers.borrow_mut().push(parent);
+        let result = self.parse_match(body.into_inner());
+        self.supers.borrow_mut().pop();
+        Ok(result?.set_operation(Operations::Concat))
+    }
+
+    /// The parent block that `super()` refers to in the block currently being parsed
+    pub fn super_block(&self) -> Option<Node> {
+        self.supers.borrow().last().cloned().flatten()
+    }
+}
+
+/// Find every block definition in a template, including blocks nested inside of other blocks
+fn collect_blocks<'i>(pair: Pair<'i, Rule>, blocks: &mut Vec<(String, Pair<'i, Rule>)>) {
+    if pair.as_rule() == Rule::ctrl_block_block {
+        let mut name = String::new();
+        let mut body = None;
+        for inner in pair.into_inner() {
+            match inner.as_rule() {
+                Rule::ident => name = inner.as_str().into(),
+                Rule::template_inner => body = Some(inner),
+                _ => {}
+            }
+        }
+        if let Some(body) = body {
+            for inner in body.clone().into_inner() {
+                collect_blocks(inner, blocks);
+            }
+            blocks.push((name, body));
+        }
+    } else {
+        for inner in pair.into_inner() {
+            collect_blocks(inner, blocks);
+        }
+    }
+}
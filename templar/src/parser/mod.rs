@@ -1,36 +1,252 @@
+mod ast;
+mod inheritance;
+mod recover;
 mod rules;
 mod tree;
 
 use crate::*;
+use inheritance::BlockOverrides;
+use pest::iterators::Pair;
 use pest::Parser;
 use pest_derive::*;
+use std::cell::{Cell, RefCell};
 use std::collections::BTreeMap;
-use std::mem::replace;
 use tree::ParseTree;
 
 #[derive(Parser)]
 #[grammar = "templar.pest"]
 struct TemplarParser;
 
+/// State shared by every level of a single parse, such as the macros defined so far.
+pub(crate) struct ParseState<'a> {
+    pub templar: &'a Templar,
+    name: Option<&'a str>,
+    parents: &'a [String],
+    overrides: Option<&'a BlockOverrides<'a>>,
+    macros: RefCell<HashMap<String, Arc<TemplateMacro>>>,
+    macro_depth: Cell<usize>,
+    supers: RefCell<Vec<Option<Node>>>,
+    /// True while an output block is parsed under the strict undefined policy
+    strict_values: Cell<bool>,
+}
+
+impl<'a> ParseState<'a> {
+    pub fn new(templar: &'a Templar) -> Self {
+        ParseState {
+            templar,
+            name: None,
+            parents: &[],
+            overrides: None,
+            macros: Default::default(),
+            macro_depth: Cell::new(0),
+            supers: Default::default(),
+            strict_values: Cell::new(false),
+        }
+    }
+
+    /// Create the state for parsing a named template. `parents` holds the names of every
+    /// template that is currently being parsed, including this one.
+    pub fn named(templar: &'a Templar, name: &'a str, parents: &'a [String]) -> Self {
+        ParseState {
+            name: Some(name),
+            parents,
+            ..ParseState::new(templar)
+        }
+    }
+
+    /// Load a template referred to by the template being parsed. Names are first resolved relative
+    /// to the current template and then from the root of the loaders, unless they start with `./`
+    /// or `../` in which case they are only resolved relatively.
+    pub fn load_template(&self, name: &str) -> Result<Template> {
+        self.resolve(name, |candidate| {
+            self.templar.load_template(candidate, self.parents)
+        })
+    }
+
+    fn resolve<T>(&self, name: &str, load: impl Fn(&str) -> Result<T>) -> Result<T> {
+        let relative_only = name.starts_with("./") || name.starts_with("../");
+        let mut candidates = vec![];
+        if let Some(parent) = self.name {
+            candidates.extend(loader::resolve_relative(parent, name));
+        }
+        if !relative_only {
+            candidates.extend(loader::resolve_relative("", name.trim_start_matches('/')));
+        }
+        for candidate in candidates.iter() {
+            match load(candidate) {
+                Err(TemplarError::TemplateNotFound(_)) => continue,
+                result => return result,
+            }
+        }
+        Err(TemplarError::TemplateNotFound(name.into()))
+    }
+
+    pub fn get_macro(&self, name: &str) -> Option<Arc<TemplateMacro>> {
+        self.macros.borrow().get(name).cloned()
+    }
+
+    pub fn add_macro(&self, mac: TemplateMacro) {
+        self.macros
+            .borrow_mut()
+            .insert(mac.name().to_string(), Arc::new(mac));
+    }
+
+    /// True while the body of a macro definition is being parsed, which is where `caller()` is valid
+    pub fn in_macro(&self) -> bool {
+        self.macro_depth.get() > 0
+    }
+
+    pub fn enter_macro(&self) {
+        self.macro_depth.set(self.macro_depth.get() + 1);
+    }
+
+    pub fn exit_macro(&self) {
+        self.macro_depth.set(self.macro_depth.get() - 1);
+    }
+
+    /// A lookup of a context path, which fails if the path is undefined while strict values are on
+    pub fn lookup(&self, path: ContextPath) -> Node {
+        match self.strict_values.get() {
+            true => Node::Operation(Arc::new(
+                Operations::StrictValue.build(vec![Node::Value(path)]),
+            )),
+            false => Node::Value(path),
+        }
+    }
+
+    /// Turn strict values on or off, returning whether they were on
+    pub fn set_strict_values(&self, strict: bool) -> bool {
+        self.strict_values.replace(strict)
+    }
+}
+
 impl Templar {
     /// Parse a template string into a `Template`
     #[inline]
     pub fn parse_template(&self, input: &str) -> Result<Template> {
-        let result: Node = self.parse_match(
+        let state = ParseState::new(self);
+        let result: Node = state.parse_root(
             TemplarParser::parse(Rule::template_root, input)
                 .map_err(|e| TemplarError::ParseFailure(format!("{}", e)))?,
         )?;
-        Ok(result.set_operation(Operations::Concat).into())
+        Ok(
+            Template::from(result.set_operation(Operations::Concat).fold(self))
+                .with_limits(&self.limits),
+        )
+    }
+
+    /// Parse a template that was loaded by name, `parents` are the names of any templates that
+    /// are including this one and is used to detect recursion.
+    pub(crate) fn parse_named_template(
+        &self,
+        input: &str,
+        name: &str,
+        parents: &[String],
+    ) -> Result<Template> {
+        let mut stack = parents.to_vec();
+        stack.push(name.into());
+        let state = ParseState::named(self, name, &stack);
+        let result: Node = state.parse_root(
+            TemplarParser::parse(Rule::template_root, input)
+                .map_err(|e| TemplarError::ParseFailure(format!("{}: {}", name, e)))?,
+        )?;
+        Ok(
+            Template::from(result.set_operation(Operations::Concat).fold(self))
+                .with_limits(&self.limits),
+        )
+    }
+
+    /// Parse a template string into a syntax tree, for tools that inspect templates rather than
+    /// render them. See the `ast` module for details.
+    pub fn parse_ast(&self, input: &str) -> Result<crate::ast::Ast> {
+        ast::build(input)
+    }
+
+    /// Parse a template string into a syntax tree without stopping at the first syntax error.
+    ///
+    /// Every error is returned along with a tree for the rest of the template. Tags that fail to
+    /// parse are replaced with `TemplateNode::Error` nodes, and when one of the tags of a block
+    /// is at fault the other tags of the block are replaced too, leaving its body in place.
+    /// Templates without errors give the same tree as `parse_ast`.
+    ///
+    /// # Usage
+    ///
+    /// ```
+    /// # use templar::*;
+    /// # use templar::ast::TemplateNode;
+    /// let (ast, errors) = Templar::global().parse_ast_recovering("{{ a + }} {% end for %} {{ b }}");
+    /// assert_eq!(errors.len(), 2);
+    /// assert_eq!(errors[1].message, "Unexpected `end for` tag");
+    /// assert!(matches!(ast.nodes[0], TemplateNode::Error(_)));
+    /// assert!(matches!(ast.nodes[4], TemplateNode::Output(_)));
+    /// ```
+    pub fn parse_ast_recovering(
+        &self,
+        input: &str,
+    ) -> (crate::ast::Ast, Vec<crate::ast::SyntaxError>) {
+        recover::build_recovering(input)
+    }
+
+    /// Check a template for likely mistakes such as unknown filters and functions, values that are
+    /// assigned but never read or `if` conditions that can never change. The template is not
+    /// rendered and includes are not followed. An error is only returned if the template fails to
+    /// parse.
+    ///
+    /// # Usage
+    ///
+    /// ```
+    /// # use templar::*;
+    /// # use templar::ast::LintKind;
+    /// let diagnostics = Templar::global().lint("{{ name | shout }}")?;
+    /// assert_eq!(diagnostics[0].kind, LintKind::UnknownFilter);
+    /// assert_eq!(diagnostics[0].message, "Filter 'shout' was not found");
+    /// # Ok::<(), templar::TemplarError>(())
+    /// ```
+    pub fn lint(&self, input: &str) -> Result<Vec<crate::ast::Diagnostic>> {
+        Ok(crate::ast::lint(&ast::build(input)?, self))
+    }
+
+    /// Reformat a template with normalised spacing inside tags and consistent `end` keywords,
+    /// optionally indenting nested control blocks. Text outside of tags is kept byte for byte.
+    ///
+    /// This fails rather than return a template that would render differently to the original.
+    ///
+    /// # Usage
+    ///
+    /// ```
+    /// # use templar::*;
+    /// # use templar::ast::FormatOptions;
+    /// let formatted = Templar::global()
+    ///     .format_template("{%if a%}{{a|upper}}{%end   if%}", &FormatOptions::default())?;
+    /// assert_eq!(formatted, "{% if a %}{{ a | upper }}{% end if %}");
+    /// # Ok::<(), templar::TemplarError>(())
+    /// ```
+    pub fn format_template(
+        &self,
+        input: &str,
+        options: &crate::ast::FormatOptions,
+    ) -> Result<String> {
+        let original = ast::build(input)?;
+        let formatted = crate::ast::print(&original, options);
+        let reparsed = ast::build(&formatted).map_err(|e| {
+            TemplarError::ParseFailure(format!("Formatted template failed to parse: {}", e))
+        })?;
+        if crate::ast::canonical(&original) != crate::ast::canonical(&reparsed) {
+            return Err(TemplarError::ParseFailure(
+                "Formatting would change the output of this template".into(),
+            ));
+        }
+        Ok(formatted)
     }
 
     /// Parses an expression string into a `Template`
     #[inline]
     pub fn parse_expression(&self, input: &str) -> Result<Template> {
-        Ok(self
-            .parse_match(
-                TemplarParser::parse(Rule::expression, input.trim())
-                    .map_err(|e| TemplarError::ParseFailure(format!("{}", e)))?,
-            )?
-            .into())
+        let state = ParseState::new(self);
+        let result: Node = state.parse_match(
+            TemplarParser::parse(Rule::expression, input.trim())
+                .map_err(|e| TemplarError::ParseFailure(format!("{}", e)))?,
+        )?;
+        Ok(Template::from(result.fold(self)).with_limits(&self.limits))
     }
 }
@@ -0,0 +1,420 @@
+//! An error-recovering parse that reports every syntax error in a template, rather than stopping
+//! at the first one.
+//!
+//! The template is split into tags without using the grammar, and control tags are matched into
+//! blocks. Tags that can never parse, such as an `end if` without an `if`, are replaced with
+//! comments of the same length. The template is then parsed repeatedly; each time parsing fails
+//! the tag at fault is replaced along with the rest of its block, until the template parses. The
+//! replaced tags become error nodes in the resulting tree, so every span still points at the
+//! original source.
+
+use super::*;
+use crate::ast::{Ast, ErrorNode, Span, SyntaxError, TemplateNode, Text};
+use pest::error::{ErrorVariant, InputLocation};
+
+/// Build the syntax tree of a template, replacing the tags that fail to parse with error nodes
+pub(crate) fn build_recovering(source: &str) -> (Ast, Vec<SyntaxError>) {
+    let tags = lex(source);
+    let mut recovery = Recovery {
+        source,
+        groups: (0..tags.len()).collect(),
+        blanked: vec![false; tags.len()],
+        tags,
+        errors: vec![],
+    };
+    recovery.match_blocks();
+    loop {
+        let patched = recovery.patched();
+        let error = match TemplarParser::parse(Rule::template_root, &patched) {
+            Ok(pairs) => match ast::body(pairs) {
+                Ok(nodes) => return recovery.finish(nodes),
+                Err(e) => SyntaxError {
+                    span: Span::new(0, source.len()),
+                    message: e.to_string(),
+                },
+            },
+            Err(e) => {
+                let position = match e.location {
+                    InputLocation::Pos(pos) => pos,
+                    InputLocation::Span((start, _)) => start,
+                };
+                match recovery.culprit(position) {
+                    Some(i) => {
+                        let mut message = recovery.tags[i].describe(source);
+                        if let Some(expected) = expected(&e.variant) {
+                            message = format!("{}, expected {}", message, expected);
+                        }
+                        recovery.reject(i, message);
+                        continue;
+                    }
+                    None => SyntaxError {
+                        span: Span::new(position, position),
+                        message: e.variant.message().into_owned(),
+                    },
+                }
+            }
+        };
+        // Nothing left to blame, give up on the template as a whole
+        let ast = Ast {
+            source: source.into(),
+            nodes: vec![TemplateNode::Error(ErrorNode {
+                span: Span::new(0, source.len()),
+            })],
+        };
+        recovery.errors.push(error);
+        return (ast, recovery.finish_errors());
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Kind {
+    Output,
+    Comment,
+    Control,
+}
+
+/// A tag found by scanning the source
+#[derive(Debug)]
+struct RawTag {
+    kind: Kind,
+    span: Span,
+    /// Whether the tag has an end marker, unterminated tags end at the next tag or the end of the
+    /// template
+    closed: bool,
+}
+
+impl RawTag {
+    /// The text between the markers of the tag
+    fn inner<'a>(&self, source: &'a str) -> &'a str {
+        let mut text = &self.span.slice(source)[2..];
+        if self.closed {
+            text = &text[..text.len() - 2];
+            text = text.strip_suffix('-').unwrap_or(text);
+        }
+        text.strip_prefix('-').unwrap_or(text).trim()
+    }
+
+    /// The first word of a control tag, and the word after it for `end` tags
+    fn keywords<'a>(&self, source: &'a str) -> (&'a str, &'a str) {
+        let inner = self.inner(source);
+        let word = |text: &'a str| {
+            let len = text
+                .find(|c: char| !c.is_ascii_alphanumeric() && c != '_')
+                .unwrap_or(text.len());
+            &text[..len]
+        };
+        let first = word(inner);
+        (first, word(inner[first.len()..].trim_start()))
+    }
+
+    fn describe(&self, source: &str) -> String {
+        match self.kind {
+            Kind::Output => "Invalid output tag".into(),
+            Kind::Comment => "Invalid comment".into(),
+            Kind::Control => match self.keywords(source) {
+                ("end", kw) => format!("Invalid `end {}` tag", kw),
+                (kw, _) => format!("Invalid `{}` tag", kw),
+            },
+        }
+    }
+}
+
+/// Split a template into tags. Braces and quoted strings are skipped so that the end marker of a
+/// tag is only found outside of map literals and strings.
+fn lex(source: &str) -> Vec<RawTag> {
+    let bytes = source.as_bytes();
+    let starts_tag = |i: usize| {
+        bytes.get(i) == Some(&b'{') && matches!(bytes.get(i + 1), Some(b'{' | b'%' | b'#'))
+    };
+    let mut tags = vec![];
+    let mut i = 0;
+    while i < bytes.len() {
+        if !starts_tag(i) {
+            i += 1;
+            continue;
+        }
+        let start = i;
+        let kind = match bytes[i + 1] {
+            b'{' => Kind::Output,
+            b'#' => Kind::Comment,
+            _ => Kind::Control,
+        };
+        i += 2;
+        let mut depth = 0usize;
+        let closed = loop {
+            if i >= bytes.len() {
+                break false;
+            }
+            match (kind, bytes[i]) {
+                (Kind::Comment, b'#') if bytes.get(i + 1) == Some(&b'}') => {
+                    i += 2;
+                    break true;
+                }
+                (Kind::Comment, _) => {}
+                (Kind::Output, b'}') if depth == 0 && bytes.get(i + 1) == Some(&b'}') => {
+                    i += 2;
+                    break true;
+                }
+                (Kind::Control, b'%') if depth == 0 && bytes.get(i + 1) == Some(&b'}') => {
+                    i += 2;
+                    break true;
+                }
+                _ if depth == 0 && starts_tag(i) => break false,
+                (_, b'{') => depth += 1,
+                (_, b'}') => depth = depth.saturating_sub(1),
+                (_, quote @ (b'\'' | b'"' | b'`')) => {
+                    // Strings that do not end on the same line are most likely a mistake, so the
+                    // quote is treated as part of the tag
+                    let rest = &bytes[i + 1..];
+                    let line = rest.iter().position(|b| *b == b'\n').unwrap_or(rest.len());
+                    if let Some(end) = rest[..line].iter().position(|b| *b == quote) {
+                        i += end + 1;
+                    }
+                }
+                _ => {}
+            }
+            i += 1;
+        };
+        tags.push(RawTag {
+            kind,
+            span: Span::new(start, i),
+            closed,
+        });
+    }
+    tags
+}
+
+/// The keywords of control tags that open a block ended by an `end` tag
+const BLOCKS: &[&str] = &["if", "for", "scope", "filter", "macro", "call", "block"];
+
+/// The keywords of control tags that stand on their own
+const STANDALONE: &[&str] = &["include", "extends"];
+
+struct Recovery<'a> {
+    source: &'a str,
+    tags: Vec<RawTag>,
+    /// The index of the tag opening the block each tag belongs to, or the tag itself
+    groups: Vec<usize>,
+    /// The tags that have been replaced because they failed to parse
+    blanked: Vec<bool>,
+    errors: Vec<SyntaxError>,
+}
+
+impl<'a> Recovery<'a> {
+    /// Match the opening, `else` and `end` tags of blocks, rejecting tags that can never parse
+    fn match_blocks(&mut self) {
+        let mut open: Vec<(usize, &str)> = vec![];
+        for i in 0..self.tags.len() {
+            let tag = &self.tags[i];
+            if !tag.closed {
+                self.reject(i, "Tag is never closed".into());
+                continue;
+            }
+            if tag.kind != Kind::Control {
+                continue;
+            }
+            match tag.keywords(self.source) {
+                ("end", kw) => match open.iter().rposition(|(_, k)| *k == kw) {
+                    Some(pos) => {
+                        for (opener, kw) in open.split_off(pos + 1) {
+                            self.unclosed(opener, kw);
+                        }
+                        if let Some((opener, _)) = open.pop() {
+                            self.groups[i] = opener;
+                        }
+                    }
+                    None => self.reject(i, format!("Unexpected `end {}` tag", kw)),
+                },
+                ("else", _) => match open.last() {
+                    Some((opener, "if")) => self.groups[i] = *opener,
+                    _ => self.reject(i, "Unexpected `else` tag outside of an `if` block".into()),
+                },
+                (kw, _) if BLOCKS.contains(&kw) => open.push((i, kw)),
+                (kw, _) if STANDALONE.contains(&kw) => {}
+                ("", _) => self.reject(i, "Expected a tag name".into()),
+                (kw, _) => self.reject(i, format!("Unknown tag `{}`", kw)),
+            }
+        }
+        for (opener, kw) in open {
+            self.unclosed(opener, kw);
+        }
+    }
+
+    fn unclosed(&mut self, opener: usize, kw: &str) {
+        self.reject(opener, format!("`{}` block is never closed", kw));
+    }
+
+    /// Report an error for a tag and replace every tag of the block it belongs to
+    fn reject(&mut self, tag: usize, message: String) {
+        self.errors.push(SyntaxError {
+            span: self.tags[tag].span,
+            message,
+        });
+        let group = self.groups[tag];
+        for i in 0..self.tags.len() {
+            if i == tag || self.groups[i] == group {
+                self.blanked[i] = true;
+            }
+        }
+    }
+
+    /// The tag that a parse failure at `position` is blamed on, the last tag that starts at or
+    /// before it and has not already been replaced
+    fn culprit(&self, position: usize) -> Option<usize> {
+        (0..self.tags.len())
+            .rev()
+            .find(|i| !self.blanked[*i] && self.tags[*i].span.start <= position)
+    }
+
+    fn blanked_spans(&self) -> Vec<Span> {
+        self.tags
+            .iter()
+            .zip(self.blanked.iter())
+            .filter(|(_, blanked)| **blanked)
+            .map(|(tag, _)| tag.span)
+            .collect()
+    }
+
+    /// The source with every replaced tag swapped for a comment, or spaces when it is too short
+    /// to hold one, keeping every offset the same
+    fn patched(&self) -> String {
+        let mut patched = String::with_capacity(self.source.len());
+        let mut last = 0;
+        for span in self.blanked_spans() {
+            patched.push_str(&self.source[last..span.start]);
+            let len = span.end - span.start;
+            if len >= 4 {
+                patched.push_str("{#");
+                patched.push_str(&" ".repeat(len - 4));
+                patched.push_str("#}");
+            } else {
+                patched.push_str(&" ".repeat(len));
+            }
+            last = span.end;
+        }
+        patched.push_str(&self.source[last..]);
+        patched
+    }
+
+    fn finish(self, nodes: Vec<TemplateNode>) -> (Ast, Vec<SyntaxError>) {
+        let blanked = self.blanked_spans();
+        let ast = Ast {
+            source: self.source.into(),
+            nodes: mark_errors(nodes, self.source, &blanked),
+        };
+        (ast, self.finish_errors())
+    }
+
+    fn finish_errors(mut self) -> Vec<SyntaxError> {
+        self.errors.sort_by_key(|e| (e.span.start, e.span.end));
+        self.errors
+    }
+}
+
+/// Turn the comments and text that replaced tags back into error nodes
+fn mark_errors(nodes: Vec<TemplateNode>, source: &str, blanked: &[Span]) -> Vec<TemplateNode> {
+    let mark = |body: &mut Vec<TemplateNode>| {
+        *body = mark_errors(std::mem::take(body), source, blanked);
+    };
+    let mut marked = vec![];
+    for mut node in nodes {
+        match &mut node {
+            TemplateNode::Comment(n) if blanked.contains(&n.span) => {
+                marked.push(TemplateNode::Error(ErrorNode { span: n.span }));
+                continue;
+            }
+            TemplateNode::Text(n) => {
+                let mut last = n.span.start;
+                for span in blanked {
+                    if span.start >= last && span.end <= n.span.end {
+                        if span.start > last {
+                            marked.push(text(source, last, span.start));
+                        }
+                        marked.push(TemplateNode::Error(ErrorNode { span: *span }));
+                        last = span.end;
+                    }
+                }
+                if last != n.span.start {
+                    if last < n.span.end {
+                        marked.push(text(source, last, n.span.end));
+                    }
+                    continue;
+                }
+            }
+            TemplateNode::If(n) => {
+                for branch in n.branches.iter_mut() {
+                    mark(&mut branch.body);
+                }
+                if let Some(otherwise) = &mut n.otherwise {
+                    mark(&mut otherwise.body);
+                }
+            }
+            TemplateNode::For(n) => mark(&mut n.body),
+            TemplateNode::Scope(n) => mark(&mut n.body),
+            TemplateNode::Filter(n) => mark(&mut n.body),
+            TemplateNode::Macro(n) => mark(&mut n.body),
+            TemplateNode::Call(n) => mark(&mut n.body),
+            TemplateNode::Block(n) => mark(&mut n.body),
+            _ => {}
+        }
+        marked.push(node);
+    }
+    marked
+}
+
+fn text(source: &str, start: usize, end: usize) -> TemplateNode {
+    TemplateNode::Text(Text {
+        span: Span::new(start, end),
+        text: source[start..end].into(),
+    })
+}
+
+/// What the parser expected to find, in words
+fn expected(variant: &ErrorVariant<Rule>) -> Option<String> {
+    let positives = match variant {
+        ErrorVariant::ParsingError { positives, .. } => positives,
+        ErrorVariant::CustomError { .. } => return None,
+    };
+    let mut names: Vec<&str> = vec![];
+    for name in positives.iter().filter_map(describe) {
+        if !names.contains(&name) {
+            names.push(name);
+        }
+    }
+    // Lambdas start with a name, which is not worth mentioning when any expression would do
+    if names.contains(&"an expression") {
+        names.retain(|n| *n != "a name");
+    }
+    Some(match names.as_slice() {
+        [] => return None,
+        [name] => name.to_string(),
+        [names @ .., last] => format!("{} or {}", names.join(", "), last),
+    })
+}
+
+/// A readable name for a rule, or `None` for rules that only say that a tag was expected
+fn describe(rule: &Rule) -> Option<&'static str> {
+    Some(match rule {
+        Rule::expression_cap
+        | Rule::literal_cap
+        | Rule::not_expr
+        | Rule::lambda
+        | Rule::value
+        | Rule::function
+        | Rule::true_lit
+        | Rule::false_lit
+        | Rule::null_lit
+        | Rule::number_lit
+        | Rule::string_lit
+        | Rule::array_lit
+        | Rule::map_lit => "an expression",
+        Rule::ident | Rule::filter_head => "a name",
+        Rule::include_name => "a template name",
+        Rule::args => "arguments",
+        Rule::filter => "a filter",
+        Rule::test => "a test",
+        Rule::macro_param => "a parameter",
+        Rule::kw_if => "`if`",
+        _ => return None,
+    })
+}
@@ -1,14 +1,14 @@
 use super::*;
 
 macro_rules! parse_token {
-    (expression : $rule:expr => $tree:expr) => {
-        $tree.push($tree.templar.parse_match($rule.into_inner())?)?;
-    };
+    (expression : $rule:expr => $tree:expr) => {{
+        $tree.push($tree.state.parse_match($rule.into_inner())?)?;
+    }};
     (content : $rule:expr => $tree:expr) => {
         $tree.push(Node::Data($rule.as_str().into()))?
     };
     (template : $rule:expr => $tree:expr) => {
-        $tree.push($tree.templar.parse_match($rule.into_inner())?.set_operation(Operations::Concat))?
+        $tree.push($tree.state.parse_match($rule.into_inner())?.set_operation(Operations::Concat))?
     };
     (for : $rule:expr => $tree:expr) => {{
         $tree.set_op(Operations::ForLoop)?;
@@ -16,14 +16,14 @@ macro_rules! parse_token {
         return Ok($tree.into_node()?.into_scope())
     }};
     (scope : $rule:expr => $tree:expr) => {
-        $tree.push($tree.templar.parse_match($rule.into_inner())?.set_operation(Operations::Concat).into_scope())?
+        $tree.push($tree.state.parse_match($rule.into_inner())?.set_operation(Operations::Concat).into_scope())?
     };
-    (true => $tree:expr) => {
+    (true => $tree:expr) => {{
         $tree.push(Node::Data(true.into()))?;
-    };
-    (false => $tree:expr) => {
+    }};
+    (false => $tree:expr) => {{
         $tree.push(Node::Data(false.into()))?;
-    };
+    }};
     (str ' ' : $rule:expr => $tree:expr) => {{
         $tree.push({
             let mut result = String::new();
@@ -41,12 +41,19 @@ macro_rules! parse_token {
             Node::Data(result.into())
         })?;
     }};
-    (nil => $tree:expr) => {
+    (nil => $tree:expr) => {{
         $tree.push(Node::Data(InnerData::Null.into()))?;
-    };
-    (args : $rule:expr => $tree:expr) => {
-        $tree.push($tree.templar.parse_match($rule.into_inner())?)?;
-    };
+    }};
+    (args : $rule:expr => $tree:expr) => {{
+        $tree.push($tree.state.parse_args($rule)?)?;
+    }};
+    (not : $rule:expr => $tree:expr) => {{
+        let op = match $tree.state.templar.strict_bool {
+            true => Operations::StrictNot,
+            false => Operations::Not,
+        };
+        $tree.push(Node::Operation(Arc::new(op.build(vec![$tree.state.parse_match($rule.into_inner())?]))))?;
+    }};
     (op : $name:ident => $tree:expr) => {
         $tree.set_op(Operations::$name)?
     };
@@ -60,19 +67,19 @@ macro_rules! parse_token {
                 .parse::<i64>()
                 .map_err(|e| TemplarError::ParseFailure(format!("{}", e)))?
                 .into(),
-        ))?;
+        ))?
     };
     (array : $rule:expr => $tree:expr) => {
         $tree.push({
-            let mut tree = ParseTree::new($tree.templar);
+            let mut tree = ParseTree::new($tree.state);
             for pair in $rule.into_inner() {
                 match pair.as_rule() {
-                    Rule::expression_cap => tree.push(tree.templar.parse_match(pair.into_inner())?)?,
+                    Rule::expression_cap => tree.push(tree.state.parse_match(pair.into_inner())?)?,
                     _ => parse_token!(!pair),
                 }
             }
             Node::Array(tree.into_nodes()?)
-        })?;
+        })?
     };
     (map : $rule:expr => $tree:expr) => {
         $tree.push({
@@ -80,35 +87,47 @@ macro_rules! parse_token {
             let mut last_lit = Data::empty();
             for pair in $rule.into_inner() {
                 match pair.as_rule() {
-                    Rule::literal_cap => last_lit = $tree.templar.parse_match(pair.into_inner())?.into_document()?,
-                    Rule::expression_cap => { res.insert(last_lit.take(), $tree.templar.parse_match(pair.into_inner())?); },
+                    Rule::literal_cap => last_lit = $tree.state.parse_match(pair.into_inner())?.into_document()?,
+                    Rule::expression_cap => { res.insert(last_lit.take(), $tree.state.parse_match(pair.into_inner())?); },
                     _ => parse_token!(!pair),
                 }
             }
             Node::Map(res)
-        })?;
+        })?
     };
     (fn : $rule:expr => $tree:expr) => {
         $tree.push({
-            let mut tree = ParseTree::new($tree.templar);
             let mut name = String::new();
+            let mut args = None;
             for pair in $rule.into_inner() {
                 match pair.as_rule() {
                     Rule::ident => name = parse_token!(ident: pair),
-                    Rule::args => parse_token!(args: pair => tree),
+                    Rule::args => args = Some(pair),
                     _ => parse_token!(!pair),
                 }
             }
             name.shrink_to_fit();
-            let executor = FunctionExecutor::new(tree.templar.functions
-                    .get(&name)
-                    .ok_or_else(|| TemplarError::FunctionNotFound(name.to_string()))?
-                    .clone());
-            Node::Operation(Arc::new(Operation::from_function(name, executor, tree.into_node()?)))
-        })?;
+            if let Some(def) = $tree.state.get_macro(&name) {
+                $tree.state.macro_call(name, def, args, None)?
+            } else if name == "caller" && $tree.state.in_macro() {
+                Node::Operation(Arc::new(Operations::Caller.build(vec![])))
+            } else if let Some(parent) = $tree.state.super_block().filter(|_| name == "super") {
+                parent
+            } else {
+                let mut tree = ParseTree::new($tree.state);
+                if let Some(pair) = args {
+                    parse_token!(args: pair => tree);
+                }
+                let executor = FunctionExecutor::new(tree.state.templar.functions
+                        .get(&name)
+                        .ok_or_else(|| TemplarError::FunctionNotFound(name.to_string()))?
+                        .clone());
+                Node::Operation(Arc::new(Operation::from_function(name, executor, tree.into_node()?)))
+            }
+        })?
     };
     (filter : $rule:expr => $tree:expr) => {{
-        let mut tree = ParseTree::new($tree.templar);
+        let mut tree = ParseTree::new($tree.state);
         let mut name = String::new();
         for pair in $rule.into_inner() {
             match pair.as_rule() {
@@ -119,6 +138,139 @@ macro_rules! parse_token {
         }
         $tree.filter(&name, tree.into_node()?)?;
     }};
+    (test : $rule:expr => $tree:expr) => {{
+        let mut tree = ParseTree::new($tree.state);
+        let mut name = String::new();
+        let mut negate = false;
+        for pair in $rule.into_inner() {
+            match pair.as_rule() {
+                Rule::test_not => negate = true,
+                Rule::ident => name = parse_token!(ident: pair),
+                Rule::args => parse_token!(args: pair => tree),
+                _ => parse_token!(!pair),
+            }
+        }
+        $tree.test(&name, negate, tree.into_node()?)?;
+    }};
+    (lambda : $rule:expr => $tree:expr) => {{
+        let mut params: Vec<String> = vec![];
+        let mut body = Node::default();
+        for pair in $rule.into_inner() {
+            match pair.as_rule() {
+                Rule::ident => {
+                    let param: String = parse_token!(ident: pair);
+                    if params.contains(&param) {
+                        return Err(TemplarError::ParseFailure(format!(
+                            "Lambda parameter '{}' is defined more than once",
+                            param
+                        )));
+                    }
+                    params.push(param)
+                }
+                Rule::expression_cap => body = $tree.state.parse_match(pair.into_inner())?.fold($tree.state.templar),
+                _ => parse_token!(!pair),
+            }
+        }
+        let executor = LambdaExecutor::new(LambdaDef::new(params, body));
+        $tree.push(Node::Operation(Arc::new(Operation::from_lambda(executor))))?
+    }};
+    (filter_block : $rule:expr => $tree:expr) => {{
+        let mut tree = ParseTree::new($tree.state);
+        let mut filters = vec![];
+        for pair in $rule.into_inner() {
+            match pair.as_rule() {
+                Rule::filter_head | Rule::filter => filters.push(pair),
+                Rule::template_inner => parse_token!(template: pair => tree),
+                _ => parse_token!(!pair),
+            }
+        }
+        for pair in filters {
+            parse_token!(filter: pair => tree);
+        }
+        $tree.push(tree.into_node()?)?;
+    }};
+    (macro_def : $rule:expr => $tree:expr) => {{
+        let mut name = String::new();
+        let mut params = vec![];
+        let mut body = Node::default();
+        for pair in $rule.into_inner() {
+            match pair.as_rule() {
+                Rule::ident => name = parse_token!(ident: pair),
+                Rule::macro_param => {
+                    let mut inner = pair.into_inner();
+                    let name = parse_token!(ident: inner.next().unwrap());
+                    let default = match inner.next() {
+                        Some(default) => Some($tree.state.parse_match(default.into_inner())?.fold($tree.state.templar)),
+                        None => None,
+                    };
+                    params.push(MacroParam { name, default });
+                }
+                Rule::template_inner => {
+                    $tree.state.enter_macro();
+                    let parsed = $tree.state.parse_match(pair.into_inner());
+                    $tree.state.exit_macro();
+                    body = parsed?.set_operation(Operations::Concat).fold($tree.state.templar);
+                }
+                _ => parse_token!(!pair),
+            }
+        }
+        name.shrink_to_fit();
+        $tree.state.add_macro(TemplateMacro::new(name, params, body));
+    }};
+    (call_block : $rule:expr => $tree:expr) => {{
+        let mut call = None;
+        let mut body = Node::default();
+        for pair in $rule.into_inner() {
+            match pair.as_rule() {
+                Rule::function => call = Some(pair),
+                Rule::template_inner => body = $tree.state.parse_match(pair.into_inner())?.set_operation(Operations::Concat).into_scope().fold($tree.state.templar),
+                _ => parse_token!(!pair),
+            }
+        }
+        let mut inner = call.unwrap().into_inner();
+        let mut name: String = parse_token!(ident: inner.next().unwrap());
+        name.shrink_to_fit();
+        let def = $tree.state.get_macro(&name).ok_or_else(|| {
+            TemplarError::ParseFailure(format!("'{}' is not a macro and cannot be used with call", name))
+        })?;
+        $tree.push($tree.state.macro_call(name, def, inner.next(), Some(body))?)?;
+    }};
+    (include : $rule:expr => $tree:expr) => {{
+        let mut name = String::new();
+        let mut ignore_missing = false;
+        let mut op = Operations::Include;
+        let mut vars = Node::default();
+        for pair in $rule.into_inner() {
+            match pair.as_rule() {
+                Rule::include_name => name = $tree.state.parse_match(pair.into_inner())?.into_document()?.render()?,
+                Rule::include_ignore_missing => ignore_missing = true,
+                Rule::include_with_context => {}
+                Rule::include_with_vars => vars = $tree.state.parse_match(pair.into_inner())?,
+                Rule::include_only => op = Operations::IncludeOnly,
+                _ => parse_token!(!pair),
+            }
+        }
+        match $tree.state.load_template(&name) {
+            Ok(template) => {
+                let body = (*template.root_node()).clone();
+                $tree.push(Node::Operation(Arc::new(op.build(vec![vars, body]))))?
+            }
+            Err(TemplarError::TemplateNotFound(_)) if ignore_missing => {}
+            Err(e) => return Err(e),
+        }
+    }};
+    (block : $rule:expr => $tree:expr) => {{
+        let mut name = String::new();
+        let mut body = None;
+        for pair in $rule.into_inner() {
+            match pair.as_rule() {
+                Rule::ident => name = parse_token!(ident: pair),
+                Rule::template_inner => body = Some(pair),
+                _ => parse_token!(!pair),
+            }
+        }
+        $tree.push($tree.state.parse_block(&name, body.unwrap())?)?;
+    }};
     (value : $rule:expr => $tree:expr) => {
         $tree.push({
             let mut result: Vec<InnerData> = vec![];
@@ -129,7 +281,7 @@ macro_rules! parse_token {
                     _ => parse_token!(!pair),
                 }
             }
-            Node::Value(result)
+            $tree.state.lookup(ContextPath::new(result))
         })?
     };
     (value_key : $rule:expr) => {
@@ -149,22 +301,119 @@ macro_rules! parse_token {
     }};
 }
 
-impl Templar {
-    pub(crate) fn parse_match(&self, pairs: pest::iterators::Pairs<'_, Rule>) -> Result<Node> {
+impl<'a> ParseState<'a> {
+    /// Parse an output block such as `{{ user.name }}`, applying the configured undefined policy
+    pub(crate) fn parse_output(&self, block: Pair<'_, Rule>) -> Result<Node> {
+        let outer = self.set_strict_values(self.templar.undefined == UndefinedPolicy::Strict);
+        let node = self.parse_match(block.into_inner());
+        self.set_strict_values(outer);
+        let node = node?.set_operation(Operations::Concat);
+        Ok(match self.templar.undefined {
+            UndefinedPolicy::Lenient | UndefinedPolicy::Strict => node,
+            UndefinedPolicy::Debug => {
+                Node::Operation(Arc::new(Operations::DebugUndefined.build(vec![node])))
+            }
+        })
+    }
+
+    /// Build a call to a template macro. Unlike functions, each argument is kept as its own node
+    /// so that it can be bound to the matching macro parameter.
+    pub(crate) fn macro_call(
+        &self,
+        name: String,
+        def: Arc<TemplateMacro>,
+        args: Option<Pair<'_, Rule>>,
+        caller: Option<Node>,
+    ) -> Result<Node> {
+        let mut nodes = vec![];
+        for pair in args.into_iter().flat_map(|a| a.into_inner()) {
+            match pair.as_rule() {
+                Rule::expression_cap => nodes.push(self.parse_match(pair.into_inner())?),
+                Rule::named_arg => {
+                    return Err(TemplarError::ParseFailure(format!(
+                        "Macro '{}' does not support named arguments",
+                        name
+                    )))
+                }
+                _ => parse_token!(!pair),
+            }
+        }
+        let executor = MacroExecutor::new(def, caller);
+        Ok(Node::Operation(Arc::new(Operation::from_macro(
+            name, executor, nodes,
+        ))))
+    }
+
+    /// Parse the arguments of a call. Positional arguments are kept in the same form as they have
+    /// always been, but if any named arguments are used they are collected into `Arguments`.
+    pub(crate) fn parse_args(&self, args: Pair<'_, Rule>) -> Result<Node> {
+        let mut positional = vec![];
+        let mut named = BTreeMap::new();
+        for pair in args.into_inner() {
+            match pair.as_rule() {
+                Rule::expression_cap if !named.is_empty() => {
+                    return Err(TemplarError::ParseFailure(
+                        "Positional arguments must come before named arguments".into(),
+                    ))
+                }
+                Rule::expression_cap => positional.push(self.parse_match(pair.into_inner())?),
+                Rule::named_arg => {
+                    let mut inner = pair.into_inner();
+                    let name: String = parse_token!(ident: inner.next().unwrap());
+                    let value = self.parse_match(inner.next().unwrap().into_inner())?;
+                    if named
+                        .insert(InnerData::from(name.as_str()), value)
+                        .is_some()
+                    {
+                        return Err(TemplarError::ParseFailure(format!(
+                            "Argument '{}' was passed more than once",
+                            name
+                        )));
+                    }
+                }
+                _ => parse_token!(!pair),
+            }
+        }
+        if named.is_empty() {
+            return Ok(positional.into());
+        }
+        Ok(Node::Operation(Arc::new(
+            Operations::Arguments.build(vec![Node::Array(positional), Node::Map(named)]),
+        )))
+    }
+
+    pub(crate) fn parse_match<'i>(
+        &self,
+        pairs: impl IntoIterator<Item = Pair<'i, Rule>>,
+    ) -> Result<Node> {
         let mut tree = ParseTree::new(self);
         for pair in pairs {
             match pair.as_rule() {
                 Rule::template_inner
-                | Rule::template_block
                 | Rule::ctrl_block_if
                 | Rule::ctrl_block_else
                 | Rule::ctrl_block_loop => parse_token!(template: pair => tree),
+                Rule::template_block => tree.push(self.parse_output(pair)?)?,
                 Rule::ctrl_block_end_loop => parse_token!(for: pair => tree),
                 Rule::ctrl_block_scope => parse_token!(scope: pair => tree),
+                Rule::ctrl_block_filter => parse_token!(filter_block: pair => tree),
+                Rule::ctrl_block_macro => parse_token!(macro_def: pair => tree),
+                Rule::ctrl_block_call => parse_token!(call_block: pair => tree),
+                Rule::ctrl_block_include => parse_token!(include: pair => tree),
+                Rule::ctrl_block_block => parse_token!(block: pair => tree),
+                Rule::ctrl_block_extends => {
+                    return Err(TemplarError::ParseFailure(
+                        "extends must be the first tag in a template".into(),
+                    ))
+                }
                 Rule::expression_cap => parse_token!(expression: pair => tree),
                 Rule::content => parse_token!(content: pair => tree),
+                Rule::comment_block => {}
                 Rule::filter => parse_token!(filter: pair => tree),
+                Rule::test => parse_token!(test: pair => tree),
                 Rule::function => parse_token!(fn: pair => tree),
+                Rule::lambda => parse_token!(lambda: pair => tree),
+                Rule::not_expr => parse_token!(not: pair => tree),
                 Rule::value => parse_token!(value: pair => tree),
                 Rule::number_lit => parse_token!(number: pair => tree),
                 Rule::true_lit => parse_token!(true => tree),
@@ -173,13 +422,16 @@ impl Templar {
                 Rule::null_lit => parse_token!(nil => tree),
                 Rule::array_lit => parse_token!(array: pair => tree),
                 Rule::map_lit => parse_token!(map: pair => tree),
+                Rule::kw_if if self.templar.strict_bool => parse_token!(op: StrictIfThen => tree),
                 Rule::kw_if => parse_token!(op: IfThen => tree),
                 Rule::op_add => parse_token!(op: Add => tree),
                 Rule::op_sub => parse_token!(op: Subtract => tree),
                 Rule::op_div => parse_token!(op: Divide => tree),
                 Rule::op_mlt => parse_token!(op: Multiply => tree),
                 Rule::op_mod => parse_token!(op: Modulus => tree),
+                Rule::op_and if self.templar.strict_bool => parse_token!(op: StrictAnd => tree),
                 Rule::op_and => parse_token!(op: And => tree),
+                Rule::op_or if self.templar.strict_bool => parse_token!(op: StrictOr => tree),
                 Rule::op_or => parse_token!(op: Or => tree),
                 Rule::op_eq => parse_token!(op: Equals => tree),
                 Rule::op_ne => parse_token!(op: NotEquals => tree),
@@ -193,6 +445,6 @@ impl Templar {
                 _ => parse_token!(!pair),
             }
         }
-        Ok(tree.into_node()?)
+        tree.into_node()
     }
 }
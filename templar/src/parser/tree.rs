@@ -1,17 +1,17 @@
 use super::*;
 
 pub struct ParseTree<'a> {
-    pub templar: &'a Templar,
+    pub state: &'a ParseState<'a>,
     tree: Vec<Node>,
     current_op: Option<Operations>,
 }
 
 impl<'a> ParseTree<'a> {
-    pub fn new(templar: &'a Templar) -> ParseTree<'a> {
+    pub fn new(state: &'a ParseState<'a>) -> ParseTree<'a> {
         ParseTree {
             tree: vec![],
             current_op: None,
-            templar,
+            state,
         }
     }
 
@@ -44,7 +44,10 @@ impl<'a> ParseTree<'a> {
                     self.tree.push(Node::default())
                 }
             }
-            let tree = replace(&mut self.tree, vec![]);
+            let mut tree = std::mem::take(&mut self.tree);
+            if *op == Operations::Set {
+                tree[0] = lenient(std::mem::take(&mut tree[0]));
+            }
             let node = Node::Operation(Arc::new(op.build(tree)));
             self.tree.push(node);
             self.current_op = None;
@@ -67,9 +70,13 @@ impl<'a> ParseTree<'a> {
 
     pub fn filter(&mut self, filter: &str, args: Node) -> Result<()> {
         self.finish_op()?;
-        let tree = replace(&mut self.tree, vec![]);
-        let nodes = vec![tree.into(), args];
+        let mut input = std::mem::take(&mut self.tree).into();
+        if matches!(filter, "default" | "exists") {
+            input = lenient(input);
+        }
+        let nodes = vec![input, args];
         let filter_fn = self
+            .state
             .templar
             .filters
             .get(filter)
@@ -85,6 +92,30 @@ impl<'a> ParseTree<'a> {
         Ok(())
     }
 
+    pub fn test(&mut self, test: &str, negate: bool, args: Node) -> Result<()> {
+        self.finish_op()?;
+        let mut input = std::mem::take(&mut self.tree).into();
+        if matches!(test, "defined" | "undefined") {
+            input = lenient(input);
+        }
+        let nodes = vec![input, args];
+        let test_fn = self
+            .state
+            .templar
+            .tests
+            .get(test)
+            .ok_or_else(|| TemplarError::TestNotFound(test.into()))?
+            .clone();
+        let executor = TestExecutor::new(test_fn, negate);
+        self.tree
+            .push(Node::Operation(Arc::new(Operation::from_test(
+                test.into(),
+                executor,
+                nodes,
+            ))));
+        Ok(())
+    }
+
     pub fn into_node(mut self) -> Result<Node> {
         self.finish_op()?;
         Ok(self.tree.into())
@@ -95,3 +126,12 @@ impl<'a> ParseTree<'a> {
         Ok(self.tree)
     }
 }
+
+/// A lookup that may be undefined under the strict undefined policy, such as the target of a set
+/// or the input of `default`
+fn lenient(node: Node) -> Node {
+    match &node {
+        Node::Operation(op) if op.name() == "StrictValue" => op.nodes()[0].clone(),
+        _ => node,
+    }
+}
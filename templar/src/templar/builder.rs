@@ -12,6 +12,16 @@ use super::*;
 pub struct TemplarBuilder {
     functions: HashMap<String, Arc<functions::Function>>,
     filters: HashMap<String, Arc<filters::Filter>>,
+    tests: HashMap<String, Arc<tests::Test>>,
+    pure_functions: HashSet<String>,
+    pure_filters: HashSet<String>,
+    /// The built in functions with side effects that are still registered under their own names
+    builtin_effects: HashSet<String>,
+    sandbox: Option<SandboxPolicy>,
+    loaders: Vec<Arc<dyn TemplateLoader>>,
+    strict_bool: bool,
+    undefined: UndefinedPolicy,
+    limits: Option<RenderLimits>,
 }
 
 impl Default for TemplarBuilder {
@@ -19,6 +29,18 @@ impl Default for TemplarBuilder {
         TemplarBuilder {
             functions: functions::default_functions(),
             filters: filters::default_filters(),
+            tests: tests::default_tests(),
+            pure_functions: functions::default_pure_functions(),
+            pure_filters: filters::default_pure_filters(),
+            builtin_effects: functions::SANDBOXED_FUNCTIONS
+                .iter()
+                .map(|name| name.to_string())
+                .collect(),
+            sandbox: None,
+            loaders: vec![],
+            strict_bool: false,
+            undefined: UndefinedPolicy::default(),
+            limits: None,
         }
     }
 }
@@ -30,45 +52,211 @@ impl TemplarBuilder {
         TemplarBuilder {
             functions: Default::default(),
             filters: Default::default(),
+            tests: Default::default(),
+            pure_functions: Default::default(),
+            pure_filters: Default::default(),
+            builtin_effects: Default::default(),
+            sandbox: None,
+            loaders: vec![],
+            strict_bool: false,
+            undefined: UndefinedPolicy::default(),
+            limits: None,
         }
     }
 
-    /// Add a function to the configuration with the name specified
+    /// Add a function to the configuration with the name specified. The function is called every
+    /// time a template using it is rendered, see `add_pure_function` for functions that only
+    /// depend on their arguments.
     pub fn add_function<T: 'static + Fn(Data) -> Data + Send + Sync>(
         &mut self,
         name: &str,
         val: T,
     ) -> &mut Self {
         self.functions.insert(name.into(), Arc::new(val));
+        self.pure_functions.remove(name);
+        self.builtin_effects.remove(name);
+        self
+    }
+
+    /// Add a function whose result only depends on its arguments. When every argument is a
+    /// literal the function is called once while the template is parsed, and the result is reused
+    /// for every render. Functions that read files, the environment or run commands must be added
+    /// with `add_function` instead.
+    ///
+    /// ```
+    /// # use templar::*;
+    /// let mut builder = TemplarBuilder::default();
+    /// builder.add_pure_function("double", |args| (args.into_inner().cast::<i64>().unwrap_or(0) * 2).into());
+    /// let template = builder.build().parse("{{ double(21) }}")?;
+    /// assert_eq!(template.render(&StandardContext::new())?, "42");
+    /// # Ok::<(), templar::TemplarError>(())
+    /// ```
+    pub fn add_pure_function<T: 'static + Fn(Data) -> Data + Send + Sync>(
+        &mut self,
+        name: &str,
+        val: T,
+    ) -> &mut Self {
+        self.functions.insert(name.into(), Arc::new(val));
+        self.pure_functions.insert(name.into());
+        self.builtin_effects.remove(name);
         self
     }
 
     /// Remove the specified function name from the configuration
     pub fn remove_function(&mut self, name: &str) -> &mut Self {
         self.functions.remove(name);
+        self.pure_functions.remove(name);
+        self.builtin_effects.remove(name);
+        self
+    }
+
+    /// Check every call of the built in `file`, `env`, `script` and `command` functions against
+    /// `policy`, see `SandboxPolicy`. The policy is applied when the instance is built, to those of
+    /// the built in functions that are still registered then. It never adds a function: functions
+    /// that were removed stay removed, and custom functions added under the same names are left
+    /// as they are.
+    pub fn set_sandbox_policy(&mut self, policy: SandboxPolicy) -> &mut Self {
+        self.sandbox = Some(policy);
         self
     }
 
-    /// Add a filter to the configuration with the specified signature
+    /// Add a filter to the configuration with the specified signature. The filter is called every
+    /// time a template using it is rendered, see `add_pure_filter` for filters that only depend
+    /// on their input and arguments.
     pub fn add_filter<T: 'static + Fn(Data, Data) -> Data + Send + Sync>(
         &mut self,
         name: &str,
         val: T,
     ) -> &mut Self {
         self.filters.insert(name.into(), Arc::new(val));
+        self.pure_filters.remove(name);
+        self
+    }
+
+    /// Add a filter whose result only depends on its input and arguments. When they are all
+    /// literals the filter is called once while the template is parsed, and the result is reused
+    /// for every render. All of the built in filters are pure.
+    pub fn add_pure_filter<T: 'static + Fn(Data, Data) -> Data + Send + Sync>(
+        &mut self,
+        name: &str,
+        val: T,
+    ) -> &mut Self {
+        self.filters.insert(name.into(), Arc::new(val));
+        self.pure_filters.insert(name.into());
         self
     }
 
     /// Remove the specified filter name from the configuration
     pub fn remove_filter(&mut self, name: &str) -> &mut Self {
         self.filters.remove(name);
+        self.pure_filters.remove(name);
+        self
+    }
+
+    /// Add a test to the configuration with the specified signature. Tests are used with the `is`
+    /// keyword, e.g. `{{ value is name }}`
+    pub fn add_test<T: 'static + Fn(&Data, Data) -> Result<bool> + Send + Sync>(
+        &mut self,
+        name: &str,
+        val: T,
+    ) -> &mut Self {
+        self.tests.insert(name.into(), Arc::new(val));
+        self
+    }
+
+    /// Remove the specified test name from the configuration
+    pub fn remove_test(&mut self, name: &str) -> &mut Self {
+        self.tests.remove(name);
+        self
+    }
+
+    /// Add a template loader to the configuration. When a named template is requested, loaders are
+    /// tried in the order they were added.
+    pub fn add_loader<T: 'static + TemplateLoader>(&mut self, loader: T) -> &mut Self {
+        self.loaders.push(Arc::new(loader));
+        self
+    }
+
+    /// Remove all template loaders from the configuration
+    pub fn clear_loaders(&mut self) -> &mut Self {
+        self.loaders.clear();
+        self
+    }
+
+    /// Require booleans in conditions instead of using truthiness. When enabled, `if` fails unless
+    /// its condition is a boolean, `!` fails on non-boolean values, and `&&` and `||` treat any
+    /// non-boolean value as false and always evaluate both sides.
+    pub fn set_strict_bool(&mut self, strict: bool) -> &mut Self {
+        self.strict_bool = strict;
+        self
+    }
+
+    /// Choose how templates render values that are not defined in the context. The default is
+    /// `UndefinedPolicy::Lenient` which renders them as an empty string.
+    pub fn set_undefined_policy(&mut self, policy: UndefinedPolicy) -> &mut Self {
+        self.undefined = policy;
+        self
+    }
+
+    /// Render every template parsed by the new instance within `limits`, see `RenderLimits`.
+    /// Templates loaded from untrusted sources should be parsed with limits in place.
+    ///
+    /// ```
+    /// # use templar::*;
+    /// let mut limits = RenderLimits::new();
+    /// limits.set_max_function_calls(1);
+    /// let mut builder = TemplarBuilder::default();
+    /// builder.set_render_limits(limits);
+    ///
+    /// let template = builder.build().parse("{{ env('HOME') }}{{ env('HOME') }}")?;
+    /// assert!(template.render(&StandardContext::new()).is_err());
+    /// # Ok::<(), templar::TemplarError>(())
+    /// ```
+    pub fn set_render_limits(&mut self, limits: RenderLimits) -> &mut Self {
+        self.limits = Some(limits);
         self
     }
 
     /// Build a new templar instance with this configuration
     pub fn build(self) -> Templar {
-        let functions = self.functions;
+        let mut functions = self.functions;
+        if let Some(policy) = &self.sandbox {
+            for (name, function) in functions::sandboxed_functions(policy) {
+                if self.builtin_effects.contains(name) {
+                    functions.insert(name.into(), function);
+                }
+            }
+        }
         let filters = self.filters;
-        Templar { functions, filters }
+        let tests = self.tests;
+        let loaders = self.loaders;
+        Templar {
+            functions,
+            filters,
+            tests,
+            pure_functions: self.pure_functions,
+            pure_filters: self.pure_filters,
+            loaders,
+            strict_bool: self.strict_bool,
+            undefined: self.undefined,
+            limits: self.limits.map(Arc::new),
+            templates: Default::default(),
+        }
     }
 }
+
+/// How a template renders a value that is not defined in the context, e.g. `{{ a.b.c }}` when `a`
+/// has no `b`. The policy only applies to `{{ }}` blocks, not to conditions such as
+/// `{% if user.admin %}`, and the `default` and `exists` filters and the `defined` and `undefined`
+/// tests can still be used with undefined values.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum UndefinedPolicy {
+    /// Render undefined values as an empty string
+    #[default]
+    Lenient,
+    /// Fail to render with `TemplarError::UndefinedValue` naming the undefined path, wherever it
+    /// is read, e.g. `{{ missing | length }}` fails instead of rendering a length
+    Strict,
+    /// Render a visible marker such as `{{ undefined: a.b.c }}` in place of the value
+    Debug,
+}
@@ -7,10 +7,11 @@ use crate::*;
 mod builder;
 mod template;
 
-pub use builder::TemplarBuilder;
+pub use builder::{TemplarBuilder, UndefinedPolicy};
 pub use template::{Template, TemplateTree};
 
-use std::collections::BTreeMap;
+use std::collections::{BTreeMap, HashSet};
+use std::sync::RwLock;
 
 lazy_static! {
     static ref GLOBAL: Templar = Templar::default();
@@ -40,6 +41,14 @@ lazy_static! {
 pub struct Templar {
     pub(crate) functions: HashMap<String, Arc<functions::Function>>,
     pub(crate) filters: HashMap<String, Arc<filters::Filter>>,
+    pub(crate) tests: HashMap<String, Arc<tests::Test>>,
+    pub(crate) pure_functions: HashSet<String>,
+    pub(crate) pure_filters: HashSet<String>,
+    pub(crate) loaders: Vec<Arc<dyn TemplateLoader>>,
+    pub(crate) strict_bool: bool,
+    pub(crate) undefined: UndefinedPolicy,
+    pub(crate) limits: Option<Arc<RenderLimits>>,
+    pub(crate) templates: RwLock<HashMap<String, Template>>,
 }
 
 impl Default for Templar {
@@ -55,6 +64,77 @@ impl Templar {
         &GLOBAL
     }
 
+    /// Retrieve a named template from the configured loaders.
+    ///
+    /// The template is parsed the first time it is requested and cached by name afterwards, so
+    /// subsequent calls are cheap.
+    ///
+    /// ```
+    /// # use templar::*;
+    /// let mut builder = TemplarBuilder::default();
+    /// let loader: MemoryLoader = vec![("hello.tmpl", "{{ 'hello' | upper }}")].into_iter().collect();
+    /// builder.add_loader(loader);
+    /// let templar = builder.build();
+    ///
+    /// # let context = StandardContext::new();
+    /// let template = templar.get_template("hello.tmpl")?;
+    /// assert_eq!(template.render(&context)?, "HELLO");
+    /// # Ok::<(), templar::TemplarError>(())
+    /// ```
+    pub fn get_template(&self, name: &str) -> Result<Template> {
+        self.load_template(name, &[])
+    }
+
+    pub(crate) fn load_template(&self, name: &str, parents: &[String]) -> Result<Template> {
+        if let Some(template) = self.templates.read().unwrap().get(name) {
+            return Ok(template.clone());
+        }
+        if parents.iter().any(|p| p == name) {
+            return Err(TemplarError::ParseFailure(format!(
+                "Recursive template reference: {} -> {}",
+                parents.join(" -> "),
+                name
+            )));
+        }
+        let template = self.parse_named_template(&self.load_source(name)?, name, parents)?;
+        self.templates
+            .write()
+            .unwrap()
+            .insert(name.into(), template.clone());
+        Ok(template)
+    }
+
+    /// Load the raw source of a named template without parsing or caching it
+    pub fn load_source(&self, name: &str) -> Result<String> {
+        for loader in self.loaders.iter() {
+            match loader.load(name) {
+                Err(TemplarError::TemplateNotFound(_)) => continue,
+                result => return result,
+            }
+        }
+        Err(TemplarError::TemplateNotFound(name.into()))
+    }
+
+    /// The names of the filters available to templates, sorted
+    pub fn filter_names(&self) -> Vec<&str> {
+        sorted_names(&self.filters)
+    }
+
+    /// The names of the functions available to templates, sorted
+    pub fn function_names(&self) -> Vec<&str> {
+        sorted_names(&self.functions)
+    }
+
+    /// The names of the tests available to templates, sorted
+    pub fn test_names(&self) -> Vec<&str> {
+        sorted_names(&self.tests)
+    }
+
+    /// Remove all cached templates, forcing them to be reloaded on next access
+    pub fn clear_template_cache(&self) {
+        self.templates.write().unwrap().clear();
+    }
+
     /// Parse a `Template` or `TemplateTree` value.
     ///
     /// ```
@@ -99,14 +179,14 @@ impl Templar {
     #[inline]
     #[cfg(feature = "json-extension")]
     pub fn parse_json(&self, json: &str) -> Result<TemplateTree> {
-        Ok(self.parse(&serde_json::from_str(json).wrap()?)?)
+        self.parse(&serde_json::from_str(json).wrap()?)
     }
 
     /// Identical to parse_json except this expects a YAML string.
     #[inline]
     #[cfg(feature = "yaml-extension")]
     pub fn parse_yaml(&self, yml: &str) -> Result<TemplateTree> {
-        Ok(self.parse(&serde_yaml::from_str(yml).wrap()?)?)
+        self.parse(&serde_yaml::from_str(yml).wrap()?)
     }
 }
 
@@ -132,7 +212,7 @@ impl Parseable<Template> for &InnerData {
     #[inline]
     fn parse_into(t: Self, templar: &Templar) -> Result<Template> {
         Ok(match t {
-            InnerData::String(s) => templar.parse_template(&s)?,
+            InnerData::String(s) => templar.parse_template(s)?,
             InnerData::Newtype(d) => templar.parse(d.as_ref())?,
             _ => Node::Data(t.clone().into()).into(),
         })
@@ -147,7 +227,7 @@ impl Parseable<TemplateTree> for &InnerData {
             InnerData::Newtype(d) => templar.parse(d.as_ref())?,
             InnerData::Seq(s) => TemplateTree::Sequence(Arc::new(
                 s.iter()
-                    .map(|i| Ok(templar.parse(i)?))
+                    .map(|i| templar.parse(i))
                     .collect::<Result<Vec<TemplateTree>>>()?,
             )),
             InnerData::Map(map) => TemplateTree::Mapping(Arc::new(
@@ -174,3 +254,9 @@ mod private {
     impl Seal for &String {}
     impl Seal for &str {}
 }
+
+fn sorted_names<T: ?Sized>(map: &HashMap<String, Arc<T>>) -> Vec<&str> {
+    let mut names: Vec<&str> = map.keys().map(|k| k.as_str()).collect();
+    names.sort_unstable();
+    names
+}
@@ -1,12 +1,14 @@
 use crate::*;
 use std::collections::BTreeMap;
 use std::convert::TryFrom;
+use std::fmt;
+use std::io;
 use std::sync::Arc;
 
 /// Template holds the prepared result of parsing a template. Because the template does not need to be
 /// reparsed, subsequent executions come at a low cost.
 #[derive(Debug, Clone, Default)]
-pub struct Template(Arc<Node>);
+pub struct Template(Arc<Node>, Option<Arc<RenderLimits>>);
 
 impl Template {
     /// Render a template as a string.
@@ -23,7 +25,60 @@ impl Template {
     /// ```
     pub fn render(&self, ctx: &impl Context) -> Result<String> {
         //let local_ctx = ctx.create_scope();
-        self.0.render(ctx)
+        self.limited(|| self.0.render(ctx))
+    }
+
+    /// Render a template as a string within `limits`, in place of any limits set with
+    /// `TemplarBuilder::set_render_limits`.
+    ///
+    /// # Usage
+    ///
+    /// ```
+    /// # use templar::*;
+    /// # let context = StandardContext::new();
+    /// let mut limits = RenderLimits::new();
+    /// limits.set_max_output_bytes(8);
+    ///
+    /// let t = Templar::global().parse("{{ 'a very long string' }}")?;
+    /// assert!(matches!(
+    ///     t.render_with_limits(&context, &limits),
+    ///     Err(TemplarError::LimitExceeded(_))
+    /// ));
+    /// # Ok::<(), templar::TemplarError>(())
+    /// ```
+    pub fn render_with_limits(&self, ctx: &impl Context, limits: &RenderLimits) -> Result<String> {
+        limits.enforce(|| self.0.render(ctx))
+    }
+
+    /// Render a template into a writer. Text, output blocks and loop iterations are written out as
+    /// they are rendered rather than being collected into one string first, which keeps memory use
+    /// down for large outputs. On an error, the output rendered up to that point has already been
+    /// written. The writer is not flushed.
+    ///
+    /// # Usage
+    ///
+    /// ```
+    /// # use templar::*;
+    /// # let context = StandardContext::new();
+    ///
+    /// let t = Templar::global().parse("{% for i in [1, 2, 3] %}{{ i }},{% end for %}")?;
+    /// let mut out = Vec::new();
+    /// t.render_to(&mut out, &context)?;
+    /// assert_eq!(out, b"1,2,3,");
+    /// # Ok::<(), templar::TemplarError>(())
+    /// ```
+    pub fn render_to(&self, out: &mut impl io::Write, ctx: &impl Context) -> Result<()> {
+        let mut writer = IoWriter { out, error: None };
+        match self.limited(|| self.0.render_to(&ctx.wrap(), &mut writer)) {
+            Err(e) => Err(writer.error.map(TemplarError::from).unwrap_or(e)),
+            Ok(()) => Ok(()),
+        }
+    }
+
+    /// Render a template into anything implementing `std::fmt::Write`, such as an existing
+    /// `String`, in the same way as `render_to`.
+    pub fn render_fmt(&self, out: &mut impl fmt::Write, ctx: &impl Context) -> Result<()> {
+        self.limited(|| self.0.render_to(&ctx.wrap(), out))
     }
 
     /// Execute a template, getting a `InnerData` from the `unstructured` crate as a result.
@@ -42,12 +97,98 @@ impl Template {
     /// ```
     pub fn exec(&self, ctx: &impl Context) -> Data {
         //let local_ctx = ctx.create_scope();
-        self.0.exec(ctx)
+        match &self.1 {
+            Some(limits) => limits
+                .enforce(|| self.0.exec(ctx).into_result())
+                .unwrap_or_else(Data::from),
+            None => self.0.exec(ctx),
+        }
+    }
+
+    /// Compile the template into instructions for a small stack machine. The compiled template
+    /// renders the same as this one, but faster, which pays off for templates that are rendered
+    /// many times.
+    ///
+    /// # Usage
+    ///
+    /// ```
+    /// # use templar::*;
+    /// # let context = StandardContext::new();
+    ///
+    /// let t = Templar::global().parse("{% if 5 > 2 %}yes{% end if %}")?;
+    /// assert_eq!(t.compile().render(&context)?, "yes");
+    /// # Ok::<(), templar::TemplarError>(())
+    /// ```
+    pub fn compile(&self) -> CompiledTemplate {
+        CompiledTemplate::new(self)
+    }
+
+    /// The context paths this template reads, such as `server.host`, sorted and without
+    /// duplicates. Names bound by the template itself, e.g. loop variables or values assigned
+    /// with `=`, are not included. The root of the context is listed as `.`
+    ///
+    /// # Usage
+    ///
+    /// ```
+    /// # use templar::*;
+    /// let t = Templar::global().parse("{% for p in ports %}{{ host }}:{{ p }}{% end for %}")?;
+    /// assert_eq!(t.referenced_paths(), vec!["host", "ports"]);
+    /// # Ok::<(), templar::TemplarError>(())
+    /// ```
+    pub fn referenced_paths(&self) -> Vec<String> {
+        References::of(&self.0).paths.into_iter().collect()
+    }
+
+    /// The names of the filters this template uses, sorted and without duplicates. Pure filters
+    /// that only had literal inputs were evaluated when the template was parsed and are not listed.
+    pub fn referenced_filters(&self) -> Vec<String> {
+        References::of(&self.0).filters.into_iter().collect()
+    }
+
+    /// The names of the functions this template calls, sorted and without duplicates. Macros
+    /// defined by the template, and pure functions that were evaluated when the template was
+    /// parsed, are not included.
+    pub fn referenced_functions(&self) -> Vec<String> {
+        References::of(&self.0).functions.into_iter().collect()
     }
 
     pub(crate) fn root_node(&self) -> Arc<Node> {
         self.0.clone()
     }
+
+    /// Whether the template is rendered within limits set with `TemplarBuilder::set_render_limits`
+    pub(crate) fn is_limited(&self) -> bool {
+        self.1.is_some()
+    }
+
+    /// Render within `limits` whenever the template is rendered
+    pub(crate) fn with_limits(mut self, limits: &Option<Arc<RenderLimits>>) -> Self {
+        self.1 = limits.clone();
+        self
+    }
+
+    /// Run `render` within the limits of the template, if it has any
+    fn limited<T>(&self, render: impl FnOnce() -> Result<T>) -> Result<T> {
+        match &self.1 {
+            Some(limits) => limits.enforce(render),
+            None => render(),
+        }
+    }
+}
+
+/// Adapts an `io::Write` for streaming, keeping hold of the I/O error that stopped the output
+struct IoWriter<'a, W: io::Write> {
+    out: &'a mut W,
+    error: Option<io::Error>,
+}
+
+impl<W: io::Write> fmt::Write for IoWriter<'_, W> {
+    fn write_str(&mut self, s: &str) -> fmt::Result {
+        self.out.write_all(s.as_bytes()).map_err(|e| {
+            self.error = Some(e);
+            fmt::Error
+        })
+    }
 }
 
 /// TemplateTree holds the parsed result of a InnerData tree. This tree of templates
@@ -97,7 +238,7 @@ impl TemplateTree {
 
 impl From<Node> for Template {
     fn from(n: Node) -> Template {
-        Template(Arc::new(n))
+        Template(Arc::new(n), None)
     }
 }
 
@@ -0,0 +1,151 @@
+use super::*;
+use crate::ast::*;
+
+fn parse(source: &str) -> Result<Ast> {
+    Templar::global().parse_ast(source)
+}
+
+#[derive(Default)]
+struct Collect {
+    paths: Vec<String>,
+    filters: Vec<String>,
+    functions: Vec<String>,
+    tests: Vec<String>,
+}
+
+impl Visitor for Collect {
+    fn visit_path(&mut self, path: &Path) {
+        self.paths.push(path.to_string());
+    }
+
+    fn visit_filter(&mut self, filter: &FilterCall) {
+        self.filters.push(filter.name.name.clone());
+        walk_filter_args(self, filter);
+    }
+
+    fn visit_function(&mut self, call: &FunctionCall) {
+        self.functions.push(call.name.name.clone());
+        walk_args(self, &call.args);
+    }
+
+    fn visit_test(&mut self, test: &TestExpr) {
+        self.tests.push(test.name.name.clone());
+        walk_test(self, test);
+    }
+}
+
+fn walk_filter_args(visitor: &mut Collect, filter: &FilterCall) {
+    if let Some(args) = &filter.args {
+        walk_args(visitor, args);
+    }
+}
+
+#[test]
+fn ast_spans_cover_source() -> Result<()> {
+    let source = "Hello {{ user.name | upper }}!{# note #}";
+    let ast = parse(source)?;
+    let spans: Vec<&str> = ast.nodes.iter().map(|n| n.span().slice(source)).collect();
+    assert_eq!(
+        spans,
+        vec!["Hello ", "{{ user.name | upper }}", "!", "{# note #}"]
+    );
+    match &ast.nodes[1] {
+        TemplateNode::Output(out) => match &out.expr {
+            Expression::Filter(f) => {
+                assert_eq!(f.span.slice(source), "user.name | upper");
+                assert_eq!(f.input.span().slice(source), "user.name");
+                assert_eq!(f.filter.name.name, "upper");
+            }
+            other => panic!("unexpected expression {:?}", other),
+        },
+        other => panic!("unexpected node {:?}", other),
+    }
+    Ok(())
+}
+
+#[test]
+fn ast_operators_apply_left_to_right() -> Result<()> {
+    let source = "{{ 1 + 2 * (3 - x) }}";
+    let ast = parse(source)?;
+    let expr = match &ast.nodes[0] {
+        TemplateNode::Output(out) => &out.expr,
+        other => panic!("unexpected node {:?}", other),
+    };
+    match expr {
+        Expression::Binary(mul) => {
+            assert_eq!(mul.op, BinaryOp::Multiply);
+            assert_eq!(mul.left.span().slice(source), "1 + 2");
+            assert_eq!(mul.right.span().slice(source), "(3 - x)");
+            assert!(matches!(&*mul.right, Expression::Group(_)));
+        }
+        other => panic!("unexpected expression {:?}", other),
+    }
+    Ok(())
+}
+
+#[test]
+fn ast_control_blocks() -> Result<()> {
+    let source = "{% if a %}1{% else if b %}2{% else %}3{% end if %}\
+                  {% for i in items %}{{ i }}{% end for %}\
+                  {% macro m(x, y='d') %}{{ x }}{% end macro %}\
+                  {% include 'part.tmpl' ignore missing with { 'k': v } only %}";
+    let ast = parse(source)?;
+    match &ast.nodes[0] {
+        TemplateNode::If(block) => {
+            assert_eq!(block.branches.len(), 2);
+            assert_eq!(block.branches[1].span.slice(source), "{% else if b %}2");
+            assert!(block.otherwise.is_some());
+        }
+        other => panic!("unexpected node {:?}", other),
+    }
+    match &ast.nodes[1] {
+        TemplateNode::For(block) => {
+            assert_eq!(block.binding.to_string(), "i");
+            assert_eq!(block.iterable.span().slice(source), "items");
+            assert_eq!(block.body.len(), 1);
+        }
+        other => panic!("unexpected node {:?}", other),
+    }
+    match &ast.nodes[2] {
+        TemplateNode::Macro(block) => {
+            assert_eq!(block.name.name, "m");
+            assert_eq!(block.params.len(), 2);
+            assert!(block.params[1].default.is_some());
+        }
+        other => panic!("unexpected node {:?}", other),
+    }
+    match &ast.nodes[3] {
+        TemplateNode::Include(block) => {
+            assert_eq!(block.name.value, InnerData::from("part.tmpl"));
+            assert!(block.ignore_missing && block.only && block.vars.is_some());
+        }
+        other => panic!("unexpected node {:?}", other),
+    }
+    Ok(())
+}
+
+#[test]
+fn ast_visitor() -> Result<()> {
+    let source = "{% for u in users | select(x => x.active) %}\
+                  {{ env('HOME') ~ u.name | default(fallback) }}{{ u.age is divisibleby(n) }}{{ .['key'] }}\
+                  {% end for %}";
+    let mut collect = Collect::default();
+    collect.visit_ast(&parse(source)?);
+    assert_eq!(
+        collect.paths,
+        vec!["u", "users", "x.active", "u.name", "fallback", "u.age", "n", "key"]
+    );
+    assert_eq!(collect.filters, vec!["select", "default"]);
+    assert_eq!(collect.functions, vec!["env"]);
+    assert_eq!(collect.tests, vec!["divisibleby"]);
+    Ok(())
+}
+
+#[test]
+fn ast_does_not_resolve_templates() -> Result<()> {
+    let ast =
+        parse("{% extends 'missing.tmpl' %}{% block a %}{{ unknown() | unknown }}{% end block %}")?;
+    assert!(matches!(ast.nodes[0], TemplateNode::Extends(_)));
+    assert!(matches!(ast.nodes[1], TemplateNode::Block(_)));
+    Ok(())
+}
@@ -0,0 +1,91 @@
+use super::*;
+
+const CYCLES: &str = r#"---
+a: "{{ b }}"
+b: "{{ a }}"
+itself: "{{ itself }}"
+nested:
+  inner: "{{ nested.outer }}"
+  outer: "<{{ nested.inner }}>"
+name: Demo
+upper: "{{ name | upper }}"
+twice: "{{ upper }} {{ upper }}"
+list:
+  - "{{ list }}"
+"#;
+
+fn context(context: StandardContext) -> Result<StandardContext> {
+    context.set(Templar::global().parse_yaml(CYCLES)?)?;
+    Ok(context)
+}
+
+fn cycle_error(context: &StandardContext, source: &str) -> Result<String> {
+    match Templar::global().parse(source)?.render(context) {
+        Err(TemplarError::ContextFailure(message)) => Ok(message),
+        result => panic!("{:?}: expected a cycle, got {:?}", source, result),
+    }
+}
+
+#[test]
+fn cycles_are_named() -> Result<()> {
+    for ctx in [StandardContext::new(), StandardContext::memoized()] {
+        let ctx = context(ctx)?;
+        let prefix = "Dynamic values refer to each other in a cycle: ";
+        assert_eq!(
+            cycle_error(&ctx, "{{ a }}")?,
+            format!("{}a -> b -> a", prefix)
+        );
+        assert_eq!(
+            cycle_error(&ctx, "{{ b }}")?,
+            format!("{}b -> a -> b", prefix)
+        );
+        assert_eq!(
+            cycle_error(&ctx, "{{ itself }}")?,
+            format!("{}itself -> itself", prefix)
+        );
+        assert_eq!(
+            cycle_error(&ctx, "{{ nested.inner }}")?,
+            format!("{}nested.inner -> nested.outer -> nested.inner", prefix)
+        );
+    }
+    Ok(())
+}
+
+#[test]
+fn cycles_fail_reads_of_the_whole_context() -> Result<()> {
+    let ctx = context(StandardContext::new())?;
+    assert!(ctx.get().into_result().is_err());
+    cycle_error(&ctx, "{{ nested }}")?;
+    Ok(())
+}
+
+#[test]
+fn cycles_name_the_entries_they_are_found_in() -> Result<()> {
+    let ctx = context(StandardContext::new())?;
+    let prefix = "Dynamic values refer to each other in a cycle: ";
+    assert_eq!(
+        cycle_error(&ctx, "{{ . | json }}")?,
+        format!("{}a -> b -> a", prefix)
+    );
+    assert_eq!(
+        cycle_error(&ctx, "{{ nested | json }}")?,
+        format!("{}nested.inner -> nested.outer -> nested.inner", prefix)
+    );
+    assert_eq!(
+        cycle_error(&ctx, "{{ list | json }}")?,
+        format!("{}list.0 -> list.0", prefix)
+    );
+    Ok(())
+}
+
+#[test]
+fn repeated_reads_are_not_cycles() -> Result<()> {
+    let ctx = context(StandardContext::new())?;
+    let template = Templar::global().parse("{{ twice }} {{ upper }}")?;
+    assert_eq!(template.render(&ctx)?, "DEMO DEMO DEMO");
+
+    // A cycle found earlier leaves nothing behind
+    cycle_error(&ctx, "{{ a }}")?;
+    assert_eq!(template.render(&ctx)?, "DEMO DEMO DEMO");
+    Ok(())
+}
@@ -29,3 +29,28 @@ fn run_dynamic_context_new_value() -> Result<()> {
     assert_eq!(result, "HELLO!!".to_string());
     Ok(())
 }
+
+#[test]
+fn run_dynamic_context_root() -> Result<()> {
+    let template = Templar::global().parse_yaml(DYN_CONTEXT)?;
+    let context = StandardContext::new();
+    context.set(template)?;
+    let root = context.get().into_result()?;
+    assert_eq!(root["somedict"]["static"], InnerData::from("value"));
+    assert_eq!(root["somedict"]["dynamic"], InnerData::from("VALUE"));
+    Ok(())
+}
+
+#[test]
+fn run_dynamic_context_walk_result() -> Result<()> {
+    let template = Templar::global().parse_yaml(DYN_CONTEXT)?;
+    let context = StandardContext::new();
+    context.set(template)?;
+    context.set_path(
+        &[&"alias".into()],
+        Templar::global().parse_template("{{ somedict }}")?,
+    )?;
+    let tmpl = Templar::global().parse_template("{{ alias.dynamic }} {{ alias.missing }}")?;
+    assert_eq!(tmpl.render(&context)?, "VALUE ");
+    Ok(())
+}
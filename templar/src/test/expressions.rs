@@ -62,6 +62,37 @@ test_expressions! {
     order_with_inner_2: "20+(5*5)" == 45i64;
     order_with_complex_inner: " 20+(5 + 5 + (2 + 1))" == 33i64;
     !fail_math_op_against_string: "'hello' + 5";
+    !fail_add_overflow: "9223372036854775807 + 1";
+    !fail_multiply_overflow: "9223372036854775807 * 2";
+    !fail_divide_overflow: "(0 - 9223372036854775807 - 1) / -1";
+    !fail_divide_by_zero: "1 / 0";
+    !fail_modulus_by_zero: "1 % 0";
+
+    greater_than_equals: "2 >= 2" == true;
+    greater_than_equals_false: "1 >= 2" == false;
+    less_than_equals: "2 <= 2" == true;
+    less_than_equals_false: "3 <= 2" == false;
+
+    // booleans
+    and_truthy: "'a' && 'b'" == "b";
+    and_falsy_left: "0 && 'a'" == 0i64;
+    and_falsy_right: "1 && ''" == "";
+    or_truthy_left: "'a' || 0" == "a";
+    or_truthy_right: "0 || 'a'" == "a";
+    or_falsy: "'' || 0 || [] || {} || missing || null" == InnerData::Null;
+    or_fallback: "missing.nickname || 'name'" == "name";
+    and_short_circuit: "false && ('a' + 1)" == false;
+    or_short_circuit: "true || ('a' + 1)" == true;
+    !and_propagates_error: "true && ('a' + 1)";
+    !or_propagates_error: "('a' + 1) || true";
+    not_bool: "!false" == true;
+    not_truthy: "!'a'" == false;
+    not_falsy: "!0" == true;
+    not_missing: "!missing" == true;
+    not_twice: "!!'a'" == true;
+    not_inner: "!(1 == 2) && !''" == true;
+    !not_propagates_error: "!('a' + 1)";
+    !array_propagates_error: "[1, 'a' + 1]";
 
     // arrays
     get_index_of_array: "[1,2,3] | index(1)" == 2i64;
@@ -87,6 +118,7 @@ test_expressions! {
     concat_non_string: "'one' ~ 1 ~ true" == "one1true";
     replace: "'this-is-a-thing' | replace('-','_') " == "this_is_a_thing";
     truncate: "'this-is-a-thing' | truncate(5) " == "this-";
+    truncate_within_char: "'aé' | truncate(2)" == "a";
 
     // encoding/decoding
     base64_encode_filter: "'Test' | base64('encode')" == "VGVzdA==";
@@ -97,6 +129,44 @@ test_expressions! {
     script_key_filter_2: " script('echo -n test') | key('status') " == 0;
     command_var_args: "command('echo', '-n', 'test') | key('stdout')" == "test";
 
+    // named arguments
+    named_arg_join: "[1, 2, 3] | join(sep=', ')" == "1, 2, 3";
+    named_arg_split: "'a,b' | split(sep=',') | index(index=1)" == "b";
+    named_arg_json: "{'a': [1]} | json(pretty=true)" == "{\n  \"a\": [\n    1\n  ]\n}";
+    named_arg_json_compact: "{'a': [1]} | json(pretty=false)" == "{\"a\":[1]}";
+    named_arg_macro_filter: "'this-is' | replace(old='-', new='_')" == "this_is";
+    named_arg_mixed: "'this-is' | replace('-', new='_')" == "this_is";
+    named_arg_default: "missing | default(value='x')" == "x";
+    named_arg_command_cwd: "command('pwd', cwd='/') | key('stdout') | trim" == "/";
+    named_arg_script_cwd: "script('echo -n $PWD', cwd='/') | key('stdout')" == "/";
+    named_arg_test: "9 is divisibleby(3)" == true;
+    !named_arg_unknown: "'abc' | truncate(length=1)";
+    !named_arg_extra: "'abc' | truncate(1, extra=1)";
+    !named_arg_twice: "'a-b' | replace('-', old='_', new='+')";
+    !named_arg_missing: "'a-b' | replace(new='+')";
+    !named_arg_command_unknown: "command('pwd', dir='/')";
+
+    // lambdas
+    lambda_map: "[1, 2, 3] | map(x => x * 2) | join(',')" == "2,4,6";
+    lambda_map_key: "[{'name': 'a'}, {'name': 'b'}] | map(x => x.name) | join(',')" == "a,b";
+    lambda_map_entries: "{'a': 1, 'b': 2} | map(e => e.key ~ '=' ~ e.value) | join(',')" == "a=1,b=2";
+    lambda_select: "[80, 443, 8080] | select(p => p > 1024) | join(',')" == "8080";
+    lambda_reject: "[80, 443, 8080] | reject(p => p > 1024) | join(',')" == "80,443";
+    lambda_select_no_lambda: "[true, false, true] | select | length" == 2u64;
+    lambda_sort_by: "[{'n': 'b'}, {'n': 'c'}, {'n': 'a'}] | sort_by(x => x.n) | map(x => x.n) | join" == "a\nb\nc";
+    lambda_group_by: "[1, 2, 3, 4, 5] | group_by(x => x % 2) | json" == "{\"0\":[2,4],\"1\":[1,3,5]}";
+    lambda_any: "[1, 2, 3] | any(x => x > 2)" == true;
+    lambda_all: "[1, 2, 3] | all(x => x > 2)" == false;
+    lambda_all_empty: "[] | all(x => x > 2)" == true;
+    lambda_reduce: "[1, 2, 3, 4] | reduce((acc, x) => acc + x)" == 10i64;
+    lambda_reduce_initial: "[1, 2, 3] | reduce((acc, x) => acc ~ x, initial='>')" == ">123";
+    lambda_named_fn: "[3, 1, 2] | sort_by(fn=x => x) | join(',')" == "1,2,3";
+    lambda_body_filters: "['a', 'b'] | map(x => x | upper) | join(',')" == "A,B";
+    lambda_nested: "[[1, 2], [3]] | map(xs => xs | map(x => x + 1) | join(',')) | join(';')" == "2,3;4";
+    !lambda_map_requires_lambda: "[1] | map('x')";
+    !lambda_wrong_arity: "[1] | map((a, b) => a)";
+    !lambda_body_error: "[1] | map(x => x + 'a')";
+
     // set
     test_set: "val = 'hello'" == InnerData::Unassigned;
     test_set_return: "val = 'hello' ~ val" == "hello";
@@ -104,3 +174,47 @@ test_expressions! {
     // HTML Escape
     escape_html: "`<script>alert('hello!')</script>` | e" == "&lt;script&gt;alert(&#x27;hello!&#x27;)&lt;&#x2F;script&gt;";
 }
+
+#[test]
+fn named_args_must_follow_positional() {
+    let result = Templar::global().parse_expression("'a-b' | replace(old='-', '_')");
+    assert!(matches!(result, Err(TemplarError::ParseFailure(_))));
+    let result = Templar::global().parse_expression("[1] | join(sep=',', sep=';')");
+    assert!(matches!(result, Err(TemplarError::ParseFailure(_))));
+}
+
+#[test]
+fn named_args_custom_function() -> Result<()> {
+    let mut builder = TemplarBuilder::default();
+    builder.add_function("greet", |args| {
+        let mut args = match args.into_arguments(2) {
+            Ok(args) => args,
+            Err(e) => return e.into(),
+        };
+        let name = args.take(0, "name").ok().flatten().unwrap_or_default();
+        let greeting = args.take(1, "greeting").ok().flatten();
+        let greeting = greeting.unwrap_or_else(|| "Hello".into());
+        format!("{} {}", greeting, name).into()
+    });
+    let templar = builder.build();
+    let context = StandardContext::new();
+    let tmpl =
+        templar.parse_expression("greet('Bob', greeting='Hi') ~ ', ' ~ greet(name='Amy')")?;
+    assert_eq!(tmpl.render(&context)?, "Hi Bob, Hello Amy");
+    Ok(())
+}
+
+#[test]
+fn strict_bool() -> Result<()> {
+    let mut builder = TemplarBuilder::default();
+    builder.set_strict_bool(true);
+    let templar = builder.build();
+    let context = StandardContext::new();
+    let exec = |exp: &str| -> Result<Data> { Ok(templar.parse_expression(exp)?.exec(&context)) };
+    assert_eq!(exec("'a' && true")?.into_inner(), InnerData::Bool(false));
+    assert_eq!(exec("1 || true")?.into_inner(), InnerData::Bool(true));
+    assert_eq!(exec("!false")?.into_inner(), InnerData::Bool(true));
+    assert!(exec("!'a'")?.is_failed());
+    assert!(exec("false && ('a' + 1)")?.is_failed());
+    Ok(())
+}
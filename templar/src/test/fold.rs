@@ -0,0 +1,128 @@
+use super::*;
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+fn parse(template: &str) -> Result<Template> {
+    Templar::global().parse_template(template)
+}
+
+/// The parts of the root concatenation of a template, or the root itself
+fn parts(template: &Template) -> Vec<Node> {
+    match &*template.root_node() {
+        Node::Operation(op) if op.name() == "Concat" => op.nodes().to_vec(),
+        node => vec![node.clone()],
+    }
+}
+
+fn is_data(node: &Node) -> bool {
+    matches!(node, Node::Data(_))
+}
+
+#[test]
+fn fold_literal_expressions() -> Result<()> {
+    let context = StandardContext::new();
+    for (expr, expected) in &[
+        ("100 * 5 / 10", "50"),
+        ("'a' ~ 'b'", "ab"),
+        ("[1, 2] | length", "2"),
+        ("{'a': 1 + 1} | key('a')", "2"),
+        ("'x' | replace('x', 'y') | upper", "Y"),
+        ("true && !false", "true"),
+    ] {
+        let template = Templar::global().parse_expression(expr)?;
+        assert!(is_data(&template.root_node()), "{} was not folded", expr);
+        assert_eq!(template.render(&context)?, *expected);
+    }
+    Ok(())
+}
+
+#[test]
+fn fold_merges_content() -> Result<()> {
+    let template = parse("a{{ 'b' }}c{# comment #}{{ x ~ 'd' }}{{ 'e' | upper }}f")?;
+    let parts = parts(&template);
+    assert_eq!(parts.len(), 3, "{:?}", parts);
+    assert!(is_data(&parts[0]) && matches!(parts[1], Node::Value(_)) && is_data(&parts[2]));
+
+    let context = StandardContext::new();
+    context.set_path(&[&"x".into()], "X")?;
+    assert_eq!(template.render(&context)?, "abcXdEf");
+    assert!(is_data(
+        &parse("a{{ 1 + 1 }}{% if true %}b{% end if %}")?.root_node()
+    ));
+    Ok(())
+}
+
+#[test]
+fn fold_skips_context_and_side_effects() -> Result<()> {
+    for template in &[
+        "{{ x + 1 }}",
+        "{{ env('HOME') }}",
+        "{{ x = 1 }}",
+        "{% for i in [1, 2] %}{{ i }}{% end for %}",
+        "{{ 2 is even }}",
+    ] {
+        let parsed = parse(template)?;
+        assert!(!is_data(&parsed.root_node()), "{} was folded", template);
+    }
+    // Errors are left for render to report
+    let template = parse("{{ 'a' * 2 }}")?;
+    assert!(template.render(&StandardContext::new()).is_err());
+    Ok(())
+}
+
+#[test]
+fn fold_pure_functions_once() -> Result<()> {
+    static PURE: AtomicUsize = AtomicUsize::new(0);
+    static IMPURE: AtomicUsize = AtomicUsize::new(0);
+    let mut builder = TemplarBuilder::default();
+    builder
+        .add_pure_function("pure", |_| {
+            PURE.fetch_add(1, Ordering::SeqCst);
+            "p".into()
+        })
+        .add_function("impure", |_| {
+            IMPURE.fetch_add(1, Ordering::SeqCst);
+            "i".into()
+        });
+    let templar = builder.build();
+    let template = templar.parse_template("{{ pure(1) }}{{ impure(1) }}{{ pure(x) }}")?;
+    let context = StandardContext::new();
+    assert_eq!(template.render(&context)?, "pip");
+    assert_eq!(template.render(&context)?, "pip");
+    assert_eq!(PURE.load(Ordering::SeqCst), 3);
+    assert_eq!(IMPURE.load(Ordering::SeqCst), 2);
+    Ok(())
+}
+
+#[test]
+fn fold_leaves_failures_for_render() -> Result<()> {
+    for source in &[
+        "{% if false %}{{ 9223372036854775807 + 1 }}{% end if %}",
+        "{% if false %}{{ (0 - 9223372036854775807 - 1) / -1 }}{% end if %}",
+        "{% if false %}{{ 1 % 0 }}{% end if %}",
+    ] {
+        assert_eq!(parse(source)?.render(&StandardContext::new())?, "");
+    }
+    Ok(())
+}
+
+#[test]
+fn fold_within_limits() -> Result<()> {
+    // Results too large to keep in the template are built when rendering
+    let template = parse("{{ 'a\nb' | indent(100000) }}")?;
+    assert!(!is_data(&template.root_node()));
+    assert_eq!(template.render(&StandardContext::new())?.len(), 100_003);
+
+    let mut limits = RenderLimits::new();
+    limits.set_max_output_bytes(10);
+    let mut builder = TemplarBuilder::default();
+    builder.set_render_limits(limits);
+    let template = builder
+        .build()
+        .parse_template("{{ 'aaaaaaaa' ~ 'bbbbbbbb' }}")?;
+    assert!(!is_data(&template.root_node()));
+    assert!(matches!(
+        template.render(&StandardContext::new()),
+        Err(TemplarError::LimitExceeded(_))
+    ));
+    Ok(())
+}
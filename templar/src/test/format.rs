@@ -0,0 +1,90 @@
+use super::*;
+use crate::ast::FormatOptions;
+
+fn format(template: &str) -> Result<String> {
+    Templar::global().format_template(template, &FormatOptions::default())
+}
+
+fn render(template: &str) -> Result<String> {
+    let context = StandardContext::new();
+    context.set_path(&[&"a".into()], true)?;
+    context.set_path(&[&"name".into()], "world")?;
+    let items: Vec<InnerData> = vec![1.into(), 2.into(), 3.into()];
+    context.set_path(&[&"items".into()], InnerData::Seq(items))?;
+    Templar::global().parse_template(template)?.render(&context)
+}
+
+#[test]
+fn format_normalises_tags() -> Result<()> {
+    assert_eq!(format("{{name|upper}}")?, "{{ name | upper }}");
+    assert_eq!(format("{{   1+2*3 }}")?, "{{ 1 + 2 * 3 }}");
+    assert_eq!(
+        format("{{ join( items ,sep = ', ' ) }}")?,
+        "{{ join(items, sep=', ') }}"
+    );
+    assert_eq!(
+        format("{{ items|map( (x)=>x~'!' ) }}{{[1,2]}}{{{'a':1}}}")?,
+        "{{ items | map(x => x ~ '!') }}{{ [1, 2] }}{{ {'a': 1} }}"
+    );
+    assert_eq!(
+        format("{{ !(a&&b) }}{{ items is   not empty }}{{ .['my key'] }}")?,
+        "{{ !(a && b) }}{{ items is not empty }}{{ .['my key'] }}"
+    );
+    assert_eq!(
+        format("{%if a%}x{%else   if b%}y{%else%}z{%end    if%}")?,
+        "{% if a %}x{% else if b %}y{% else %}z{% end if %}"
+    );
+    assert_eq!(
+        format("{%for i in items%}{{i}}{%end for%}{%scope%}{{i}}{%end scope%}")?,
+        "{% for i in items %}{{ i }}{% end for %}{% scope %}{{ i }}{% end scope %}"
+    );
+    assert_eq!(
+        format("{%filter upper|trim%}x{%end filter%}{%macro m( a,b=1 )%}{%end macro%}")?,
+        "{% filter upper | trim %}x{% end filter %}{% macro m(a, b=1) %}{% end macro %}"
+    );
+    assert_eq!(
+        format("{%include \"x.tmpl\"   ignore missing with {'a':1}  only%}")?,
+        "{% include \"x.tmpl\" ignore missing with {'a': 1} only %}"
+    );
+    Ok(())
+}
+
+#[test]
+fn format_keeps_text_and_trimming() -> Result<()> {
+    let template =
+        "  Hello  \n\t{# a  comment #}\n  {{-name-}}  \n  {%-if a -%}\n  yes\n  {%- end if%} !\n";
+    let formatted = format(template)?;
+    assert_eq!(
+        formatted,
+        "  Hello  \n\t{# a  comment #}\n  {{- name -}}  \n  {%- if a -%}\n  yes\n  {%- end if %} !\n"
+    );
+    assert_eq!(render(&formatted)?, render(template)?);
+    assert_eq!(format(&formatted)?, formatted);
+    Ok(())
+}
+
+#[test]
+fn format_indents_nested_blocks() -> Result<()> {
+    let template =
+        "{%- for i in items -%}\n{%- if i > 1 -%}\n{{- i -}}\n{%- end if -%}\n{%- end for %}\n";
+    let options = FormatOptions::default().indent(2);
+    let formatted = Templar::global().format_template(template, &options)?;
+    assert_eq!(
+        formatted,
+        "{%- for i in items -%}\n  {%- if i > 1 -%}\n    {{- i -}}\n  {%- end if -%}\n{%- end for %}\n"
+    );
+    assert_eq!(render(&formatted)?, render(template)?);
+
+    // Tags that do not trim the whitespace before them are left where they are
+    let template = "{% if a %}\n{{ name }}\n{% end if %}";
+    assert_eq!(
+        Templar::global().format_template(template, &options)?,
+        template
+    );
+    Ok(())
+}
+
+#[test]
+fn format_invalid_template() {
+    assert!(format("{{ name ").is_err());
+}
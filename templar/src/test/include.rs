@@ -0,0 +1,117 @@
+use super::*;
+use std::iter::FromIterator;
+
+fn templar() -> Templar {
+    let mut builder = TemplarBuilder::default();
+    builder.add_loader(MemoryLoader::from_iter(vec![
+        ("header.tmpl", "# {{ title | upper }}"),
+        ("item.tmpl", "- {{ item }}\n"),
+        ("setter.tmpl", "{{ title = 'changed' }}{{ title }}"),
+        (
+            "pages/index.tmpl",
+            "{% include 'nav.tmpl' %}|{% include 'header.tmpl' %}",
+        ),
+        ("pages/nav.tmpl", "pages nav"),
+        ("pages/sub/page.tmpl", "{% include '../nav.tmpl' %}"),
+        ("pages/local.tmpl", "{% include './header.tmpl' %}"),
+        ("loop_a.tmpl", "{% include 'loop_b.tmpl' %}"),
+        ("loop_b.tmpl", "{% include 'loop_a.tmpl' %}"),
+    ]));
+    builder.build()
+}
+
+fn render(templar: &Templar, template: &str) -> Result<String> {
+    let context = StandardContext::new();
+    context.set_path(&[&"title".into()], "hello")?;
+    templar.parse_template(template)?.render(&context)
+}
+
+#[test]
+fn include_basic() -> Result<()> {
+    assert_eq!(
+        render(&templar(), "{% include 'header.tmpl' %}!")?,
+        "# HELLO!"
+    );
+    Ok(())
+}
+
+#[test]
+fn include_sees_loop_variables() -> Result<()> {
+    let result = render(
+        &templar(),
+        "{% for item in [1, 2] %}{% include 'item.tmpl' %}{% end for %}",
+    )?;
+    assert_eq!(result, "- 1\n- 2\n");
+    Ok(())
+}
+
+#[test]
+fn include_with_context() -> Result<()> {
+    let result = render(&templar(), "{% include 'header.tmpl' with context %}")?;
+    assert_eq!(result, "# HELLO");
+    Ok(())
+}
+
+#[test]
+fn include_with_variables() -> Result<()> {
+    let result = render(&templar(), "{% include 'item.tmpl' with {'item': 'x'} %}")?;
+    assert_eq!(result, "- x\n");
+    Ok(())
+}
+
+#[test]
+fn include_only() -> Result<()> {
+    let t = templar();
+    assert_eq!(render(&t, "{% include 'header.tmpl' only %}")?, "# ");
+    assert_eq!(
+        render(&t, "{% include 'header.tmpl' without context %}")?,
+        "# "
+    );
+    let result = render(&t, "{% include 'header.tmpl' with {'title': 'x'} only %}")?;
+    assert_eq!(result, "# X");
+    Ok(())
+}
+
+#[test]
+fn include_does_not_leak_assignments() -> Result<()> {
+    let result = render(&templar(), "{% include 'setter.tmpl' %} {{ title }}")?;
+    assert_eq!(result, "changed hello");
+    Ok(())
+}
+
+#[test]
+fn include_ignore_missing() -> Result<()> {
+    let t = templar();
+    assert_eq!(
+        render(&t, "a{% include 'nope.tmpl' ignore missing %}b")?,
+        "ab"
+    );
+    assert!(matches!(
+        t.parse_template("{% include 'nope.tmpl' %}"),
+        Err(TemplarError::TemplateNotFound(_))
+    ));
+    Ok(())
+}
+
+#[test]
+fn include_relative_to_parent() -> Result<()> {
+    let t = templar();
+    let context = StandardContext::new();
+    context.set_path(&[&"title".into()], "hi")?;
+    let page = t.get_template("pages/index.tmpl")?.render(&context)?;
+    assert_eq!(page, "pages nav|# HI");
+    let nested = t.get_template("pages/sub/page.tmpl")?.render(&context)?;
+    assert_eq!(nested, "pages nav");
+    assert!(matches!(
+        t.get_template("pages/local.tmpl"),
+        Err(TemplarError::TemplateNotFound(_))
+    ));
+    Ok(())
+}
+
+#[test]
+fn include_recursion_is_an_error() {
+    let result = templar().get_template("loop_a.tmpl");
+    assert!(matches!(result, Err(TemplarError::ParseFailure(msg))
+        if msg.contains("loop_a.tmpl -> loop_b.tmpl -> loop_a.tmpl")));
+}
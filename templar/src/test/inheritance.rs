@@ -0,0 +1,129 @@
+use super::*;
+use std::iter::FromIterator;
+
+const BASE: &str = r#"# {% block title %}Default title{% end block %}
+{% block body %}base body{% end block %}
+-- {% block footer %}base footer{% end block %}"#;
+
+fn templar() -> Templar {
+    let mut builder = TemplarBuilder::default();
+    builder.add_loader(MemoryLoader::from_iter(vec![
+        ("base.tmpl", BASE),
+        (
+            "service.tmpl",
+            "{% extends 'base.tmpl' %}{% block title %}{{ name | upper }}{% end block %}",
+        ),
+        (
+            "services/web.tmpl",
+            r#"{% extends '../service.tmpl' %}
+ignored content
+{% macro port(p) %}:{{ p }}{% end macro %}
+{% block body %}web{{ port(8080) }}{% end block %}
+{% block footer %}{{ super() }} + web footer{% end block %}"#,
+        ),
+        (
+            "nested_base.tmpl",
+            "{% block outer %}[{% block inner %}inner{% end block %}]{% end block %}",
+        ),
+        (
+            "nested_child.tmpl",
+            "{% extends 'nested_base.tmpl' %}{% block inner %}{{ super() | upper }}{% end block %}",
+        ),
+        ("loop_a.tmpl", "{% extends 'loop_b.tmpl' %}"),
+        ("loop_b.tmpl", "{% extends 'loop_a.tmpl' %}"),
+        (
+            "late_extends.tmpl",
+            "content{% extends 'base.tmpl' %}",
+        ),
+        (
+            "duplicate.tmpl",
+            "{% extends 'base.tmpl' %}{% block body %}a{% end block %}{% block body %}b{% end block %}",
+        ),
+    ]));
+    builder.build()
+}
+
+fn render(templar: &Templar, name: &str) -> Result<String> {
+    let context = StandardContext::new();
+    context.set_path(&[&"name".into()], "api")?;
+    templar.get_template(name)?.render(&context)
+}
+
+#[test]
+fn base_renders_default_blocks() -> Result<()> {
+    let result = render(&templar(), "base.tmpl")?;
+    assert_eq!(result, "# Default title\nbase body\n-- base footer");
+    Ok(())
+}
+
+#[test]
+fn child_overrides_block() -> Result<()> {
+    let result = render(&templar(), "service.tmpl")?;
+    assert_eq!(result, "# API\nbase body\n-- base footer");
+    Ok(())
+}
+
+#[test]
+fn multi_level_inheritance_with_super() -> Result<()> {
+    let result = render(&templar(), "services/web.tmpl")?;
+    assert_eq!(result, "# API\nweb:8080\n-- base footer + web footer");
+    Ok(())
+}
+
+#[test]
+fn nested_blocks_can_be_overridden() -> Result<()> {
+    assert_eq!(render(&templar(), "nested_child.tmpl")?, "[INNER]");
+    Ok(())
+}
+
+#[test]
+fn extends_from_anonymous_template() -> Result<()> {
+    let t = templar();
+    let template =
+        t.parse_template("\n{% extends 'base.tmpl' %}{% block body %}{{ 1 + 1 }}{% end block %}")?;
+    let result = template.render(&StandardContext::new())?;
+    assert_eq!(result, "# Default title\n2\n-- base footer");
+    Ok(())
+}
+
+#[test]
+fn extends_resolves_to_single_tree() -> Result<()> {
+    // The parent is parsed into the child's tree, so the parent cache is not needed for rendering
+    let t = templar();
+    let template = t.get_template("service.tmpl")?;
+    t.clear_template_cache();
+    assert_eq!(
+        template.render(&StandardContext::new())?,
+        "# \nbase body\n-- base footer"
+    );
+    Ok(())
+}
+
+#[test]
+fn extends_errors() {
+    let t = templar();
+    assert!(
+        matches!(t.get_template("loop_a.tmpl"), Err(TemplarError::ParseFailure(msg))
+        if msg.contains("loop_a.tmpl -> loop_b.tmpl -> loop_a.tmpl"))
+    );
+    assert!(matches!(
+        t.get_template("late_extends.tmpl"),
+        Err(TemplarError::ParseFailure(_))
+    ));
+    assert!(matches!(
+        t.get_template("duplicate.tmpl"),
+        Err(TemplarError::ParseFailure(_))
+    ));
+    assert!(matches!(
+        t.parse_template("{% extends 'missing.tmpl' %}"),
+        Err(TemplarError::TemplateNotFound(_))
+    ));
+}
+
+#[test]
+fn super_outside_block_is_not_a_function() {
+    assert!(matches!(
+        Templar::global().parse_template("{{ super() }}"),
+        Err(TemplarError::FunctionNotFound(name)) if name == "super"
+    ));
+}
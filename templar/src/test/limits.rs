@@ -0,0 +1,208 @@
+use super::*;
+use std::fmt;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::time::{Duration, Instant};
+
+fn limited(limits: &RenderLimits) -> Templar {
+    let mut builder = TemplarBuilder::default();
+    builder.set_render_limits(limits.clone());
+    builder.build()
+}
+
+fn exceeded(result: Result<impl fmt::Debug>) -> String {
+    match result {
+        Err(TemplarError::LimitExceeded(message)) => message,
+        result => panic!("expected a limit to be exceeded, got {:?}", result),
+    }
+}
+
+#[test]
+fn loop_iterations_are_limited() -> Result<()> {
+    let mut limits = RenderLimits::new();
+    limits.set_max_loop_iterations(4);
+    let context = StandardContext::new();
+    let within = Templar::global().parse("{% for i in [1, 2, 3, 4] %}{{ i }}{% end for %}")?;
+    assert_eq!(within.render_with_limits(&context, &limits)?, "1234");
+
+    // Iterations are counted across every loop, including nested ones
+    let nested = Templar::global().parse(
+        "{% for i in [1, 2] %}{% for j in {'a': 1, 'b': 2} %}{{ j.key }}{% end for %}{% end for %}",
+    )?;
+    assert_eq!(
+        exceeded(nested.render_with_limits(&context, &limits)),
+        "More than 4 loop iterations"
+    );
+    Ok(())
+}
+
+#[test]
+fn depth_is_limited() -> Result<()> {
+    let mut limits = RenderLimits::new();
+    let template = Templar::global()
+        .parse("{% if a %}{% if a %}{% if a %}{{ 'deep' }}{% end if %}{% end if %}{% end if %}")?;
+    let context = StandardContext::new();
+    context.set_path(&[&"a".into()], true)?;
+    limits.set_max_depth(100);
+    assert_eq!(template.render_with_limits(&context, &limits)?, "deep");
+    limits.set_max_depth(3);
+    assert_eq!(
+        exceeded(template.render_with_limits(&context, &limits)),
+        "Nested more than 3 levels deep"
+    );
+    Ok(())
+}
+
+#[test]
+fn output_is_limited() -> Result<()> {
+    let mut limits = RenderLimits::new();
+    limits.set_max_output_bytes(10);
+    let templar = limited(&limits);
+    let context = StandardContext::new();
+    let within = templar.parse("{% for i in [1, 2, 3] %}{{ i }}{% end for %}")?;
+    assert_eq!(within.render(&context)?, "123");
+
+    let template = templar.parse("{% for i in [1, 2, 3, 4, 5, 6] %}<{{ i }}>{% end for %}")?;
+    assert_eq!(
+        exceeded(template.render(&context)),
+        "Output longer than 10 bytes"
+    );
+    let mut out = Vec::new();
+    exceeded(template.render_to(&mut out, &context));
+    assert_eq!(out, b"<1><2><3><");
+    Ok(())
+}
+
+#[test]
+fn filter_and_function_results_are_limited() -> Result<()> {
+    let mut limits = RenderLimits::new();
+    limits.set_max_output_bytes(100);
+    let mut builder = TemplarBuilder::default();
+    builder
+        .set_render_limits(limits)
+        .add_function("big", |_| "x".repeat(1000).into());
+    let templar = builder.build();
+    let context = StandardContext::new();
+    context.set_path(&[&"text".into()], "a\nb\nc")?;
+    context.set_path(
+        &[&"items".into()],
+        InnerData::Seq(vec!["x".repeat(60).into(), "y".repeat(60).into()]),
+    )?;
+
+    let start = Instant::now();
+    for source in &[
+        "{{ 'a\nb' | indent(2000000000) }}",
+        "{{ text | indent(2000000000) }}",
+        "{{ text | replace('', '0123456789012345678901234') }}",
+        "{{ items | join }}",
+        "{{ big() | length }}",
+    ] {
+        let template = templar.parse(*source)?;
+        assert_eq!(
+            exceeded(template.render(&context)),
+            "Output longer than 100 bytes",
+            "{}",
+            source
+        );
+    }
+    assert!(start.elapsed() < Duration::from_secs(5));
+    Ok(())
+}
+
+#[test]
+fn limits_end_with_the_render() -> Result<()> {
+    let mut limits = RenderLimits::new();
+    limits.set_max_loop_iterations(1);
+    let context = StandardContext::new();
+    let template = Templar::global().parse("{% for i in [1, 2] %}{{ i }}{% end for %}")?;
+    template.render_with_limits(&context, &Default::default())?;
+    assert!(!crate::execution::limits::is_limited());
+    exceeded(template.render_with_limits(&context, &limits));
+    assert!(!crate::execution::limits::is_limited());
+    limited(&limits).parse("{{ 1 }}")?.render(&context)?;
+    assert!(!crate::execution::limits::is_limited());
+    // Later renders on the thread are not limited, and compiled ones use the machine again
+    assert_eq!(template.render(&context)?, "12");
+    assert_eq!(template.compile().render(&context)?, "12");
+    Ok(())
+}
+
+#[test]
+fn function_calls_are_limited() -> Result<()> {
+    let calls = Arc::new(AtomicUsize::new(0));
+    let counter = calls.clone();
+    let mut builder = TemplarBuilder::default();
+    builder.add_function("count", move |_| {
+        (counter.fetch_add(1, Ordering::SeqCst) as i64).into()
+    });
+    let mut limits = RenderLimits::new();
+    limits.set_max_function_calls(2);
+    builder.set_render_limits(limits);
+    let templar = builder.build();
+    let context = StandardContext::new();
+
+    assert_eq!(
+        templar
+            .parse("{{ count() }}{{ count() }}")?
+            .render(&context)?,
+        "01"
+    );
+    let template = templar.parse("{{ count() }}{{ count() }}{{ count() | default('x') }}")?;
+    // The error replaced by `default` still fails the render
+    assert_eq!(
+        exceeded(template.render(&context)),
+        "More than 2 function calls"
+    );
+    assert_eq!(calls.load(Ordering::SeqCst), 4);
+    Ok(())
+}
+
+#[test]
+fn renders_are_limited_in_time() -> Result<()> {
+    let mut builder = TemplarBuilder::default();
+    builder.add_function("nap", |_| {
+        std::thread::sleep(Duration::from_millis(1));
+        Data::empty()
+    });
+    let mut limits = RenderLimits::new();
+    limits.set_timeout(Duration::from_millis(20));
+    builder.set_render_limits(limits);
+    let items: Vec<InnerData> = (0..1000i64).map(InnerData::from).collect();
+    let context = StandardContext::new();
+    context.set_path(&[&"items".into()], items)?;
+    let template = builder
+        .build()
+        .parse("{% for i in items %}{{ nap() }}{% end for %}")?;
+
+    let started = Instant::now();
+    assert_eq!(exceeded(template.render(&context)), "Took longer than 20ms");
+    assert!(started.elapsed() < Duration::from_millis(500));
+    Ok(())
+}
+
+#[test]
+fn limits_cover_every_way_of_rendering() -> Result<()> {
+    let mut limits = RenderLimits::new();
+    limits.set_max_loop_iterations(2);
+    let templar = limited(&limits);
+    let context = StandardContext::new();
+    context.set_path(
+        &[&"dynamic".into()],
+        templar.parse("{% for i in [1, 2, 3] %}{{ i }}{% end for %}")?,
+    )?;
+    let template = templar.parse("{{ dynamic }}")?;
+    exceeded(template.render(&context));
+    exceeded(template.exec(&context).into_result());
+    exceeded(template.render_fmt(&mut String::new(), &context));
+    exceeded(template.compile().render(&context));
+
+    // Limits given for a render take the place of those of the templar
+    let mut generous = RenderLimits::new();
+    generous.set_max_loop_iterations(3);
+    assert_eq!(template.render_with_limits(&context, &generous)?, "123");
+
+    // Nothing is left behind to limit later renders
+    let unlimited = Templar::global().parse("{{ dynamic }}")?;
+    assert_eq!(unlimited.render(&context)?, "123");
+    assert_eq!(unlimited.compile().render(&context)?, "123");
+    Ok(())
+}
@@ -0,0 +1,106 @@
+use super::*;
+use crate::ast::LintKind;
+
+fn lint(template: &str) -> Result<Vec<(LintKind, String)>> {
+    Ok(Templar::global()
+        .lint(template)?
+        .into_iter()
+        .map(|d| (d.kind, d.span.slice(template).to_string()))
+        .collect())
+}
+
+#[test]
+fn lint_unknown_names() -> Result<()> {
+    assert_eq!(
+        lint("{{ a | shout }}{{ whoami() }}{{ a is odd_ish }}{% filter yell %}{% end filter %}")?,
+        vec![
+            (LintKind::UnknownFilter, "shout".into()),
+            (LintKind::UnknownFunction, "whoami".into()),
+            (LintKind::UnknownTest, "odd_ish".into()),
+            (LintKind::UnknownFilter, "yell".into()),
+        ]
+    );
+    // Macros, caller and super are not reported
+    assert_eq!(
+        lint(
+            "{% macro m(a) %}{{ a }}{{ caller() }}{% end macro %}{% call m(1) %}{% end call %}\
+             {% block b %}{{ super() }}{% end block %}{{ env('HOME') | upper }}"
+        )?,
+        vec![]
+    );
+    Ok(())
+}
+
+#[test]
+fn lint_unused_assignments() -> Result<()> {
+    assert_eq!(
+        lint("{{ x = 1 }}{{ y = 2 }}{{ y }}{{ a.b = 3 }}{{ a.c = 4 }}{{ a.c.d }}")?,
+        vec![
+            (LintKind::UnusedAssignment, "x".into()),
+            (LintKind::UnusedAssignment, "a.b".into()),
+        ]
+    );
+    assert_eq!(lint("{{ x = 1 }}{% include 'other.tmpl' %}")?, vec![]);
+    assert_eq!(lint("{{ x = 1 }}{{ . | json }}")?, vec![]);
+    Ok(())
+}
+
+#[test]
+fn lint_shadowed_loops() -> Result<()> {
+    assert_eq!(
+        lint("{% for i in a %}{% for i in i %}{% end for %}{% end for %}{% for i in b %}{% end for %}")?,
+        vec![(LintKind::ShadowedLoopVariable, "i".into())]
+    );
+    Ok(())
+}
+
+#[test]
+fn lint_constant_conditions() -> Result<()> {
+    assert_eq!(
+        lint("{{ 1 == '1' }}{{ [1] == 1 }}{{ 1 == 2 }}{{ a == 'x' }}")?,
+        vec![
+            (LintKind::ConstantComparison, "1 == '1'".into()),
+            (LintKind::ConstantComparison, "[1] == 1".into()),
+        ]
+    );
+    assert_eq!(
+        lint("{{ 1 != '1' }}{{ 1 != 2 }}{{ 'a' != a }}")?,
+        vec![(LintKind::ConstantComparison, "1 != '1'".into())]
+    );
+    assert_eq!(
+        lint(
+            "{{ 1 < 'a' }}{{ true > 1 }}{{ [1] <= {'a': 1} }}{{ null >= 0 }}{{ 1 < 2 }}{{ 'a' >= 'b' }}"
+        )?,
+        vec![
+            (LintKind::ConstantComparison, "1 < 'a'".into()),
+            (LintKind::ConstantComparison, "true > 1".into()),
+            (LintKind::ConstantComparison, "[1] <= {'a': 1}".into()),
+            (LintKind::ConstantComparison, "null >= 0".into()),
+        ]
+    );
+    assert_eq!(
+        lint("{% if 'yes' %}{% else if 1 %}{% else if true %}{% else if a %}{% end if %}")?,
+        vec![
+            (LintKind::NonBoolCondition, "'yes'".into()),
+            (LintKind::NonBoolCondition, "1".into()),
+        ]
+    );
+    Ok(())
+}
+
+#[test]
+fn lint_unused_macro_params() -> Result<()> {
+    assert_eq!(
+        lint("{% macro m(a, b, c=1) %}{{ a.name }}{% end macro %}{{ m(1, 2) }}")?,
+        vec![
+            (LintKind::UnusedMacroParam, "b".into()),
+            (LintKind::UnusedMacroParam, "c".into()),
+        ]
+    );
+    Ok(())
+}
+
+#[test]
+fn lint_parse_error() {
+    assert!(Templar::global().lint("{{ a ").is_err());
+}
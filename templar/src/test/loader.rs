@@ -0,0 +1,100 @@
+use super::*;
+use std::fs;
+use std::iter::FromIterator;
+
+static EMBEDDED: &[(&str, &str)] = &[("embedded.tmpl", "{{ 'embedded' | upper }}")];
+
+fn with_loader<T: 'static + TemplateLoader>(loader: T) -> Templar {
+    let mut builder = TemplarBuilder::default();
+    builder.add_loader(loader);
+    builder.build()
+}
+
+#[test]
+fn memory_loader() -> Result<()> {
+    let mut loader = MemoryLoader::new();
+    loader.add("one.tmpl", "{{ 1 + 1 }}");
+    let templar = with_loader(loader);
+    let context = StandardContext::new();
+    assert_eq!(templar.get_template("one.tmpl")?.render(&context)?, "2");
+    Ok(())
+}
+
+#[test]
+fn embedded_loader() -> Result<()> {
+    let templar = with_loader(EmbeddedLoader::new(EMBEDDED));
+    let context = StandardContext::new();
+    let result = templar.get_template("embedded.tmpl")?.render(&context)?;
+    assert_eq!(result, "EMBEDDED");
+    Ok(())
+}
+
+#[test]
+fn filesystem_loader() -> Result<()> {
+    let dir = std::env::temp_dir().join(format!("templar-loader-{}", std::process::id()));
+    fs::create_dir_all(dir.join("nested"))?;
+    fs::write(dir.join("nested").join("file.tmpl"), "{{ 'from disk' }}")?;
+    let templar = with_loader(FileSystemLoader::new(&dir));
+    let context = StandardContext::new();
+    let result = templar.get_template("nested/file.tmpl");
+    let missing = templar.get_template("nested/missing.tmpl");
+    fs::remove_dir_all(&dir)?;
+    assert_eq!(result?.render(&context)?, "from disk");
+    assert!(matches!(missing, Err(TemplarError::TemplateNotFound(_))));
+    Ok(())
+}
+
+#[test]
+fn filesystem_loader_stays_in_base() -> Result<()> {
+    let dir = std::env::temp_dir().join(format!("templar-loader-base-{}", std::process::id()));
+    fs::create_dir_all(dir.join("base"))?;
+    fs::write(dir.join("secret.tmpl"), "secret")?;
+    let templar = with_loader(FileSystemLoader::new(dir.join("base")));
+    let outside = dir.join("secret.tmpl");
+    let results = vec![
+        templar.get_template("../secret.tmpl"),
+        templar.get_template("./../secret.tmpl"),
+        templar.get_template(&outside.to_string_lossy()),
+    ];
+    fs::remove_dir_all(&dir)?;
+    for result in results {
+        assert!(matches!(result, Err(TemplarError::TemplateNotFound(_))));
+    }
+    Ok(())
+}
+
+#[test]
+fn loaders_are_tried_in_order() -> Result<()> {
+    let mut builder = TemplarBuilder::default();
+    builder
+        .add_loader(MemoryLoader::from_iter(vec![("a", "first")]))
+        .add_loader(MemoryLoader::from_iter(vec![
+            ("a", "second"),
+            ("b", "second"),
+        ]));
+    let templar = builder.build();
+    let context = StandardContext::new();
+    assert_eq!(templar.get_template("a")?.render(&context)?, "first");
+    assert_eq!(templar.get_template("b")?.render(&context)?, "second");
+    Ok(())
+}
+
+#[test]
+fn templates_are_cached() -> Result<()> {
+    let templar = with_loader(MemoryLoader::from_iter(vec![("a", "{{ 'cached' }}")]));
+    let first = templar.get_template("a")?;
+    let second = templar.get_template("a")?;
+    assert!(Arc::ptr_eq(&first.root_node(), &second.root_node()));
+    templar.clear_template_cache();
+    let third = templar.get_template("a")?;
+    assert!(!Arc::ptr_eq(&first.root_node(), &third.root_node()));
+    Ok(())
+}
+
+#[test]
+fn missing_template() {
+    assert!(matches!(
+        Templar::global().get_template("nope.tmpl"),
+        Err(TemplarError::TemplateNotFound(name)) if name == "nope.tmpl"
+    ));
+}
@@ -0,0 +1,75 @@
+use super::*;
+
+#[test]
+fn paths_are_interned() {
+    let a = ContextPath::new(vec!["server".into(), "host".into()]);
+    let b = ContextPath::new(vec!["server".into(), "host".into()]);
+    let c = ContextPath::new(vec!["server".into(), "port".into()]);
+    assert_eq!(a.id(), b.id());
+    assert_ne!(a.id(), c.id());
+    assert_eq!(
+        &*a,
+        &[InnerData::from("server"), InnerData::from("host")][..]
+    );
+}
+
+#[test]
+fn unused_paths_are_released() {
+    let keys = || vec![InnerData::from("released"), InnerData::from("path")];
+    let dropped = ContextPath::new(keys()).id();
+    let a = ContextPath::new(keys());
+    let b = ContextPath::new(keys());
+    assert_ne!(a.id(), dropped);
+    assert_eq!(a.id(), b.id());
+}
+
+#[test]
+fn cached_lookups_see_changes() -> Result<()> {
+    let context = StandardContext::new();
+    context.set_path(&[&"server".into(), &"host".into()], "one")?;
+    let template = Templar::global().parse("{{ server.host }}")?;
+    assert_eq!(template.render(&context)?, "one");
+    assert_eq!(template.render(&context)?, "one");
+    context.set_path(&[&"server".into(), &"host".into()], "two")?;
+    assert_eq!(template.render(&context)?, "two");
+    context.set_path(&[&"server".into()], InnerData::Null)?;
+    assert_eq!(template.render(&context)?, "null");
+    Ok(())
+}
+
+#[test]
+fn cached_lookups_see_assignments() -> Result<()> {
+    let context = StandardContext::new();
+    let template = Templar::global().parse("{{ a.b }}{{ a.b = 2 }}{{ a.b }}")?;
+    assert_eq!(template.render(&context)?, "2");
+    assert_eq!(template.render(&context)?, "22");
+    Ok(())
+}
+
+#[test]
+fn dynamic_values_are_not_cached() -> Result<()> {
+    let context = StandardContext::new();
+    context.set_path(&[&"name".into()], "one")?;
+    context.set_path(
+        &[&"greeting".into()],
+        Templar::global().parse("hello {{ name }}")?,
+    )?;
+    let template = Templar::global().parse("{{ greeting }}")?;
+    assert_eq!(template.render(&context)?, "hello one");
+    let scoped =
+        Templar::global().parse("{% scope %}{{ name = 'two' }}{{ greeting }}{% end scope %}")?;
+    assert_eq!(scoped.render(&context)?, "hello two");
+    assert_eq!(template.render(&context)?, "hello one");
+    Ok(())
+}
+
+#[test]
+fn scopes_shadow_cached_lookups() -> Result<()> {
+    let context = StandardContext::new();
+    context.set_path(&[&"x".into()], 1i64)?;
+    let template =
+        Templar::global().parse("{{ x }}{% for x in [2, 3] %}{{ x }}{% end for %}{{ x }}")?;
+    assert_eq!(template.render(&context)?, "1231");
+    assert_eq!(template.render(&context)?, "1231");
+    Ok(())
+}
@@ -0,0 +1,146 @@
+use super::*;
+use crate::context::{ContextMapValue, Memo};
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+/// A templar with a `count()` function that counts how often it was called
+fn counting() -> (Templar, Arc<AtomicUsize>) {
+    let calls = Arc::new(AtomicUsize::new(0));
+    let counter = calls.clone();
+    let mut builder = TemplarBuilder::default();
+    builder.add_function("count", move |_| {
+        (counter.fetch_add(1, Ordering::SeqCst) as i64).into()
+    });
+    (builder.build(), calls)
+}
+
+fn set(context: &StandardContext, path: &[&str], value: impl Into<ContextMapValue>) -> Result<()> {
+    let path: Vec<InnerData> = path.iter().map(|p| InnerData::from(*p)).collect();
+    let refs: Vec<&InnerData> = path.iter().collect();
+    context.set_path(&refs, value)
+}
+
+#[test]
+fn memoized_values_run_once() -> Result<()> {
+    let (templar, calls) = counting();
+    let context = StandardContext::memoized();
+    set(&context, &["value"], templar.parse("{{ count() }}")?)?;
+    let template = templar.parse("{{ value }}{{ value }}{{ value }}")?;
+    assert_eq!(template.render(&context)?, "000");
+    assert_eq!(template.render(&context)?, "000");
+    assert_eq!(calls.load(Ordering::SeqCst), 1);
+    Ok(())
+}
+
+#[test]
+fn values_run_every_time_without_memoizing() -> Result<()> {
+    let (templar, calls) = counting();
+    let context = StandardContext::new();
+    set(&context, &["value"], templar.parse("{{ count() }}")?)?;
+    let template = templar.parse("{{ value }}{{ value }}")?;
+    assert_eq!(template.render(&context)?, "01");
+    assert_eq!(calls.load(Ordering::SeqCst), 2);
+    Ok(())
+}
+
+#[test]
+fn memoized_values_follow_what_they_read() -> Result<()> {
+    let (templar, calls) = counting();
+    let context = StandardContext::memoized();
+    set(&context, &["user", "name"], "Al")?;
+    set(
+        &context,
+        &["greeting"],
+        templar.parse("{{ count() }} {{ user.name }}")?,
+    )?;
+    let template = templar.parse("{{ greeting }}")?;
+    assert_eq!(template.render(&context)?, "0 Al");
+
+    // Writes elsewhere, or beside the paths read, keep the result
+    set(&context, &["other"], "x")?;
+    set(&context, &["user", "age"], 5i64)?;
+    assert_eq!(template.render(&context)?, "0 Al");
+
+    // Writing the path read, or anything above it, runs the value again
+    set(&context, &["user", "name"], "Bo")?;
+    assert_eq!(template.render(&context)?, "1 Bo");
+    set(&context, &["user"], InnerData::Null)?;
+    assert_eq!(template.render(&context)?, "2 null");
+    assert_eq!(calls.load(Ordering::SeqCst), 3);
+    Ok(())
+}
+
+#[test]
+fn memoized_values_follow_the_values_they_read() -> Result<()> {
+    let (templar, calls) = counting();
+    let context = StandardContext::memoized();
+    set(&context, &["name"], "Al")?;
+    set(&context, &["inner"], templar.parse("{{ name }}")?)?;
+    set(
+        &context,
+        &["outer"],
+        templar.parse("{{ count() }} {{ inner }}")?,
+    )?;
+    let template = templar.parse("{{ outer }}")?;
+    assert_eq!(template.render(&context)?, "0 Al");
+    assert_eq!(template.render(&context)?, "0 Al");
+    set(&context, &["name"], "Bo")?;
+    assert_eq!(template.render(&context)?, "1 Bo");
+
+    // Replacing a value drops its result
+    set(&context, &["inner"], templar.parse("{{ name }}!")?)?;
+    assert_eq!(template.render(&context)?, "2 Bo!");
+    assert_eq!(calls.load(Ordering::SeqCst), 3);
+    Ok(())
+}
+
+#[test]
+fn volatile_values_always_run() -> Result<()> {
+    let (templar, calls) = counting();
+    let context = StandardContext::memoized();
+    set(
+        &context,
+        &["value"],
+        templar.parse("{{ volatile(count()) }}")?,
+    )?;
+    set(&context, &["outer"], templar.parse("<{{ value }}>")?)?;
+    let template = templar.parse("{{ value }}{{ outer }}")?;
+    assert_eq!(template.render(&context)?, "0<1>");
+    assert_eq!(template.render(&context)?, "2<3>");
+    assert_eq!(calls.load(Ordering::SeqCst), 4);
+    Ok(())
+}
+
+#[test]
+fn memoized_values_see_scopes() -> Result<()> {
+    let (templar, _) = counting();
+    let context = StandardContext::memoized();
+    set(&context, &["name"], "root")?;
+    set(&context, &["greeting"], templar.parse("hello {{ name }}")?)?;
+    let template = templar.parse(
+        "{{ greeting }},{% for name in ['a', 'b'] %}{{ greeting }},{% end for %}{{ greeting }}",
+    )?;
+    let expected = "hello root,hello a,hello b,hello root";
+    assert_eq!(template.render(&context)?, expected);
+    assert_eq!(template.render(&context)?, expected);
+    Ok(())
+}
+
+#[test]
+fn results_are_not_kept_past_a_write_made_while_executing() -> Result<()> {
+    // Stands in for another thread writing to the context while the value executes
+    let memo = Arc::new(Memo::default());
+    let calls = Arc::new(AtomicUsize::new(0));
+    let (writer, counter) = (memo.clone(), calls.clone());
+    let mut builder = TemplarBuilder::default();
+    builder.add_function("write", move |_| {
+        counter.fetch_add(1, Ordering::SeqCst);
+        writer.invalidate(&[&"other".into()]);
+        "written".into()
+    });
+    let node = builder.build().parse("{{ write() }}")?.root_node();
+    let context = StandardContext::new();
+    assert_eq!(memo.exec(&node, &context).render()?, "written");
+    assert_eq!(memo.exec(&node, &context).render()?, "written");
+    assert_eq!(calls.load(Ordering::SeqCst), 2);
+    Ok(())
+}
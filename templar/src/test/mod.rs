@@ -1,6 +1,23 @@
+mod ast;
+mod cycles;
 mod dynamic_context;
 mod expressions;
+mod fold;
+mod format;
+mod include;
+mod inheritance;
+mod limits;
+mod lint;
+mod loader;
+mod lookup_cache;
+mod memo;
 mod parsing;
+mod recover;
+mod references;
+mod sandbox;
 mod shared_context_safe;
+mod stream;
+mod templates;
+mod vm;
 
 use crate::*;
@@ -0,0 +1,130 @@
+use super::*;
+use crate::ast::{Ast, SyntaxError, TemplateNode};
+
+fn recover(template: &str) -> (Ast, Vec<SyntaxError>) {
+    Templar::global().parse_ast_recovering(template)
+}
+
+/// The message and source of every error in a template
+fn errors(template: &str) -> Vec<(String, &str)> {
+    recover(template)
+        .1
+        .into_iter()
+        .map(|e| (e.message, e.span.slice(template)))
+        .collect()
+}
+
+fn owned<'a>(errors: &[(&str, &'a str)]) -> Vec<(String, &'a str)> {
+    errors.iter().map(|(m, s)| (m.to_string(), *s)).collect()
+}
+
+#[test]
+fn recover_valid_template() -> Result<()> {
+    let template =
+        "{% if a %}{{ b | upper }}{% else %}{% for x in y %}{{ x }}{% end for %}{% end if %}";
+    let (ast, errors) = recover(template);
+    assert_eq!(errors, vec![]);
+    let expected = Templar::global().parse_ast(template)?;
+    assert_eq!(format!("{:?}", ast), format!("{:?}", expected));
+    Ok(())
+}
+
+#[test]
+fn recover_reports_every_error() {
+    let template = "{{ a + }}\n{% for x in %}{{ x }}{% end for %}\n{{ 'ok' }}{% if a %}";
+    assert_eq!(
+        errors(template),
+        owned(&[
+            ("Invalid output tag, expected an expression", "{{ a + }}"),
+            (
+                "Invalid `for` tag, expected an expression",
+                "{% for x in %}"
+            ),
+            ("`if` block is never closed", "{% if a %}"),
+        ])
+    );
+}
+
+#[test]
+fn recover_keeps_the_rest_of_the_tree() {
+    let template = "{{ a + }}\n{% for x in %}{{ x }}{% end for %}\n{{ 'ok' }}";
+    let (ast, _) = recover(template);
+    let nodes: Vec<(&str, &str)> = ast
+        .nodes
+        .iter()
+        .map(|node| {
+            let kind = match node {
+                TemplateNode::Error(_) => "error",
+                TemplateNode::Output(_) => "output",
+                TemplateNode::Text(_) => "text",
+                _ => "other",
+            };
+            (kind, node.span().slice(template))
+        })
+        .collect();
+    assert_eq!(
+        nodes,
+        vec![
+            ("error", "{{ a + }}"),
+            ("text", "\n"),
+            ("error", "{% for x in %}"),
+            ("output", "{{ x }}"),
+            ("error", "{% end for %}"),
+            ("text", "\n"),
+            ("output", "{{ 'ok' }}"),
+        ]
+    );
+}
+
+#[test]
+fn recover_unmatched_tags() {
+    assert_eq!(
+        errors("{% end if %}{% if a %}{% for x in y %}{% end if %}{% else %}"),
+        owned(&[
+            ("Unexpected `end if` tag", "{% end if %}"),
+            ("`for` block is never closed", "{% for x in y %}"),
+            (
+                "Unexpected `else` tag outside of an `if` block",
+                "{% else %}"
+            ),
+        ])
+    );
+    assert_eq!(
+        errors("{% set x = 1 %}{% %}{{ a"),
+        owned(&[
+            ("Unknown tag `set`", "{% set x = 1 %}"),
+            ("Expected a tag name", "{% %}"),
+            ("Tag is never closed", "{{ a"),
+        ])
+    );
+}
+
+#[test]
+fn recover_nested_errors() {
+    let template = "{% if a %}{{ b | }}{% else %}{% macro m( %}{% end macro %}{% end if %}";
+    assert_eq!(
+        errors(template),
+        owned(&[
+            ("Invalid output tag, expected a name", "{{ b | }}"),
+            ("Invalid `macro` tag, expected a name", "{% macro m( %}"),
+        ])
+    );
+    let (ast, _) = recover(template);
+    match &ast.nodes[0] {
+        TemplateNode::If(block) => {
+            assert!(matches!(block.branches[0].body[0], TemplateNode::Error(_)));
+            let otherwise = &block.otherwise.as_ref().unwrap().body;
+            assert!(matches!(otherwise[0], TemplateNode::Error(_)));
+            assert!(matches!(otherwise[1], TemplateNode::Error(_)));
+        }
+        other => panic!("Expected an if block, found {:?}", other),
+    }
+}
+
+#[test]
+fn recover_skips_strings_and_maps() {
+    assert_eq!(
+        errors("{{ {'a': {'b': 1}} }}{{ '}}' }}{{ a | }}"),
+        owned(&[("Invalid output tag, expected a name", "{{ a | }}")])
+    );
+}
@@ -0,0 +1,68 @@
+use super::*;
+
+fn parse(template: &str) -> Result<Template> {
+    Templar::global().parse_template(template)
+}
+
+#[test]
+fn referenced_paths() -> Result<()> {
+    let t =
+        parse("{{ server.host }}:{{ server.port }} {{ server.host | upper }} {{ .['my key'] }}")?;
+    assert_eq!(
+        t.referenced_paths(),
+        vec!["my key", "server.host", "server.port"]
+    );
+    Ok(())
+}
+
+#[test]
+fn referenced_paths_exclude_locals() -> Result<()> {
+    let t = parse(
+        "{% for user in users %}{{ user.name }}{{ title }}{% end for %}\
+         {{ count = 1 }}{{ count }}\
+         {{ items | map(x => x.id ~ sep) }}\
+         {% macro field(name, value=fallback) %}{{ name }}={{ value }}{{ caller() }}{% end macro %}\
+         {% call field('a') %}{{ body }}{% end call %}",
+    )?;
+    assert_eq!(
+        t.referenced_paths(),
+        vec!["body", "fallback", "items", "sep", "title", "users"]
+    );
+    Ok(())
+}
+
+#[test]
+fn referenced_paths_before_set() -> Result<()> {
+    let t = parse("{{ x }}{{ x = 1 }}{% for i in [1] %}{{ y = i }}{% end for %}{{ y }}")?;
+    assert_eq!(t.referenced_paths(), vec!["x", "y"]);
+    Ok(())
+}
+
+#[test]
+fn referenced_filters_and_functions() -> Result<()> {
+    let t = parse(
+        "{% macro m() %}{{ env('HOME') }}{% end macro %}{{ m() }}\
+         {% filter upper %}{{ script('ls') | trim | trim }}{% end filter %}\
+         {{ 1 is even }}",
+    )?;
+    assert_eq!(t.referenced_filters(), vec!["trim", "upper"]);
+    assert_eq!(t.referenced_functions(), vec!["env", "script"]);
+    Ok(())
+}
+
+#[test]
+fn referenced_in_includes() -> Result<()> {
+    let mut loader = MemoryLoader::new();
+    loader
+        .add("part.tmpl", "{{ title }}{{ site.name | lower }}")
+        .add("only.tmpl", "{{ title }}{{ other | upper }}");
+    let mut builder = TemplarBuilder::default();
+    builder.add_loader(loader);
+    let templar = builder.build();
+    let t = templar.parse_template("{% include 'part.tmpl' with { 'title': name } %}")?;
+    assert_eq!(t.referenced_paths(), vec!["name", "site.name"]);
+    let t = templar.parse_template("{% include 'only.tmpl' with { 'title': name } only %}")?;
+    assert_eq!(t.referenced_paths(), vec!["name"]);
+    assert_eq!(t.referenced_filters(), vec!["upper"]);
+    Ok(())
+}
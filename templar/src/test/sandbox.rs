@@ -0,0 +1,166 @@
+use super::*;
+use std::fs;
+
+fn sandboxed(policy: SandboxPolicy) -> Templar {
+    let mut builder = TemplarBuilder::default();
+    builder.set_sandbox_policy(policy);
+    builder.build()
+}
+
+fn denied(templar: &Templar, source: &str) -> Result<String> {
+    match templar.parse(source)?.render(&StandardContext::new()) {
+        Err(TemplarError::PermissionDenied(message)) => Ok(message),
+        result => panic!(
+            "{:?}: expected permission to be denied, got {:?}",
+            source, result
+        ),
+    }
+}
+
+fn render(templar: &Templar, source: &str) -> Result<String> {
+    templar.parse(source)?.render(&StandardContext::new())
+}
+
+#[test]
+fn trusted_allows_everything() -> Result<()> {
+    for templar in &[Templar::default(), sandboxed(SandboxPolicy::trusted())] {
+        assert_eq!(
+            render(templar, "{{ command('echo', '-n', 'hi') | key('stdout') }}")?,
+            "hi"
+        );
+        assert_eq!(
+            render(templar, "{{ script('echo -n hi') | key('stdout') }}")?,
+            "hi"
+        );
+        assert!(!render(templar, "{{ env('PATH') }}")?.is_empty());
+    }
+    Ok(())
+}
+
+#[test]
+fn restricted_allows_what_is_added() -> Result<()> {
+    let restricted = sandboxed(SandboxPolicy::restricted());
+    assert_eq!(
+        denied(&restricted, "{{ command('echo', ['hi']) }}")?,
+        "Command 'echo' is not allowed by the sandbox policy"
+    );
+    assert_eq!(
+        denied(&restricted, "{{ script('echo hi') }}")?,
+        "Scripts are not allowed by the sandbox policy"
+    );
+    assert_eq!(
+        denied(&restricted, "{{ env(env_var='PATH') }}")?,
+        "Environment variable 'PATH' is not allowed by the sandbox policy"
+    );
+
+    let mut policy = SandboxPolicy::restricted();
+    policy.allow_command("echo").allow_env("PATH");
+    let templar = sandboxed(policy);
+    assert_eq!(
+        render(
+            &templar,
+            "{{ command('echo', '-n', 'hi') | key('stdout') }}"
+        )?,
+        "hi"
+    );
+    assert!(!render(&templar, "{{ env('PATH') }}")?.is_empty());
+    assert_eq!(
+        denied(&templar, "{{ env('HOME') }}")?,
+        "Environment variable 'HOME' is not allowed by the sandbox policy"
+    );
+    // Commands are matched exactly, and `env` options or variables can't be slipped in first
+    denied(&templar, "{{ command('/bin/echo', ['hi']) }}")?;
+    denied(&templar, "{{ command('X=1', ['echo', 'hi']) }}")?;
+    denied(&templar, "{{ script('echo hi') }}")?;
+
+    let mut policy = SandboxPolicy::restricted();
+    policy.allow_command("sh");
+    assert_eq!(
+        render(
+            &sandboxed(policy),
+            "{{ script('echo -n hi') | key('stdout') }}"
+        )?,
+        "hi"
+    );
+    Ok(())
+}
+
+#[test]
+fn files_are_kept_to_allowed_dirs() -> Result<()> {
+    let dir = std::env::temp_dir().join(format!("templar-sandbox-{}", std::process::id()));
+    let (allowed, outside) = (dir.join("allowed"), dir.join("outside"));
+    fs::create_dir_all(&allowed)?;
+    fs::create_dir_all(&outside)?;
+    fs::write(allowed.join("in.txt"), "inside")?;
+    fs::write(outside.join("out.txt"), "outside")?;
+    #[cfg(unix)]
+    std::os::unix::fs::symlink(outside.join("out.txt"), allowed.join("link.txt"))?;
+
+    let mut policy = SandboxPolicy::restricted();
+    policy.allow_dir(&allowed);
+    let templar = sandboxed(policy);
+    let file = |name: &str| format!("{{{{ file('{}') }}}}", allowed.join(name).display());
+    let result = (|| {
+        assert_eq!(render(&templar, &file("in.txt"))?, "inside");
+        let named = format!(
+            "{{{{ file(path='{}') }}}}",
+            allowed.join("in.txt").display()
+        );
+        assert_eq!(render(&templar, &named)?, "inside");
+        assert_eq!(
+            denied(&templar, &file("../outside/out.txt"))?,
+            format!(
+                "File '{}' is not within the directories allowed by the sandbox policy",
+                allowed.join("../outside/out.txt").display()
+            )
+        );
+        // Missing files are reported the same as files elsewhere
+        denied(&templar, &file("missing.txt"))?;
+        #[cfg(unix)]
+        denied(&templar, &file("link.txt"))?;
+        Ok(())
+    })();
+    fs::remove_dir_all(&dir)?;
+    result
+}
+
+#[test]
+fn pure_allows_nothing() -> Result<()> {
+    let mut policy = SandboxPolicy::pure();
+    policy
+        .allow_command("echo")
+        .allow_command("sh")
+        .allow_env("PATH")
+        .allow_dir(std::env::temp_dir());
+    let templar = sandboxed(policy);
+    denied(&templar, "{{ command('echo', ['hi']) }}")?;
+    denied(&templar, "{{ script('echo hi') }}")?;
+    denied(&templar, "{{ env('PATH') }}")?;
+    let file = std::env::temp_dir().join(format!("templar-pure-{}", std::process::id()));
+    fs::write(&file, "data")?;
+    let result = denied(&templar, &format!("{{{{ file('{}') }}}}", file.display()));
+    fs::remove_file(&file)?;
+    result.map(|_| ())
+}
+
+#[test]
+fn policies_never_add_functions() -> Result<()> {
+    let missing = |mut builder: TemplarBuilder| {
+        builder.set_sandbox_policy(SandboxPolicy::trusted());
+        let result = builder.build().parse("{{ script('echo hi') }}");
+        assert!(matches!(result, Err(TemplarError::FunctionNotFound(name)) if name == "script"));
+    };
+    missing(TemplarBuilder::new());
+    let mut builder = TemplarBuilder::default();
+    builder.remove_function("script");
+    missing(builder);
+
+    // Custom functions registered under the same names are left alone
+    let mut builder = TemplarBuilder::default();
+    builder.add_function("command", |_| "custom".into());
+    builder.set_sandbox_policy(SandboxPolicy::pure());
+    let templar = builder.build();
+    assert_eq!(render(&templar, "{{ command('echo', ['hi']) }}")?, "custom");
+    denied(&templar, "{{ script('echo hi') }}")?;
+    Ok(())
+}
@@ -2,6 +2,7 @@ use super::*;
 use std::any::Any;
 
 #[derive(Debug)]
+#[allow(dead_code)]
 struct SendSyncContainer(Box<dyn Any + Send + Sync>);
 
 // This will simply fail to compile if the test fails, so we just always pass.
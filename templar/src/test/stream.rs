@@ -0,0 +1,91 @@
+use super::vm::{context, templar, TEMPLATES};
+use super::*;
+use std::io;
+
+/// A writer that accepts a limited number of bytes before failing
+struct Limited(Vec<u8>, usize);
+
+impl io::Write for Limited {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        if self.0.len() + buf.len() > self.1 {
+            return Err(io::Error::other("disk full"));
+        }
+        self.0.extend_from_slice(buf);
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+#[test]
+fn streaming_matches_render() -> Result<()> {
+    for (policy, strict_bool) in &[
+        (UndefinedPolicy::Lenient, false),
+        (UndefinedPolicy::Strict, false),
+        (UndefinedPolicy::Debug, false),
+        (UndefinedPolicy::Lenient, true),
+    ] {
+        let templar = templar(*policy, *strict_bool);
+        for source in TEMPLATES.iter() {
+            let template = templar.parse_template(source)?;
+            let (render_ctx, stream_ctx) = (context()?, context()?);
+            let expected = template.render(&render_ctx);
+            let mut out = Vec::new();
+            let actual = template.render_to(&mut out, &stream_ctx);
+            match (expected, actual) {
+                (Ok(expected), Ok(())) => {
+                    assert_eq!(String::from_utf8(out).unwrap(), expected, "{:?}", source)
+                }
+                (Err(expected), Err(actual)) => assert_eq!(
+                    format!("{:?}", actual),
+                    format!("{:?}", expected),
+                    "{:?}",
+                    source
+                ),
+                (expected, actual) => panic!("{:?}: {:?} != {:?}", source, actual, expected),
+            }
+            assert_eq!(
+                format!("{:?}", stream_ctx.get()),
+                format!("{:?}", render_ctx.get()),
+                "{:?} left a different context",
+                source
+            );
+        }
+    }
+    Ok(())
+}
+
+#[test]
+fn streaming_into_string() -> Result<()> {
+    let template = Templar::global().parse("{% for i in [1, 2] %}<{{ i }}>{% end for %}")?;
+    let mut out = String::from("items: ");
+    template.render_fmt(&mut out, &StandardContext::new())?;
+    assert_eq!(out, "items: <1><2>");
+    Ok(())
+}
+
+#[test]
+fn streaming_keeps_output_before_an_error() -> Result<()> {
+    let template =
+        Templar::global().parse("before {% for i in [1, 'a'] %}{{ i + 1 }} {% end for %}")?;
+    let mut out = String::new();
+    assert!(template
+        .render_fmt(&mut out, &StandardContext::new())
+        .is_err());
+    assert_eq!(out, "before 2 ");
+    Ok(())
+}
+
+#[test]
+fn streaming_reports_write_errors() -> Result<()> {
+    let template = Templar::global().parse("{% for i in [1, 2, 3] %}{{ i }}{% end for %}")?;
+    let mut out = Limited(vec![], 2);
+    match template.render_to(&mut out, &StandardContext::new()) {
+        Err(TemplarError::IO(message)) => assert_eq!(message, "disk full"),
+        result => panic!("expected a write error, got {:?}", result),
+    }
+    assert_eq!(out.0, b"12");
+    Ok(())
+}
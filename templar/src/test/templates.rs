@@ -0,0 +1,216 @@
+use super::*;
+
+macro_rules! test_templates {
+    (! $name:ident : $tpl:literal ; $( $tail:tt )*) => {
+        #[test]
+        fn $name() -> Result<()> {
+            let tmpl = Templar::global().parse_template($tpl)?;
+            let context = StandardContext::new();
+            assert!(tmpl.render(&context).is_err());
+            Ok(())
+        }
+        test_templates! {
+            $( $tail )*
+        }
+    };
+    ($name:ident : $tpl:literal == $res:literal ; $( $tail:tt )*) => {
+        #[test]
+        fn $name() -> Result<()> {
+            let tmpl = Templar::global().parse_template($tpl)?;
+            let context = StandardContext::new();
+            assert_eq!(tmpl.render(&context)?, $res, "{} template '{}'", stringify!($name), $tpl);
+            Ok(())
+        }
+        test_templates! {
+            $( $tail )*
+        }
+    };
+    () => {};
+}
+
+test_templates! {
+    // filter blocks
+    filter_block_upper: "{% filter upper %}hello {{ 'world' }}{% end filter %}" == "HELLO WORLD";
+    filter_block_chain: "{% filter replace('l', 'L') | upper | trim %} hello {% end filter %}" == "HELLO";
+    filter_block_indent: "- {% filter indent(2) %}a: 1\nb: 2\n\nc: 3{% end filter %}" == "- a: 1\n  b: 2\n\n  c: 3";
+    filter_block_nested: "{% filter upper %}a{% filter trim %}  b  {% end filter %}c{% end filter %}" == "ABC";
+    filter_block_whitespace: "x\n{%- filter upper -%}\n y \n{%- end filter %}" == "xY";
+    trim_adjacent_tags: "{{- 'a' -}}\n{%- if true -%}\n b {#- c -#}\n{%- end if %}" == "ab";
+    !filter_block_error: "{% filter upper %}{{ 'a' + 1 }}{% end filter %}";
+
+    // macros
+    macro_basic: "{% macro greet(name) %}Hello {{ name }}!{% end macro %}{{ greet('Bob') }}" == "Hello Bob!";
+    macro_defaults: "{% macro kv(k, v='none') %}{{ k }}={{ v }}{% end macro %}{{ kv('a', 1) }},{{ kv('b') }}" == "a=1,b=none";
+    macro_default_refers_to_param: "{% macro m(a, b=a ~ '!') %}{{ b }}{% end macro %}{{ m('hi') }}" == "hi!";
+    macro_in_expression: "{% macro double(n) %}{{ n * 2 }}{% end macro %}{{ double(4) | upper }}" == "8";
+    macro_own_scope: "{% macro m(v) %}{{ x = v }}{{ x }}{% end macro %}{{ x = 'outer' }}{{ m('inner') }} {{ x }}" == "inner outer";
+    macro_calls_macro: "{% macro a(v) %}<{{ v }}>{% end macro %}{% macro b(v) %}{{ a(v) }}{{ a(v) }}{% end macro %}{{ b(1) }}" == "<1><1>";
+    macro_in_loop: "{% macro li(v) %}* {{ v }}\n{% end macro %}{% for i in [1, 2] %}{{ li(i) }}{% end for %}" == "* 1\n* 2\n";
+    macro_call_block: "{% macro wrap(tag) %}<{{ tag }}>{{ caller() }}</{{ tag }}>{% end macro %}{% call wrap('b') %}bold {{ 'text' }}{% end call %}" == "<b>bold text</b>";
+    macro_call_block_sees_outer: "{% macro twice() %}{{ caller() }}{{ caller() }}{% end macro %}{{ v = 'x' }}{% call twice() %}{{ v }}{% end call %}" == "xx";
+    macro_call_block_uses_calling_scope: "{% macro wrap(v) %}[{{ caller() }}]{% end macro %}{{ v = 'outer' }}{% call wrap('inner') %}{{ v }}{% end call %}" == "[outer]";
+    macro_call_block_calls_macro: "{% macro b(v) %}<{{ v }}>{% end macro %}{% macro wrap() %}[{{ caller() }}]{% end macro %}{{ v = 1 }}{% call wrap() %}{{ b(v) }}{% end call %}" == "[<1>]";
+    !macro_caller_not_inherited: "{% macro inner() %}{{ caller() }}{% end macro %}{% macro outer() %}{{ inner() }}{% end macro %}{% call outer() %}x{% end call %}";
+    !macro_too_many_args: "{% macro m(a) %}{{ a }}{% end macro %}{{ m(1, 2) }}";
+    !macro_missing_arg: "{% macro m(a, b) %}{{ a }}{% end macro %}{{ m(1) }}";
+
+    // truthiness
+    if_truthiness: "{% if '' %}a{% end if %}{% if 0 %}b{% end if %}{% if [] %}c{% end if %}{% if {} %}d{% end if %}{% if null %}e{% end if %}{% if missing %}f{% end if %}{% if 'x' %}g{% end if %}{% if [0] %}h{% end if %}" == "gh";
+    if_not: "{% if !items %}empty{% else %}full{% end if %}" == "empty";
+    if_and_or: "{% if missing && missing.key %}a{% else if missing || 'b' %}b{% end if %}" == "b";
+    !if_propagates_error: "{% if 'a' + 1 %}a{% end if %}";
+
+    // tests
+    test_defined: "{{ missing is defined }},{{ missing.nested.path is undefined }},{{ (x = 1) }}{{ x is defined }}" == "false,true,true";
+    test_not: "{{ missing is not defined }},{{ 5 is not even }}" == "true,true";
+    test_types: "{{ null is none }},{{ 1 is number }},{{ 1 is integer }},{{ 1 is float }},{{ 'a' is string }},{{ true is boolean }}" == "true,true,true,false,true,true";
+    test_collections: "{{ {'a': 1} is mapping }},{{ [1] is sequence }},{{ 'ab' is iterable }},{{ 1 is iterable }}" == "true,true,true,false";
+    test_empty: "{{ '' is empty }},{{ [] is empty }},{{ {} is empty }},{{ null is empty }},{{ missing is empty }},{{ [0] is empty }}" == "true,true,true,true,true,false";
+    test_numbers: "{{ 4 is even }},{{ 3 is odd }},{{ 10 is divisibleby(5) }},{{ 10 is divisibleby(3) }}" == "true,true,true,false";
+    test_applies_to_expression: "{{ 2 + 3 is odd }},{{ [1, 2] | length is even }}" == "true,true";
+    test_in_if: "{% if missing is defined %}yes{% else %}no{% end if %}" == "no";
+    !test_even_requires_integer: "{{ 'a' is even }}";
+    !test_divisibleby_zero: "{{ 4 is divisibleby(0) }}";
+    test_divisibleby_overflow: "{{ (0 - 9223372036854775807 - 1) is divisibleby(-1) }}" == "true";
+    lambda_captures_scope: "{% for min in [1] %}{{ [1, 2, 3] | select(x => x > min) | join(',') }}{% end for %}" == "2,3";
+    lambda_captures_nested_scope: "{{ offset = 10 }}{{ [[1], [2]] | map(xs => xs | map(x => x + offset) | index(0)) | join(',') }}" == "11,12";
+    lambda_param_shadows_context: "{{ x = 'outer' }}{{ [1] | map(x => x) | join }}{{ x }}" == "1outer";
+    lambda_cannot_modify_captured: "{{ y = 1 }}{{ [1] | map(x => (y = x) ~ y) | join }},{{ y }}" == "1,1";
+    lambda_macro_in_body: "{% macro wrap(v) %}[{{ v }}]{% end macro %}{{ ['a', 'b'] | map(x => wrap(x)) | join }}" == "[a]\n[b]";
+    !test_propagates_errors: "{{ ('a' + 1) is defined }}";
+}
+
+#[test]
+fn filter_block_unknown_filter() {
+    let result = Templar::global().parse_template("{% filter nope %}x{% end filter %}");
+    assert!(matches!(result, Err(TemplarError::FilterNotFound(name)) if name == "nope"));
+}
+
+#[test]
+fn macro_call_block_requires_macro() {
+    let result = Templar::global().parse_template("{% call env('HOME') %}x{% end call %}");
+    assert!(matches!(result, Err(TemplarError::ParseFailure(_))));
+}
+
+#[test]
+fn macro_caller_without_call_block() -> Result<()> {
+    let tmpl = Templar::global()
+        .parse_template("{% macro m() %}{{ caller() }}{% end macro %}{{ m() }}")?;
+    match tmpl.render(&StandardContext::new()) {
+        Err(TemplarError::RenderFailure(message)) => assert_eq!(
+            message,
+            "caller() can only be used in a macro that is invoked with a call block"
+        ),
+        other => panic!("expected caller() to fail, got {:?}", other),
+    }
+    Ok(())
+}
+
+#[test]
+fn macro_must_be_defined_before_use() {
+    let result = Templar::global().parse_template("{{ m() }}{% macro m() %}x{% end macro %}");
+    assert!(matches!(result, Err(TemplarError::FunctionNotFound(name)) if name == "m"));
+}
+
+#[test]
+fn test_unknown_test() {
+    let result = Templar::global().parse_template("{{ 1 is nope }}");
+    assert!(matches!(result, Err(TemplarError::TestNotFound(name)) if name == "nope"));
+}
+
+#[test]
+fn test_custom() -> Result<()> {
+    let mut builder = TemplarBuilder::default();
+    builder.add_test("positive", |value, _| {
+        Ok(value.inner_data().clone().cast::<i64>().unwrap_or_default() > 0)
+    });
+    let templar = builder.build();
+    let tmpl = templar.parse_template("{{ 5 is positive }},{{ -5 is not positive }}")?;
+    assert_eq!(tmpl.render(&StandardContext::new())?, "true,true");
+    Ok(())
+}
+
+#[test]
+fn macro_named_args_unsupported() {
+    let result =
+        Templar::global().parse_template("{% macro m(a) %}{{ a }}{% end macro %}{{ m(a=1) }}");
+    assert!(matches!(result, Err(TemplarError::ParseFailure(_))));
+}
+
+#[test]
+fn lambda_duplicate_params() {
+    let result = Templar::global().parse_template("{{ [1] | reduce((a, a) => a) }}");
+    assert!(matches!(result, Err(TemplarError::ParseFailure(_))));
+}
+
+#[test]
+fn strict_bool_if() -> Result<()> {
+    let mut builder = TemplarBuilder::default();
+    builder.set_strict_bool(true);
+    let templar = builder.build();
+    let context = StandardContext::new();
+    let tmpl = templar.parse_template("{% if 1 == 1 %}yes{% end if %}")?;
+    assert_eq!(tmpl.render(&context)?, "yes");
+    let tmpl = templar.parse_template("{% if 'a' %}yes{% end if %}")?;
+    assert!(tmpl.render(&context).is_err());
+    Ok(())
+}
+
+#[test]
+fn undefined_policy_strict() -> Result<()> {
+    let mut builder = TemplarBuilder::default();
+    builder.set_undefined_policy(UndefinedPolicy::Strict);
+    let templar = builder.build();
+    let context = StandardContext::new();
+    context.set_path(&[&"a".into(), &"b".into()], 1)?;
+    let render = |tpl: &str| templar.parse_template(tpl)?.render(&context);
+    assert_eq!(render("{{ a.b }}{{ x = 2 }}{{ x }}")?, "12");
+    assert_eq!(
+        render("{{ a.c.d is defined }},{{ a.c | default('d') }}{{ a.c | default('') }}{{ '' }}")?,
+        "false,d"
+    );
+    assert_eq!(render("{{ [1] | map(n => n) | join }}")?, "1");
+    let undefined = |tpl: &str| match render(tpl) {
+        Err(TemplarError::UndefinedValue(path)) => path,
+        other => panic!("expected an undefined value error, got {:?}", other),
+    };
+    assert_eq!(undefined("{{ a.x.y }}"), "a.x.y");
+    assert_eq!(undefined("{{ a.b ~ a.c }}"), "a.c");
+    assert_eq!(undefined("{{ a.c | upper }}"), "a.c");
+    assert_eq!(
+        undefined("{% for i in [{'a': 1}] %}{{ i.missing }}{% end for %}"),
+        "i.missing"
+    );
+    // Undefined values fail wherever they are read, not only when the block renders empty
+    assert_eq!(undefined("{{ missing | length }}"), "missing");
+    assert_eq!(undefined("{{ [missing] }}"), "missing");
+    assert_eq!(undefined("{{ missing || 'x' }}"), "missing");
+    assert_eq!(undefined("{{ [{}] | map(i => i.missing) }}"), "i.missing");
+    #[cfg(feature = "json-extension")]
+    assert_eq!(undefined("{{ missing | json }}"), "missing");
+    assert_eq!(
+        render("{{ missing | exists }},{{ missing is undefined }},{% if missing %}x{% end if %}")?,
+        "false,true,"
+    );
+    Ok(())
+}
+
+#[test]
+fn undefined_policy_debug() -> Result<()> {
+    let mut builder = TemplarBuilder::default();
+    builder.set_undefined_policy(UndefinedPolicy::Debug);
+    let templar = builder.build();
+    let tmpl = templar.parse_template("host: {{ server.host }}, {{ 'port: ' ~ server.port }}")?;
+    assert_eq!(
+        tmpl.render(&StandardContext::new())?,
+        "host: {{ undefined: server.host }}, port: {{ undefined: server.port }}"
+    );
+    Ok(())
+}
+
+#[test]
+fn undefined_policy_lenient() -> Result<()> {
+    let tmpl = Templar::global().parse_template("host: {{ server.host }}")?;
+    assert_eq!(tmpl.render(&StandardContext::new())?, "host: ");
+    Ok(())
+}
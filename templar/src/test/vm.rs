@@ -0,0 +1,173 @@
+use super::*;
+use std::iter::FromIterator;
+
+/// Templates covering every instruction of the compiled form, along with the operations that are
+/// handed back to the tree walker
+pub(super) const TEMPLATES: &[&str] = &[
+    "plain text",
+    "",
+    "{{ name }}",
+    "Hello {{ name }}, you are {{ age }}",
+    "{{ user.name }} {{ user.missing }} {{ missing.deeper }}",
+    "{{ items }}|{{ items | index(1) }}|{{ user }}",
+    "{{ age + 1 }} {{ age - 1 }} {{ age * 2 }} {{ age / 5 }} {{ age % 7 }}",
+    "{{ age / 7 + 1 }} {{ age % 7 * 2 - 1 }}",
+    "{{ name + 1 }}",
+    "{{ missing + 1 }}|{{ 1 + missing }}",
+    "{{ (name + 1) ~ 'x' }}",
+    "{{ age == 30 }} {{ age != 30 }} {{ age > 2 }} {{ age < 2 }}",
+    "{{ name == 'Bob' && age > 10 }} {{ yes && no }} {{ no || yes }} {{ no || empty }}",
+    "{{ missing && (name + 1) }} {{ yes || (name + 1) }} {{ (name + 1) || yes }}",
+    "{{ yes && (name + 1) }}",
+    "{{ name && yes }} {{ yes && 1 }}",
+    "{{ !yes }} {{ !no }} {{ !missing }} {{ !(name + 1) }}",
+    "{{ !!yes }} {{ !name }}",
+    "{% if yes %}a{% else %}b{% end if %}{% if no %}c{% else if age > 3 %}d{% end if %}",
+    "{% if missing %}a{% end if %}{% if items %}b{% end if %}{% if empty %}c{% end if %}",
+    "{% if name + 1 %}a{% else %}b{% end if %}",
+    "{{ name | upper }} {{ name | lower | trim }} {{ items | length }} {{ items | join(', ') }}",
+    "{{ name | replace('o', '0') | upper }} {{ missing | default('d') }}",
+    "{{ (name + 1) | upper }} {{ name | key('a') }}",
+    "{{ user | key('name') | upper }}",
+    "{{ env('TEMPLAR_VM_MISSING') }}|{{ json('[1, 2]') | length }}",
+    "{{ age is odd }} {{ age is not odd }} {{ name is empty }} {{ missing is empty }}",
+    "{{ (name + 1) is empty }}",
+    "{% for i in items %}[{{ i }}]{% end for %}",
+    "{% for i in items %}{% for j in items %}{{ i }}{{ j }},{% end for %}{% end for %}",
+    "{% for e in user %}{{ e.key }}={{ e.value }};{% end for %}",
+    "{% for i in [] %}x{% end for %}|{% for i in name %}{{ i }}!{% end for %}",
+    "{% for i in missing %}[{{ i }}]{% end for %}",
+    "{% for i in items %}{{ i + 'x' }}{% end for %}",
+    "{% for i in (name + 1) %}{{ i }}{% end for %}",
+    "{% for i in items %}{% if i == 2 %}two{% else %}{{ i }}{% end if %}{% end for %}",
+    "{% for user in [user, {'name': 'Al'}] %}{{ user.name }}/{{ user.id }}/{{ user }};{% end for %}",
+    "{% for name in items %}{{ name }}{{ name.x.y }}{% end for %}",
+    "{% for i in items %}{% for i in [i, 'x'] %}{{ i }}{% end for %}{{ i }}{% end for %}",
+    "{% for i in items %}{% if i > 1 %}{% for j in items %}{{ i * j }}{% end for %}{% end if %}{% end for %}",
+    "{% for e in user %}{% for x in items %}{{ e.key }}{{ x }}{{ e.value.z }}{% end for %}{% end for %}",
+    "{% for i in [[1, name + 1], 2] %}{{ i }}{% end for %}",
+    "{% for i in [{'a': [name + 1]}] %}{{ i.b }}{% end for %}",
+    "{% for i in items %}{{ . }}{% end for %}",
+    "{% for i in items %}{{ x = i }}{{ i }}{% end for %}{{ x }}",
+    "{% for i in items %}{% for j in i.k %}{{ j }}{% end for %}{% end for %}",
+    "{% for i in items %}[{{ shows_i }}]{% end for %}",
+    "{% for i in items %}{% for j in [i] %}{{ j }}{{ shows_i }}{% end for %}{% end for %}",
+    "{{ [1, age, name] }} {{ [] }} {{ [name + 1] }}",
+    "{{ {'a': age, 'b': [name]} }} {{ {'a': name + 1} }} {{ {'x': 1} | key('x') }}",
+    "{{ 'a' ~ name ~ age }} {{ name ~ (age + 1) }}",
+    "{{ x = 5 }}{{ x }}{{ user.name = 'Al' }}{{ user.name }}",
+    "{% scope %}{{ name = 'inner' }}{{ name }}{% end scope %}{{ name }}",
+    "{% for i in items %}{{ last = i }}{% end for %}{{ last }}",
+    "{% filter upper %}hello {{ name }}{% end filter %}",
+    "{% filter upper %}{{ name + 1 }}{% end filter %}",
+    "{% macro greet(who, punct='!') %}Hi {{ who }}{{ punct }}{% end macro %}{{ greet(name) }} {{ greet('Al', '?') }}",
+    "{% macro wrap() %}<{{ caller() }}>{% end macro %}{% call wrap() %}{{ name }}{% end call %}",
+    "{{ items | map(x => x * 2) | join(',') }} {{ items | select(x => x > 1) }}",
+    "{% include 'header.tmpl' %} {% include 'item.tmpl' with {'item': age} %}",
+    "{% include 'setter.tmpl' %} {{ name }}",
+    "{% include 'missing.tmpl' ignore missing %}",
+    "{{ age }}{{ name + 1 }}{{ x = 1 }}",
+    "{{ 1 }} {{ 'a' }} {{ true }} {{ null }}",
+];
+
+pub(super) fn templar(policy: UndefinedPolicy, strict_bool: bool) -> Templar {
+    let mut builder = TemplarBuilder::default();
+    builder
+        .set_undefined_policy(policy)
+        .set_strict_bool(strict_bool)
+        .add_loader(MemoryLoader::from_iter(vec![
+            ("header.tmpl", "# {{ name | upper }}"),
+            ("item.tmpl", "- {{ item }}"),
+            ("setter.tmpl", "{{ name = 'changed' }}{{ name }}"),
+        ]));
+    builder.build()
+}
+
+pub(super) fn context() -> Result<StandardContext> {
+    let context = StandardContext::new();
+    context.set({
+        let mut doc = InnerData::default();
+        doc["name"] = "Bob".into();
+        doc["age"] = 30i64.into();
+        doc["yes"] = true.into();
+        doc["no"] = false.into();
+        doc["empty"] = "".into();
+        doc["items"] = InnerData::Seq(vec![1i64.into(), 2i64.into(), 3i64.into()]);
+        doc["user"]["name"] = "Bob".into();
+        doc["user"]["id"] = 7i64.into();
+        doc
+    })?;
+    // A dynamic value that reads a loop variable from the context
+    context.set_path(&[&"shows_i".into()], Templar::global().parse("{{ i }}")?)?;
+    Ok(context)
+}
+
+/// The result of rendering, with errors compared by their message
+fn outcome(result: Result<String>) -> std::result::Result<String, String> {
+    result.map_err(|e| format!("{:?}", e))
+}
+
+fn assert_same(templar: &Templar, source: &str) -> Result<()> {
+    let template = templar.parse_template(source)?;
+    let compiled = template.compile();
+    let (tree_ctx, vm_ctx) = (context()?, context()?);
+    for _ in 0..2 {
+        let expected = outcome(template.render(&tree_ctx));
+        let actual = outcome(compiled.render(&vm_ctx));
+        assert_eq!(actual, expected, "{:?} rendered differently", source);
+    }
+    assert_eq!(
+        format!("{:?}", vm_ctx.get()),
+        format!("{:?}", tree_ctx.get()),
+        "{:?} left a different context",
+        source
+    );
+    Ok(())
+}
+
+#[test]
+fn vm_matches_tree_walker() -> Result<()> {
+    for (policy, strict_bool) in &[
+        (UndefinedPolicy::Lenient, false),
+        (UndefinedPolicy::Strict, false),
+        (UndefinedPolicy::Debug, false),
+        (UndefinedPolicy::Lenient, true),
+    ] {
+        let templar = templar(*policy, *strict_bool);
+        for source in TEMPLATES.iter() {
+            assert_same(&templar, source)?;
+        }
+    }
+    Ok(())
+}
+
+#[test]
+fn vm_matches_tree_walker_for_expressions() -> Result<()> {
+    let templar = templar(UndefinedPolicy::Lenient, false);
+    for source in &[
+        "age * 2 + 1",
+        "items",
+        "user.name | upper",
+        "[age, {'a': name}]",
+    ] {
+        let template = templar.parse_expression(source)?;
+        let context = context()?;
+        assert_eq!(
+            format!("{:?}", template.compile().exec(&context)),
+            format!("{:?}", template.exec(&context)),
+            "{:?} executed differently",
+            source
+        );
+    }
+    Ok(())
+}
+
+#[test]
+fn vm_compiles_without_fallback() -> Result<()> {
+    let template = Templar::global()
+        .parse("{% for i in items %}{% if i > 1 %}{{ i | upper }}{% end if %}{% end for %}")?;
+    let listing = format!("{:?}", template.compile());
+    assert!(!listing.contains("Exec"), "{}", listing);
+    assert!(listing.contains("Register"), "{}", listing);
+    Ok(())
+}
@@ -0,0 +1,75 @@
+use crate::*;
+
+macro_rules! type_tests {
+    ($( $name:ident : $( $pattern:pat )|+ , )*) => {
+        $(
+            pub fn $name(value: &Data, _: Data) -> Result<bool> {
+                Ok(matches!(value.inner_data(), $( $pattern )|+))
+            }
+        )*
+    };
+}
+
+type_tests! {
+    none: InnerData::Null | InnerData::Option(None),
+    number: InnerData::Number(_),
+    string: InnerData::String(_) | InnerData::Char(_),
+    boolean: InnerData::Bool(_),
+    mapping: InnerData::Map(_),
+    sequence: InnerData::Seq(_),
+    iterable: InnerData::Seq(_) | InnerData::Map(_) | InnerData::String(_),
+}
+
+pub fn defined(value: &Data, _: Data) -> Result<bool> {
+    Ok(!value.is_empty())
+}
+
+pub fn undefined(value: &Data, _: Data) -> Result<bool> {
+    Ok(value.is_empty())
+}
+
+pub fn integer(value: &Data, _: Data) -> Result<bool> {
+    Ok(matches!(value.inner_data(), InnerData::Number(n) if !n.is_float()))
+}
+
+pub fn float(value: &Data, _: Data) -> Result<bool> {
+    Ok(matches!(value.inner_data(), InnerData::Number(n) if n.is_float()))
+}
+
+pub fn empty(value: &Data, _: Data) -> Result<bool> {
+    Ok(match value.inner_data() {
+        InnerData::Unassigned | InnerData::Null | InnerData::Option(None) => true,
+        InnerData::String(s) => s.is_empty(),
+        InnerData::Seq(s) => s.is_empty(),
+        InnerData::Map(m) => m.is_empty(),
+        _ => false,
+    })
+}
+
+pub fn even(value: &Data, _: Data) -> Result<bool> {
+    Ok(as_integer("even", value.inner_data())? % 2 == 0)
+}
+
+pub fn odd(value: &Data, _: Data) -> Result<bool> {
+    Ok(as_integer("odd", value.inner_data())? % 2 != 0)
+}
+
+pub fn divisibleby(value: &Data, args: Data) -> Result<bool> {
+    let divisor = as_integer("divisibleby", args.inner_data())?;
+    if divisor == 0 {
+        return Err(TemplarError::RenderFailure(
+            "Test 'divisibleby' requires a non-zero divisor".into(),
+        ));
+    }
+    // Only `i64::MIN % -1` overflows, and it divides evenly
+    let value = as_integer("divisibleby", value.inner_data())?;
+    Ok(value.checked_rem(divisor).is_none_or(|rem| rem == 0))
+}
+
+fn as_integer(test: &str, value: &InnerData) -> Result<i64> {
+    match value {
+        InnerData::Number(n) if !n.is_float() => value.clone().cast::<i64>(),
+        _ => None,
+    }
+    .ok_or_else(|| TemplarError::RenderFailure(format!("Test '{}' requires an integer", test)))
+}
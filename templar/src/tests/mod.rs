@@ -0,0 +1,71 @@
+/*!
+Templar tests
+
+Tests are used to check a property of the result of an expression, producing a boolean.
+
+# Overview
+
+As an example, the expression `{{ user.name is defined }}` uses the "defined" test to check whether
+the value has been set in the context. Tests can be negated with `is not`, e.g. `{{ 5 is not even }}`,
+and can take arguments in the same way as filters, e.g. `{{ 10 is divisibleby(5) }}`.
+
+Like filters, a test applies to the entire expression before it.
+
+# Built in tests
+
+- defined: True if the value has been set
+- undefined: True if the value has not been set
+- none: True if the value is null
+- number: True if the value is any numeric type
+- integer: True if the value is a whole number
+- float: True if the value is a floating point number
+- string: True if the value is a string
+- boolean: True if the value is a boolean
+- mapping: True if the value is a map
+- sequence: True if the value is an array
+- iterable: True if the value is an array, map or string
+- empty: True if the value is unset, null, an empty string, an empty array or an empty map
+- even: True if the value is an even integer
+- odd: True if the value is an odd integer
+- divisibleby(int): True if the value is an integer evenly divisible by the argument
+*/
+
+mod common;
+
+use crate::*;
+use std::collections::HashMap;
+
+/// This is the definition used when adding tests to Templar. The first parameter is the value
+/// being tested and the second holds any arguments.
+pub type Test = dyn Fn(&Data, Data) -> Result<bool> + Send + Sync;
+
+macro_rules! builtin_tests {
+    ($( $( #[ $attr:meta ] )* $name:literal : $method:path ; )*) => {
+        pub(crate) fn default_tests() -> HashMap<String, Arc<Test>> {
+            let mut res = HashMap::new();
+            $(
+                $( #[ $attr ] )*
+                res.insert($name.into(), Arc::new($method) as Arc<Test>);
+            )*
+            res
+        }
+    };
+}
+
+builtin_tests! {
+    "defined": common::defined;
+    "undefined": common::undefined;
+    "none": common::none;
+    "number": common::number;
+    "integer": common::integer;
+    "float": common::float;
+    "string": common::string;
+    "boolean": common::boolean;
+    "mapping": common::mapping;
+    "sequence": common::sequence;
+    "iterable": common::iterable;
+    "empty": common::empty;
+    "even": common::even;
+    "odd": common::odd;
+    "divisibleby": common::divisibleby;
+}
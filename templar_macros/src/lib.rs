@@ -7,6 +7,7 @@ extern crate proc_macro;
 // use quote::*;
 extern crate proc_macro2;
 
+#[allow(dead_code)]
 pub(crate) mod attr;
 mod transforms;
 
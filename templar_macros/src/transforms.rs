@@ -128,22 +128,16 @@ fn safe_unwrap(var: FnArg, var_name: String, dt: DataType) -> TokenStream2 {
 }
 
 fn arg_condition(args: &[FnArg], args_name: &str) -> TokenStream2 {
-    if args.len() > 1 {
-        let args_name = Ident::new(args_name, args[0].span());
-        let arg_count: LitInt = LitInt::new(&args.len().to_string(), args[0].span());
-        quote! {
-            let mut #args_name : Vec<InnerData> = match #args_name .into_inner() {
-                InnerData::Seq(val) => val,
-                InnerData::Err(e) => return e.into(),
-                _ => return TemplarError::RenderFailure("Unexpected execution, arguments must be a sequence".to_string()).into(),
-            };
-
-            if #args_name .len() != #arg_count {
-                return TemplarError::RenderFailure(format!("This method expects {} arguments", #arg_count)).into();
-            }
-        }
-    } else {
-        quote! {}
+    if args.is_empty() {
+        return quote! {};
+    }
+    let args_name = Ident::new(args_name, args[0].span());
+    let arg_count: LitInt = LitInt::new(&args.len().to_string(), args[0].span());
+    quote! {
+        let mut #args_name = match #args_name .into_arguments(#arg_count) {
+            Ok(val) => val,
+            Err(e) => return e.into(),
+        };
     }
 }
 
@@ -193,53 +187,43 @@ fn push_set_variables(
     data_types: &[DataType],
     args: &[FnArg],
 ) {
-    match args.len() {
-        l if l > 1 => {
-            let args_name = Ident::new(args_name, args[0].span());
-            for (arg, data_type) in args.iter().zip(data_types.iter()) {
-                let dty = data_type.type_token();
-                let do_render = data_type.do_render();
-                if do_render {
-                    target.push(quote! {
-                        let #arg = match Data::new(#args_name .remove(0)).render() {
-                            Ok(val) => val,
-                            Err(e) => return e.into(),
-                        };
-                    });
-                } else {
-                    target.push(quote! {
-                        let #arg = match #args_name .remove(0) {
-                            #dty (val) => val.into(),
-                            _ => return TemplarError::RenderFailure(format!("Unexpected type in argument")).into(),
-                        };
-                    });
-                }
-            }
-        }
-        l if l == 1 => {
-            let args_name = Ident::new(&args_name, args[0].span());
-            let arg = args.first().unwrap();
-            let dty = data_types.first().unwrap();
-            let do_render = dty.do_render();
-            let dty = dty.type_token();
-            if do_render {
-                target.push(quote! {
-                    let #arg = match #args_name .render() {
-                        Ok(val) => val,
-                        Err(e) => return e.into(),
-                    };
-                });
-            } else {
-                target.push(quote! {
-                    let #arg = match #args_name .into_inner() {
-                        #dty (val) => val.into(),
-                        _ => return TemplarError::RenderFailure(format!("Unexpected type in argument")).into(),
-                    };
-                });
-            }
+    if args.is_empty() {
+        return;
+    }
+    let args_name = Ident::new(args_name, args[0].span());
+    for (index, (arg, data_type)) in args.iter().zip(data_types.iter()).enumerate() {
+        let param_name = data_type.name_token().to_string();
+        let dty = data_type.type_token();
+        let index: LitInt = LitInt::new(&index.to_string(), arg.span());
+        target.push(quote! {
+            let value = match #args_name .take(#index, #param_name) {
+                Ok(Some(val)) => val,
+                Ok(None) => return TemplarError::RenderFailure(format!("Missing value for argument '{}'", #param_name)).into(),
+                Err(e) => return e.into(),
+            };
+        });
+        if data_type.do_render() {
+            target.push(quote! {
+                let #arg = match Data::new(value).render() {
+                    Ok(val) => val,
+                    Err(e) => return e.into(),
+                };
+            });
+        } else {
+            target.push(quote! {
+                let #arg = match value {
+                    #dty (val) => val.into(),
+                    _ => return TemplarError::RenderFailure(format!("Unexpected type in argument '{}'", #param_name)).into(),
+                };
+            });
         }
-        _ => {}
     }
+    let arg_count: LitInt = LitInt::new(&args.len().to_string(), args[0].span());
+    target.push(quote! {
+        if let Err(e) = #args_name .finish(#arg_count) {
+            return e.into();
+        }
+    });
 }
 
 pub fn impl_filter(item_fn: &syn::ItemFn) -> TokenStream {